@@ -3,6 +3,7 @@ use std::path::PathBuf;
 
 fn main() {
     ensure_seccomp_sources_fetched();
+    set_tokio_task_dump_cfg();
 
     #[cfg(feature = "security")]
     security::build()
@@ -21,6 +22,29 @@ fn ensure_seccomp_sources_fetched() {
     }
 }
 
+/// Sets the `foundations_tokio_task_dump` cfg if the `tokio-task-dump` feature is enabled, tokio
+/// task dumps are opted into with `--cfg tokio_taskdump` and they are supported for the target, so
+/// the condition is not repeated in the code.
+fn set_tokio_task_dump_cfg() {
+    println!("cargo:rustc-check-cfg=cfg(foundations_tokio_task_dump)");
+
+    // NOTE: `#[cfg(...)]` gates are about the host machine in build scripts, so the target cfgs
+    // (including the ones passed with `RUSTFLAGS`) are obtained from the environment variables.
+    let has_cfg = |name: &str| env::var_os(format!("CARGO_CFG_{name}")).is_some();
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+
+    if env::var_os("CARGO_FEATURE_TOKIO_TASK_DUMP").is_some()
+        && has_cfg("TOKIO_UNSTABLE")
+        && has_cfg("FOUNDATIONS_UNSTABLE")
+        && has_cfg("TOKIO_TASKDUMP")
+        && target_os == "linux"
+        && matches!(target_arch.as_str(), "aarch64" | "x86" | "x86_64" | "s390x")
+    {
+        println!("cargo:rustc-cfg=foundations_tokio_task_dump");
+    }
+}
+
 #[cfg(feature = "security")]
 mod security {
     use super::*;
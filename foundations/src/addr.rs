@@ -1,7 +1,10 @@
 //! Network address types that support both TCP and Unix domain sockets.
 //!
 //! This module provides the [`ListenAddr`] enum, a flexible address type that can represent
-//! either TCP socket addresses or Unix domain socket paths.
+//! either TCP socket addresses or Unix domain socket paths, and the [`InheritedListener`] type
+//! for listening sockets inherited from the parent process (e.g. with [systemd socket activation]).
+//!
+//! [systemd socket activation]: https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
 
 #[cfg(feature = "settings")]
 use crate::settings::Settings;
@@ -9,8 +12,22 @@ use crate::settings::Settings;
 use serde::Deserialize;
 #[cfg(feature = "settings")]
 use serde::Serialize;
+#[cfg(unix)]
+use socket2::SockRef;
 use std::fmt;
 use std::net::{Ipv4Addr, SocketAddr};
+#[cfg(unix)]
+use std::os::fd::{BorrowedFd, FromRawFd, OwnedFd, RawFd};
+#[cfg(unix)]
+use std::sync::Mutex;
+
+/// The first file descriptor passed by systemd socket activation.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Inherited file descriptors that have already been taken by [`InheritedListener::take_fd`].
+#[cfg(unix)]
+static TAKEN_FDS: Mutex<Vec<RawFd>> = Mutex::new(Vec::new());
 
 /// Address that can be either TCP socket or Unix domain socket endpoint
 #[derive(Clone, Debug)]
@@ -69,3 +86,224 @@ impl fmt::Display for ListenAddr {
 
 #[cfg(feature = "settings")]
 impl Settings for ListenAddr {}
+
+/// Listening socket inherited from the parent process.
+///
+/// Inherited sockets allow services to use [systemd socket activation] or to listen on
+/// privileged ports that have been bound before the privileges have been dropped.
+///
+/// In the settings, inherited sockets are specified as a map with a single key, e.g. for the
+/// telemetry server [`inherited_listener`]:
+/// ```yaml
+/// # Socket passed by systemd with `FileDescriptorName=telemetry` in the socket unit.
+/// inherited_listener:
+///   systemd: telemetry
+/// ---
+/// # Explicit file descriptor number.
+/// inherited_listener:
+///   fd: 3
+/// ```
+///
+/// [`inherited_listener`]: crate::telemetry::settings::TelemetryServerSettings::inherited_listener
+///
+/// [systemd socket activation]: https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
+#[cfg(unix)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    any(feature = "telemetry-server", feature = "settings"),
+    derive(Deserialize)
+)]
+#[cfg_attr(feature = "settings", derive(Serialize))]
+#[cfg_attr(
+    any(feature = "telemetry-server", feature = "settings"),
+    serde(rename_all = "snake_case")
+)]
+pub enum InheritedListener {
+    /// File descriptor number of the listening socket.
+    Fd(RawFd),
+    /// Name of the socket passed with systemd socket activation.
+    ///
+    /// The name is looked up in the `LISTEN_FDNAMES` environment variable and is specified with
+    /// the `FileDescriptorName=` option of the socket unit. Sockets without an explicit name are
+    /// named `unknown` by systemd.
+    Systemd(String),
+}
+
+#[cfg(unix)]
+impl Default for InheritedListener {
+    fn default() -> Self {
+        InheritedListener::Fd(SD_LISTEN_FDS_START)
+    }
+}
+
+#[cfg(all(unix, feature = "settings"))]
+impl Settings for InheritedListener {}
+
+#[cfg(unix)]
+impl InheritedListener {
+    /// Takes ownership of the inherited socket's file descriptor.
+    ///
+    /// Each inherited socket can be taken only once: consequent calls return an error. The
+    /// returned file descriptor has the close-on-exec flag set, so it doesn't leak to
+    /// the child processes.
+    ///
+    /// Returns an error without closing the file descriptor if it's not a listening socket.
+    ///
+    /// This can be used to obtain inherited service listeners, the same way as foundations
+    /// does for the telemetry server.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use foundations::addr::InheritedListener;
+    /// use std::net::TcpListener;
+    ///
+    /// let listener = TcpListener::from(
+    ///     InheritedListener::Systemd("http".into()).take_fd().unwrap()
+    /// );
+    /// ```
+    pub fn take_fd(&self) -> std::io::Result<OwnedFd> {
+        let fd = match self {
+            InheritedListener::Fd(fd) => *fd,
+            InheritedListener::Systemd(name) => systemd_listen_fds()
+                .into_iter()
+                .find_map(|(fd_name, fd)| (fd_name == *name).then_some(fd))
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("no socket named {name:?} has been passed by systemd"),
+                    )
+                })?,
+        };
+
+        let mut taken_fds = TAKEN_FDS.lock().unwrap_or_else(|e| e.into_inner());
+
+        if taken_fds.contains(&fd) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("inherited file descriptor {fd} has already been taken"),
+            ));
+        }
+
+        // NOTE: the file descriptor is closed below, so make sure that it's a listening socket
+        // first. Otherwise, a misconfigured file descriptor number could close a file that the
+        // process uses (e.g. the log file). Systemd sockets are always within the `LISTEN_FDS`
+        // range, as they are looked up from it.
+        validate_listener(fd)?;
+
+        // NOTE: duplicating the file descriptor sets the close-on-exec flag that inherited file
+        // descriptors usually don't have.
+        let owned_fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+
+        // SAFETY: the file descriptor is an open socket and it hasn't been taken before.
+        drop(unsafe { OwnedFd::from_raw_fd(fd) });
+
+        taken_fds.push(fd);
+
+        Ok(owned_fd)
+    }
+}
+
+/// Checks that the file descriptor is an open listening socket, without taking ownership of it.
+#[cfg(unix)]
+fn validate_listener(fd: RawFd) -> std::io::Result<()> {
+    let invalid = |reason: String| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("inherited file descriptor {fd} {reason}"),
+        )
+    };
+
+    if fd < 0 {
+        return Err(invalid("is negative".into()));
+    }
+
+    // SAFETY: the file descriptor is only borrowed for the duration of the checks, which
+    // fail without side effects if it's not open.
+    let borrowed_fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    let socket = SockRef::from(&borrowed_fd);
+
+    // NOTE: fails with `ENOTSOCK` for other kinds of files, and with `EBADF` if it's not open.
+    if let Err(e) = socket.local_addr() {
+        return Err(invalid(format!("is not an open socket: {e}")));
+    }
+
+    #[cfg(any(target_os = "android", target_os = "freebsd", target_os = "linux"))]
+    if !socket.is_listener()? {
+        return Err(invalid("is not a listening socket".into()));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+impl fmt::Display for InheritedListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InheritedListener::Fd(fd) => write!(f, "fd:{fd}"),
+            InheritedListener::Systemd(name) => write!(f, "systemd:{name}"),
+        }
+    }
+}
+
+/// Returns the names and file descriptors of the sockets passed with [systemd socket activation].
+///
+/// [systemd socket activation]: https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
+#[cfg(unix)]
+pub fn systemd_listen_fds() -> Vec<(String, RawFd)> {
+    let var = |name| std::env::var(name).ok();
+
+    parse_systemd_listen_fds(
+        std::process::id(),
+        var("LISTEN_PID").as_deref(),
+        var("LISTEN_FDS").as_deref(),
+        var("LISTEN_FDNAMES").as_deref(),
+    )
+}
+
+#[cfg(unix)]
+fn parse_systemd_listen_fds(
+    pid: u32,
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    listen_fdnames: Option<&str>,
+) -> Vec<(String, RawFd)> {
+    // NOTE: the variables are meant for the process started by systemd, but they might have been
+    // inherited by its child processes.
+    if listen_pid.and_then(|p| p.parse().ok()) != Some(pid) {
+        return vec![];
+    }
+
+    let count = listen_fds.and_then(|n| n.parse().ok()).unwrap_or(0);
+    let mut names = listen_fdnames.unwrap_or_default().split(':');
+
+    (0..count)
+        .map(|i| {
+            let name = names.next().filter(|n| !n.is_empty()).unwrap_or("unknown");
+
+            (name.to_string(), SD_LISTEN_FDS_START + i)
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn systemd_listen_fds_with_names() {
+        assert_eq!(
+            parse_systemd_listen_fds(42, Some("42"), Some("3"), Some("http:telemetry")),
+            vec![
+                ("http".to_string(), 3),
+                ("telemetry".to_string(), 4),
+                ("unknown".to_string(), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn systemd_listen_fds_for_other_process() {
+        assert!(parse_systemd_listen_fds(42, Some("1"), Some("1"), None).is_empty());
+        assert!(parse_systemd_listen_fds(42, None, Some("1"), None).is_empty());
+    }
+}
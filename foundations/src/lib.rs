@@ -33,6 +33,14 @@
 //! - **client-telemetry**: Enables a subset of telemetry features suitable for usage in clients (e.g. on mobile devices).
 //! - **metrics**: Enables metrics functionality.
 //! - **logging**: Enables logging functionality.
+//! - **log-bridge**: Enables capturing the records of the [`log`] crate into the foundations
+//!   logger. Implicitly enables **logging** feature.
+//! - **tracing-bridge**: Enables capturing the events of the [`tracing`] crate into the
+//!   foundations logger. Implicitly enables **logging** feature.
+//! - **log-redaction**: Enables redaction of the sensitive data in log records by key and value
+//!   patterns. Implicitly enables **logging** feature.
+//! - **log-sampling**: Enables probabilistic sampling of log records. Implicitly enables
+//!   **logging** feature.
 //! - **tracing**: Enables distributed tracing functionality.
 //! - **ratelimit**: Enables helpers to simplify rate-limiting your code.
 //! - **testing**: Enables testing-related functionality.
@@ -44,6 +52,9 @@
 //!     nature of features.
 //! - **memory-profiling**: Enables memory profiling functionality and telemetry. Implicity enables
 //!   **jemalloc** feature.
+//! - **cpu-profiling**: Enables sampling CPU profiling functionality and telemetry.
+//! - **thread-dump**: Enables native stack dumps of all the process threads and the
+//!   `/debug/threads` telemetry server endpoint. Implicitly enables **logging** feature.
 //! - **cli**: Enables command line interface (CLI) functionality. Implicitly enabled **settings**
 //!   feature.
 //!
@@ -51,6 +62,10 @@
 //! Foundations has unstable features which are gated behind `--cfg foundations_unstable`:
 //!
 //! - **tokio-runtime-metrics**: Enables runtime metrics for Tokio runtimes. Implicitly enables the **metrics** feature. [Also requires tokio_unstable](https://docs.rs/tokio/latest/tokio/#unstable-features).
+//! - **tokio-task-dump**: Enables the `/debug/tasks` telemetry server endpoint with the task dumps of the runtimes registered for
+//!   **tokio-runtime-metrics**. Available only on Linux (x86, x86_64, aarch64, s390x). Also requires tokio_unstable and
+//!   `--cfg tokio_taskdump`, which enables tokio's `taskdump` feature. It's opt-in, as task dumps add overhead to every task
+//!   poll, and the feature has no effect without it.
 //!
 //! To enable these, you must add `--cfg foundations_unstable` to your RUSTFLAGS environment variable.
 //!
@@ -60,6 +75,8 @@
 //! [examples]: https://github.com/cloudflare/foundations/tree/main/examples
 //! [OpenTelemetry]: https://opentelemetry.io/
 //! [gRPC]: https://grpc.io/
+//! [`log`]: https://docs.rs/log
+//! [`tracing`]: https://docs.rs/tracing
 //! [`settings`]: crate::settings::Settings
 #![warn(missing_docs)]
 #![cfg_attr(foundations_docsrs, feature(doc_cfg))]
@@ -87,6 +104,7 @@ pub mod settings;
     feature = "metrics",
     feature = "tracing",
     feature = "memory-profiling",
+    feature = "cpu-profiling",
     feature = "telemetry-server",
 ))]
 pub mod telemetry;
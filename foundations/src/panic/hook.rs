@@ -39,6 +39,8 @@ fn log_panic(panic_info: &PanicHookInfo<'_>) {
     // Use foundations logging if telemetry is initialized
     #[cfg(feature = "logging")]
     if crate::telemetry::is_initialized() {
+        crate::telemetry::log::flush_flight_recorder();
+
         let _ = crate::telemetry::log::internal::log_to_drain(&slog::record!(
             slog::Level::Error,
             "", // tag
@@ -100,9 +102,10 @@ mod logging_tests {
     #[tokio::test]
     async fn hook_swallows_drain_error() {
         let settings = LoggingSettings::default();
-        let root_drain = wrap_root_drain(&settings, FailingDrain);
+        let root_drain = wrap_root_drain(&settings, FailingDrain).unwrap();
         let root_log = LoggerWithKvNestingTracking::new(build_log_with_drain(
-            settings.verbosity,
+            settings.verbosity.into(),
+            None,
             slog::o!(),
             Arc::clone(&root_drain),
         ));
@@ -112,6 +115,7 @@ mod logging_tests {
             root_drain,
             settings,
             log_scope_stack: Default::default(),
+            flight_recorder: None,
         })
         .unwrap();
 
@@ -147,3 +147,24 @@ allow_list! {
         uname
     ]
 }
+
+allow_list! {
+    /// An allow list of extra operations required for CPU profiling.
+    ///
+    /// The CPU profiler interrupts threads with the `SIGPROF` signal, so sandboxed threads need
+    /// to be able to return from the signal handler.
+    pub static CPU_PROFILING_EXTRAS = [
+        rt_sigreturn
+    ]
+}
+
+allow_list! {
+    /// An allow list of extra operations required for thread dumps.
+    ///
+    /// The thread dumper interrupts each thread with a realtime signal and checks in the
+    /// signal handler whether the thread is the one being captured.
+    pub static THREAD_DUMP_EXTRAS = [
+        rt_sigreturn,
+        gettid
+    ]
+}
@@ -0,0 +1,394 @@
+mod pprof;
+
+use self::pprof::ProfileBuilder;
+use super::settings::CpuProfilerSettings;
+use super::signal::install_signal_handler;
+use super::signal_stack::{StackSlot, interrupted_pc};
+use crate::{BootstrapError, BootstrapResult, Result};
+use anyhow::bail;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use once_cell::sync::OnceCell;
+use prost::Message as _;
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::ffi::{c_int, c_void};
+use std::io::Write as _;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+use std::{io, ptr, thread};
+use tokio::sync::{Mutex as AsyncMutex, oneshot};
+
+// TODO(once_cell_try): replace with `std::sync::OnceLock`
+static PROFILER: OnceCell<Option<CpuProfiler>> = OnceCell::new();
+static PROFILE_REQUEST_SENDER: OnceLock<AsyncMutex<mpsc::Sender<ProfileRequest>>> = OnceLock::new();
+
+// NOTE: the buffer and the in-flight counter are accessed from the signal handler, so they need
+// to be plain atomics that don't require any locking or allocations.
+static ACTIVE_SAMPLE_BUFFER: AtomicPtr<SampleBuffer> = AtomicPtr::new(ptr::null_mut());
+static SIGNAL_HANDLERS_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+const MAX_SAMPLING_FREQUENCY: u32 = 1000;
+const MAX_SAMPLES: usize = 1 << 16;
+
+// NOTE: prevent direct construction by the external code.
+#[derive(Copy, Clone)]
+struct Seal;
+
+/// A sampling CPU profiler that produces profiles in the [pprof] format.
+///
+/// The profiler periodically interrupts the threads that consume CPU time with the `SIGPROF`
+/// signal and captures their stacks. Profiles are gzip-compressed [pprof] protobuf messages that
+/// can be analyzed with `go tool pprof`.
+///
+/// [pprof]: https://github.com/google/pprof
+#[derive(Copy, Clone)]
+pub struct CpuProfiler {
+    max_duration: Duration,
+    _seal: Seal,
+}
+
+impl CpuProfiler {
+    /// Creates a new profiler with the given settings or returns a previously initialized
+    /// profiler ignoring the settings.
+    ///
+    /// Returns `None` if [`CpuProfilerSettings::enabled`] is set to `false`.
+    ///
+    /// # Syscall sandboxing
+    ///
+    /// If syscall sandboxing is being used (see [`crate::security`] for more details), the
+    /// profiler must be initialized prior to syscall sandboxing, so the signal handler and the
+    /// profiling thread are set up without the sandbox restrictions. Sandboxed threads also need
+    /// to be able to return from the signal handler, which is allowed by the
+    /// `CPU_PROFILING_EXTRAS` syscall allow list in [`crate::security::common_syscall_allow_lists`].
+    pub fn get_or_init_with(settings: &CpuProfilerSettings) -> BootstrapResult<Option<Self>> {
+        if !(1..=MAX_SAMPLING_FREQUENCY).contains(&settings.sampling_frequency) {
+            bail!(
+                "`sampling_frequency` value should be in the range [1, {MAX_SAMPLING_FREQUENCY}]"
+            );
+        }
+
+        PROFILER
+            .get_or_try_init(|| init_profiler(settings))
+            .copied()
+    }
+
+    /// Profiles the process for the given `duration` and returns a gzip-compressed [pprof]
+    /// profile.
+    ///
+    /// Only one profile can be collected at a time.
+    ///
+    /// # Examples
+    /// ```
+    /// use foundations::telemetry::CpuProfiler;
+    /// use foundations::telemetry::settings::CpuProfilerSettings;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let settings = CpuProfilerSettings {
+    ///         enabled: true,
+    ///         ..Default::default()
+    ///     };
+    ///
+    ///     let profiler = CpuProfiler::get_or_init_with(&settings)
+    ///         .unwrap()
+    ///         .expect("profiling should be enabled");
+    ///
+    ///     let profile = profiler.profile(Duration::from_millis(100)).await.unwrap();
+    ///
+    ///     // gzip magic bytes
+    ///     assert_eq!(profile[..2], [0x1f, 0x8b]);
+    /// }
+    /// ```
+    ///
+    /// [pprof]: https://github.com/google/pprof
+    pub async fn profile(&self, duration: Duration) -> Result<Vec<u8>> {
+        if duration > self.max_duration {
+            return Err(format!(
+                "requested profile duration exceeds the maximum of {}s",
+                self.max_duration.as_secs()
+            )
+            .into());
+        }
+
+        let Some(sender_mutex) = PROFILE_REQUEST_SENDER.get() else {
+            return Err("Profile request sender is not initialized".into());
+        };
+
+        // NOTE: we use tokio mutex here, so we can hold the lock across `await` points.
+        let Ok(sender_guard) = sender_mutex.try_lock() else {
+            return Err("profiling is already in progress".into());
+        };
+
+        let (response_sender, response_receiver) = oneshot::channel();
+
+        sender_guard.send(ProfileRequest {
+            duration,
+            response_sender,
+        })?;
+
+        response_receiver.await?
+    }
+}
+
+struct ProfileRequest {
+    duration: Duration,
+    response_sender: oneshot::Sender<Result<Vec<u8>>>,
+}
+
+fn init_profiler(settings: &CpuProfilerSettings) -> BootstrapResult<Option<CpuProfiler>> {
+    if !settings.enabled {
+        return Ok(None);
+    }
+
+    let (request_sender, request_receiver) = mpsc::channel();
+    PROFILE_REQUEST_SENDER
+        .set(AsyncMutex::new(request_sender))
+        .map_err(|_| anyhow::anyhow!("request sender had already been initialized"))?;
+
+    install_signal_handler(libc::SIGPROF, on_sigprof)
+        .map_err(|e| BootstrapError::new(e).context("failed to install SIGPROF handler"))?;
+
+    let sampling_frequency = settings.sampling_frequency;
+
+    thread::Builder::new()
+        .name("foundations-cpu-profiler".into())
+        .spawn(move || profile_thread(sampling_frequency, request_receiver))?;
+
+    Ok(Some(CpuProfiler {
+        max_duration: Duration::from_secs(settings.max_duration_secs),
+        _seal: Seal,
+    }))
+}
+
+fn profile_thread(sampling_frequency: u32, receive_request: mpsc::Receiver<ProfileRequest>) {
+    while let Ok(request) = receive_request.recv() {
+        let profile = collect_profile(sampling_frequency, request.duration);
+
+        // NOTE: the requester might have gone away during profiling, which is not a reason
+        // to stop serving further requests.
+        let _ = request.response_sender.send(profile);
+    }
+}
+
+fn collect_profile(sampling_frequency: u32, duration: Duration) -> Result<Vec<u8>> {
+    let parallelism = thread::available_parallelism().map_or(1, Into::into);
+    let expected_samples = (duration.as_secs_f64() * sampling_frequency as f64).ceil() as usize;
+    let buffer = SampleBuffer::new((expected_samples * parallelism).clamp(1, MAX_SAMPLES));
+
+    let start = SystemTime::now();
+
+    ACTIVE_SAMPLE_BUFFER.store(&buffer as *const _ as *mut _, Ordering::SeqCst);
+
+    let res = set_sampling_timer(sampling_frequency).map(|_| thread::sleep(duration));
+
+    // NOTE: always try to disarm the timer and detach the buffer, even if arming has failed.
+    let disarm_res = set_sampling_timer(0);
+
+    ACTIVE_SAMPLE_BUFFER.store(ptr::null_mut(), Ordering::SeqCst);
+
+    // NOTE: wait for the signal handlers that might still be writing to the buffer.
+    while SIGNAL_HANDLERS_IN_FLIGHT.load(Ordering::SeqCst) > 0 {
+        thread::yield_now();
+    }
+
+    res?;
+    disarm_res?;
+
+    let mut stacks: HashMap<&[usize], i64> = HashMap::new();
+
+    for stack in buffer.stacks() {
+        *stacks.entry(stack).or_default() += 1;
+    }
+
+    let period = Duration::from_secs(1) / sampling_frequency;
+    let mut builder = ProfileBuilder::new(start, start.elapsed().unwrap_or(duration), period);
+
+    for (stack, count) in stacks {
+        builder.add_stack(stack, count);
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+    encoder.write_all(&builder.build().encode_to_vec())?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Preallocated storage for the stack samples, so the signal handler doesn't need to allocate.
+struct SampleBuffer {
+    slots: Box<[UnsafeCell<StackSlot>]>,
+    len: AtomicUsize,
+}
+
+// SAFETY: each slot is written only by the signal handler that has claimed it by incrementing
+// `len`, and slots are read only after all the signal handlers are done with the buffer.
+unsafe impl Sync for SampleBuffer {}
+
+impl SampleBuffer {
+    fn new(capacity: usize) -> Self {
+        let slots = (0..capacity)
+            .map(|_| UnsafeCell::new(StackSlot::new()))
+            .collect();
+
+        Self {
+            slots,
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records the stack of the interrupted thread. Must be async-signal-safe.
+    fn record(&self, interrupted_pc: usize) {
+        let Some(slot) = self.slots.get(self.len.fetch_add(1, Ordering::Relaxed)) else {
+            return;
+        };
+
+        // SAFETY: the slot index has been uniquely claimed above.
+        unsafe { &mut *slot.get() }.capture(interrupted_pc);
+    }
+
+    fn stacks(&self) -> impl Iterator<Item = &[usize]> {
+        let len = self.len.load(Ordering::SeqCst).min(self.slots.len());
+
+        self.slots[..len].iter().map(|slot| {
+            // SAFETY: the buffer is no longer accessible to the signal handlers.
+            let slot = unsafe { &*slot.get() };
+
+            slot.frames()
+        })
+    }
+}
+
+extern "C" fn on_sigprof(_signum: c_int, _info: *mut libc::siginfo_t, ucontext: *mut c_void) {
+    // NOTE: the signal handler must not change `errno` of the interrupted code.
+    let errno = unsafe { *libc::__errno_location() };
+
+    SIGNAL_HANDLERS_IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+
+    // SAFETY: the buffer is guaranteed to be alive while there are signal handlers in flight.
+    if let Some(buffer) = unsafe { ACTIVE_SAMPLE_BUFFER.load(Ordering::SeqCst).as_ref() } {
+        buffer.record(interrupted_pc(ucontext));
+    }
+
+    SIGNAL_HANDLERS_IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+
+    unsafe { *libc::__errno_location() = errno };
+}
+
+/// Arms the process-wide CPU time timer that delivers `SIGPROF` to the threads consuming CPU.
+/// The timer is disarmed if `sampling_frequency` is `0`.
+fn set_sampling_timer(sampling_frequency: u32) -> io::Result<()> {
+    let interval = match sampling_frequency {
+        0 => Duration::ZERO,
+        freq => Duration::from_secs(1) / freq,
+    };
+
+    let interval = libc::timeval {
+        tv_sec: interval.as_secs() as libc::time_t,
+        tv_usec: interval.subsec_micros() as libc::suseconds_t,
+    };
+
+    let timer = libc::itimerval {
+        it_interval: interval,
+        it_value: interval,
+    };
+
+    if unsafe { libc::setitimer(libc::ITIMER_PROF, &timer, ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pprof::Profile;
+    use super::*;
+    use crate::security::common_syscall_allow_lists::{
+        ASYNC, CPU_PROFILING_EXTRAS, SERVICE_BASICS,
+    };
+    use crate::security::{ViolationAction, allow_list, enable_syscall_sandboxing};
+    use flate2::read::GzDecoder;
+    use std::hint::black_box;
+    use std::io::Read as _;
+    use std::time::Instant;
+
+    fn profiler() -> CpuProfiler {
+        CpuProfiler::get_or_init_with(&CpuProfilerSettings {
+            enabled: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    #[inline(never)]
+    fn burn_cpu(duration: Duration) -> u64 {
+        let start = Instant::now();
+        let mut acc = 0u64;
+
+        while start.elapsed() < duration {
+            for i in 0..10_000u64 {
+                acc = black_box(acc.wrapping_mul(31).wrapping_add(i));
+            }
+        }
+
+        acc
+    }
+
+    fn decode(profile: &[u8]) -> Profile {
+        let mut buf = vec![];
+
+        GzDecoder::new(profile).read_to_end(&mut buf).unwrap();
+
+        Profile::decode(buf.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn sampling_frequency_out_of_bounds() {
+        assert!(
+            CpuProfiler::get_or_init_with(&CpuProfilerSettings {
+                enabled: true,
+                sampling_frequency: 0,
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn profile_duration_exceeds_max() {
+        assert!(profiler().profile(Duration::from_secs(3600)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn profile_after_seccomp_initialized() {
+        let profiler = profiler();
+
+        allow_list! {
+           static ALLOW_PROFILING = [
+                ..SERVICE_BASICS,
+                ..ASYNC,
+                ..CPU_PROFILING_EXTRAS
+           ]
+        }
+        enable_syscall_sandboxing(ViolationAction::KillProcess, &ALLOW_PROFILING).unwrap();
+
+        // NOTE: burn CPU on the sandboxed thread, so it gets interrupted by the profiler.
+        let (profile, _) = tokio::join!(profiler.profile(Duration::from_secs(1)), async {
+            burn_cpu(Duration::from_millis(1500))
+        });
+
+        let profile = decode(&profile.unwrap());
+
+        assert!(
+            profile
+                .stacks()
+                .iter()
+                .any(|stack| stack.iter().any(|f| f.contains("burn_cpu")))
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Minimal subset of the [pprof profile format] required to serialize CPU profiles.
+//!
+//! [pprof profile format]: https://github.com/google/pprof/blob/main/proto/profile.proto
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(super) struct Profile {
+    #[prost(message, repeated, tag = "1")]
+    sample_type: Vec<ValueType>,
+    #[prost(message, repeated, tag = "2")]
+    sample: Vec<Sample>,
+    #[prost(message, repeated, tag = "4")]
+    location: Vec<Location>,
+    #[prost(message, repeated, tag = "5")]
+    function: Vec<Function>,
+    #[prost(string, repeated, tag = "6")]
+    string_table: Vec<String>,
+    #[prost(int64, tag = "9")]
+    time_nanos: i64,
+    #[prost(int64, tag = "10")]
+    duration_nanos: i64,
+    #[prost(message, optional, tag = "11")]
+    period_type: Option<ValueType>,
+    #[prost(int64, tag = "12")]
+    period: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ValueType {
+    #[prost(int64, tag = "1")]
+    r#type: i64,
+    #[prost(int64, tag = "2")]
+    unit: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Sample {
+    #[prost(uint64, repeated, tag = "1")]
+    location_id: Vec<u64>,
+    #[prost(int64, repeated, tag = "2")]
+    value: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Location {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(uint64, tag = "3")]
+    address: u64,
+    #[prost(message, repeated, tag = "4")]
+    line: Vec<Line>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Line {
+    #[prost(uint64, tag = "1")]
+    function_id: u64,
+    #[prost(int64, tag = "2")]
+    line: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Function {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(int64, tag = "2")]
+    name: i64,
+    #[prost(int64, tag = "3")]
+    system_name: i64,
+    #[prost(int64, tag = "4")]
+    filename: i64,
+}
+
+/// Builds a symbolized [`Profile`] out of the raw stack samples.
+pub(super) struct ProfileBuilder {
+    profile: Profile,
+    strings: HashMap<String, i64>,
+    functions: HashMap<(i64, i64), u64>,
+    locations: HashMap<usize, u64>,
+}
+
+impl ProfileBuilder {
+    pub(super) fn new(start: SystemTime, duration: Duration, period: Duration) -> Self {
+        let mut builder = Self {
+            profile: Profile {
+                time_nanos: start
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as i64,
+                duration_nanos: duration.as_nanos() as i64,
+                period: period.as_nanos() as i64,
+                ..Default::default()
+            },
+            strings: Default::default(),
+            functions: Default::default(),
+            locations: Default::default(),
+        };
+
+        // NOTE: the first entry of the string table must always be an empty string.
+        builder.string_id("");
+
+        let samples = builder.value_type("samples", "count");
+        let cpu = builder.value_type("cpu", "nanoseconds");
+
+        builder.profile.sample_type = vec![samples, cpu.clone()];
+        builder.profile.period_type = Some(cpu);
+
+        builder
+    }
+
+    /// Adds a stack that has been sampled `count` times. The stack is expected to start with
+    /// the interrupted instruction address, followed by the return addresses of the callers.
+    pub(super) fn add_stack(&mut self, stack: &[usize], count: i64) {
+        let location_id = stack
+            .iter()
+            .enumerate()
+            .map(|(i, &addr)| {
+                // NOTE: return addresses point to the instruction following the call, which
+                // can belong to a different line or even to a different function.
+                let addr = if i == 0 { addr } else { addr.saturating_sub(1) };
+
+                self.location_id(addr)
+            })
+            .collect();
+
+        self.profile.sample.push(Sample {
+            location_id,
+            value: vec![count, count * self.profile.period],
+        });
+    }
+
+    pub(super) fn build(self) -> Profile {
+        self.profile
+    }
+
+    fn location_id(&mut self, addr: usize) -> u64 {
+        if let Some(id) = self.locations.get(&addr) {
+            return *id;
+        }
+
+        let mut lines = vec![];
+
+        // NOTE: the resolver reports inlined functions first, which matches pprof's expectation
+        // that the last line is the caller into which the preceding lines were inlined.
+        backtrace::resolve(addr as *mut std::ffi::c_void, |symbol| {
+            let Some(name) = symbol.name() else {
+                return;
+            };
+
+            let filename = symbol
+                .filename()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+
+            let function_id = self.function_id(&format!("{name:#}"), &name.to_string(), &filename);
+
+            lines.push(Line {
+                function_id,
+                line: symbol.lineno().unwrap_or_default().into(),
+            });
+        });
+
+        let id = self.profile.location.len() as u64 + 1;
+
+        self.profile.location.push(Location {
+            id,
+            address: addr as u64,
+            line: lines,
+        });
+
+        self.locations.insert(addr, id);
+
+        id
+    }
+
+    fn function_id(&mut self, name: &str, system_name: &str, filename: &str) -> u64 {
+        let name = self.string_id(name);
+        let system_name = self.string_id(system_name);
+        let filename = self.string_id(filename);
+
+        if let Some(id) = self.functions.get(&(name, filename)) {
+            return *id;
+        }
+
+        let id = self.profile.function.len() as u64 + 1;
+
+        self.profile.function.push(Function {
+            id,
+            name,
+            system_name,
+            filename,
+        });
+
+        self.functions.insert((name, filename), id);
+
+        id
+    }
+
+    fn value_type(&mut self, ty: &str, unit: &str) -> ValueType {
+        ValueType {
+            r#type: self.string_id(ty),
+            unit: self.string_id(unit),
+        }
+    }
+
+    fn string_id(&mut self, s: &str) -> i64 {
+        if let Some(id) = self.strings.get(s) {
+            return *id;
+        }
+
+        let id = self.profile.string_table.len() as i64;
+
+        self.profile.string_table.push(s.to_string());
+        self.strings.insert(s.to_string(), id);
+
+        id
+    }
+}
+
+#[cfg(test)]
+impl Profile {
+    /// Returns the names of the functions in each sampled stack, innermost first.
+    pub(super) fn stacks(&self) -> Vec<Vec<&str>> {
+        let functions: HashMap<_, _> = self.function.iter().map(|f| (f.id, f)).collect();
+        let locations: HashMap<_, _> = self.location.iter().map(|l| (l.id, l)).collect();
+
+        self.sample
+            .iter()
+            .map(|sample| {
+                sample
+                    .location_id
+                    .iter()
+                    .flat_map(|id| &locations[id].line)
+                    .map(|line| {
+                        self.string_table[functions[&line.function_id].name as usize].as_str()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
@@ -6,7 +6,7 @@ use futures_util::future::BoxFuture;
 use futures_util::stream::FuturesUnordered;
 use futures_util::{FutureExt, Stream};
 #[cfg(feature = "logging")]
-use slog_async::AsyncGuard;
+use super::log::channel::ChannelGuard;
 use std::future::Future;
 #[cfg(feature = "logging")]
 use std::mem::ManuallyDrop;
@@ -26,13 +26,13 @@ feature_use!(cfg(feature = "telemetry-server"), {
 /// [security syscall-related]: `crate::security`
 pub struct TelemetryDriver {
     #[cfg(feature = "telemetry-server")]
-    server_addr: Option<ListenAddr>,
+    server_addrs: Vec<ListenAddr>,
 
     #[cfg(feature = "telemetry-server")]
     server_fut: Option<TelemetryServerFuture>,
 
     #[cfg(feature = "logging")]
-    logging_guard: Option<ManuallyDrop<AsyncGuard>>,
+    logging_guard: Option<ManuallyDrop<ChannelGuard>>,
 
     tele_futures: FuturesUnordered<BoxFuture<'static, BootstrapResult<()>>>,
 }
@@ -44,7 +44,10 @@ impl TelemetryDriver {
     ) -> Self {
         Self {
             #[cfg(feature = "telemetry-server")]
-            server_addr: server_fut.as_ref().and_then(|fut| fut.local_addr().ok()),
+            server_addrs: server_fut
+                .as_ref()
+                .map(TelemetryServerFuture::local_addrs)
+                .unwrap_or_default(),
 
             #[cfg(feature = "telemetry-server")]
             server_fut,
@@ -56,18 +59,31 @@ impl TelemetryDriver {
         }
     }
 
-    /// Binds to a `slog::AsyncGuard` to ensure logs get dropped when calling `shutdown_logger`
+    /// Binds to the log channel guard to ensure logs get dropped when calling `shutdown_logger`
     #[cfg(feature = "logging")]
-    pub(super) fn set_logging_guard(&mut self, logging_async_guard: Option<AsyncGuard>) {
+    pub(super) fn set_logging_guard(&mut self, logging_async_guard: Option<ChannelGuard>) {
         self.logging_guard = logging_async_guard.map(ManuallyDrop::new);
     }
 
     /// Address of the telemetry server.
     ///
+    /// If the server has [additional listeners], returns the address of the main listener.
+    /// Use [`TelemetryDriver::server_addrs`] to obtain addresses of all the listeners.
+    ///
     /// Returns `None` if the server wasn't spawned.
+    ///
+    /// [additional listeners]: crate::telemetry::settings::TelemetryServerSettings::additional_listeners
     #[cfg(feature = "telemetry-server")]
     pub fn server_addr(&self) -> Option<&ListenAddr> {
-        self.server_addr.as_ref()
+        self.server_addrs.first()
+    }
+
+    /// Addresses of all the telemetry server listeners, starting with the main listener.
+    ///
+    /// Returns an empty slice if the server wasn't spawned.
+    #[cfg(feature = "telemetry-server")]
+    pub fn server_addrs(&self) -> &[ListenAddr] {
+        &self.server_addrs
     }
 
     /// Instructs the telemetry driver and server to perform an orderly shutdown when the given
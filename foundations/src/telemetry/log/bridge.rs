@@ -0,0 +1,475 @@
+use super::internal::current_log;
+use crate::BootstrapResult;
+use crate::telemetry::settings::{LogBridgeSettings, LoggingSettings};
+use slog::{BorrowedKV, Drain, KV, Key, Level, Record, RecordLocation, RecordStatic, Serializer};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, RwLock};
+
+#[cfg(feature = "log-bridge")]
+use {std::collections::HashSet, std::sync::atomic::AtomicBool};
+
+#[cfg(feature = "tracing-bridge")]
+use {
+    std::fmt::Debug,
+    tracing_core::field::{Field, Visit},
+    tracing_core::subscriber::Interest,
+    tracing_core::{Event, Metadata, Subscriber},
+    tracing_subscriber::layer::{Context, Layer},
+};
+
+// NOTE: slog requires static keys and locations, so the ones that aren't static are leaked.
+// Their number is bounded by the number of the distinct callsites and field keys.
+#[cfg(feature = "log-bridge")]
+static INTERNED_STRS: LazyLock<RwLock<HashSet<&'static str>>> = LazyLock::new(Default::default);
+
+static LOCATIONS: LazyLock<RwLock<HashMap<LocationKey, &'static RecordLocation>>> =
+    LazyLock::new(Default::default);
+
+type LocationKey = (&'static str, &'static str, u32);
+
+// NOTE: the most verbose level of all the logs built so far. It's never lowered, since the logs
+// of the other telemetry contexts can still be more verbose than the current one.
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "log-bridge")]
+static LOG_CRATE_BRIDGE_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // NOTE: prevents infinite recursion if a log drain emits records through the bridged
+    // libraries itself.
+    static IS_BRIDGING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs the bridges enabled in the settings.
+pub(crate) fn install(settings: &LoggingSettings) -> BootstrapResult<()> {
+    let LogBridgeSettings {
+        #[cfg(feature = "log-bridge")]
+        log_crate,
+        #[cfg(feature = "tracing-bridge")]
+        tracing_events,
+    } = &settings.bridge;
+
+    #[cfg(feature = "log-bridge")]
+    if *log_crate {
+        static LOG_CRATE_BRIDGE: LogCrateBridge = LogCrateBridge;
+
+        log::set_logger(&LOG_CRATE_BRIDGE)
+            .map_err(|_| anyhow::anyhow!("a logger of the `log` crate is already installed"))?;
+
+        LOG_CRATE_BRIDGE_INSTALLED.store(true, Ordering::Release);
+        log::set_max_level(log_crate_level_filter(max_level()));
+    }
+
+    #[cfg(feature = "tracing-bridge")]
+    if *tracing_events {
+        use tracing_core::dispatcher::{self, Dispatch};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        #[cfg(feature = "tracing-rs-compat")]
+        if matches!(
+            settings.output,
+            crate::telemetry::settings::LogOutput::TracingRsCompat
+        ) {
+            anyhow::bail!("the `tracing` bridge can't be used with the `TracingRsCompat` output");
+        }
+
+        let subscriber = tracing_subscriber::registry().with(TracingBridgeLayer);
+
+        dispatcher::set_global_default(Dispatch::new(subscriber)).map_err(|_| {
+            anyhow::anyhow!("a global default subscriber of the `tracing` crate is already set")
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Raises the max level of the records that pass the static filters of the bridged libraries.
+pub(crate) fn raise_max_level(level: Level) {
+    if MAX_LEVEL.fetch_max(level.as_usize(), Ordering::AcqRel) >= level.as_usize() {
+        return;
+    }
+
+    #[cfg(feature = "log-bridge")]
+    if LOG_CRATE_BRIDGE_INSTALLED.load(Ordering::Acquire) {
+        log::set_max_level(log_crate_level_filter(max_level()));
+    }
+
+    #[cfg(feature = "tracing-bridge")]
+    tracing_core::callsite::rebuild_interest_cache();
+}
+
+fn max_level() -> Option<Level> {
+    Level::from_usize(MAX_LEVEL.load(Ordering::Acquire))
+}
+
+#[cfg(feature = "tracing-bridge")]
+fn is_within_max_level(level: Level) -> bool {
+    max_level().is_some_and(|max_level| level.is_at_least(max_level))
+}
+
+/// Key-value fields of a bridged record.
+#[derive(Default)]
+struct BridgedFields(Vec<(Key, String)>);
+
+impl KV for BridgedFields {
+    fn serialize(&self, _record: &Record, serializer: &mut dyn Serializer) -> slog::Result {
+        for (key, value) in &self.0 {
+            serializer.emit_str(key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_enabled(level: Level) -> bool {
+    !IS_BRIDGING.get() && current_log().read().inner.is_enabled(level)
+}
+
+fn log_bridged(level: Level, location: LocationKey, msg: &Arguments, fields: &BridgedFields) {
+    if IS_BRIDGING.replace(true) {
+        return;
+    }
+
+    let record_static = RecordStatic {
+        location: intern_location(location),
+        tag: "",
+        level,
+    };
+
+    current_log()
+        .read()
+        .log(&Record::new(&record_static, msg, BorrowedKV(fields)));
+
+    IS_BRIDGING.set(false);
+}
+
+#[cfg(feature = "log-bridge")]
+fn intern_str(s: &str) -> &'static str {
+    if let Some(interned) = INTERNED_STRS.read().unwrap().get(s) {
+        return interned;
+    }
+
+    let mut interned_strs = INTERNED_STRS.write().unwrap();
+
+    match interned_strs.get(s) {
+        Some(interned) => interned,
+        None => {
+            let interned = Box::leak(s.into());
+            interned_strs.insert(interned);
+            interned
+        }
+    }
+}
+
+fn intern_location(key: LocationKey) -> &'static RecordLocation {
+    if let Some(location) = LOCATIONS.read().unwrap().get(&key) {
+        return location;
+    }
+
+    let (module, file, line) = key;
+
+    LOCATIONS.write().unwrap().entry(key).or_insert_with(|| {
+        Box::leak(Box::new(RecordLocation {
+            file,
+            line,
+            column: 0,
+            function: "",
+            module,
+        }))
+    })
+}
+
+/// A [`log`] crate logger that routes the records to the current foundations log.
+///
+/// It's installed automatically if [`LogBridgeSettings::log_crate`] is enabled. Can be used
+/// directly to combine it with other loggers.
+///
+/// [`log`]: https://docs.rs/log
+/// [`LogBridgeSettings::log_crate`]: crate::telemetry::settings::LogBridgeSettings::log_crate
+#[cfg(feature = "log-bridge")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogCrateBridge;
+
+#[cfg(feature = "log-bridge")]
+impl log::Log for LogCrateBridge {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        is_enabled(log_crate_level(metadata.level()))
+    }
+
+    fn log(&self, record: &log::Record) {
+        let level = log_crate_level(record.level());
+
+        if !is_enabled(level) {
+            return;
+        }
+
+        let module = record
+            .module_path_static()
+            .unwrap_or_else(|| intern_str(record.module_path().unwrap_or(record.target())));
+
+        let file = record
+            .file_static()
+            .unwrap_or_else(|| intern_str(record.file().unwrap_or_default()));
+
+        let mut fields = BridgedFields::default();
+        let _ = record.key_values().visit(&mut fields);
+
+        log_bridged(
+            level,
+            (module, file, record.line().unwrap_or_default()),
+            record.args(),
+            &fields,
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "log-bridge")]
+impl<'kvs> log::kv::VisitSource<'kvs> for BridgedFields {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.0.push((intern_str(key.as_str()), value.to_string()));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "log-bridge")]
+fn log_crate_level_filter(level: Option<Level>) -> log::LevelFilter {
+    match level {
+        None => log::LevelFilter::Off,
+        Some(Level::Critical | Level::Error) => log::LevelFilter::Error,
+        Some(Level::Warning) => log::LevelFilter::Warn,
+        Some(Level::Info) => log::LevelFilter::Info,
+        Some(Level::Debug) => log::LevelFilter::Debug,
+        Some(Level::Trace) => log::LevelFilter::Trace,
+    }
+}
+
+#[cfg(feature = "log-bridge")]
+fn log_crate_level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::Error,
+        log::Level::Warn => Level::Warning,
+        log::Level::Info => Level::Info,
+        log::Level::Debug => Level::Debug,
+        log::Level::Trace => Level::Trace,
+    }
+}
+
+/// A [`tracing`] crate subscriber layer that routes the events to the current foundations log.
+///
+/// It's installed automatically as a part of the global default subscriber if
+/// [`LogBridgeSettings::tracing_events`] is enabled. Can be used directly to add the bridge to
+/// another subscriber. Spans are ignored, only the fields of the events are captured.
+///
+/// [`tracing`]: https://docs.rs/tracing
+/// [`LogBridgeSettings::tracing_events`]: crate::telemetry::settings::LogBridgeSettings::tracing_events
+#[cfg(feature = "tracing-bridge")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingBridgeLayer;
+
+#[cfg(feature = "tracing-bridge")]
+impl<S: Subscriber> Layer<S> for TracingBridgeLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if is_tracing_callsite_enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        is_tracing_callsite_enabled(metadata)
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = tracing_level(metadata.level());
+
+        if !is_enabled(level) {
+            return;
+        }
+
+        let mut visitor = TracingEventVisitor::default();
+        event.record(&mut visitor);
+
+        let module = metadata.module_path().unwrap_or(metadata.target());
+        let file = metadata.file().unwrap_or_default();
+        let message = visitor.message.unwrap_or_default();
+
+        log_bridged(
+            level,
+            (module, file, metadata.line().unwrap_or_default()),
+            &format_args!("{message}"),
+            &visitor.fields,
+        );
+    }
+}
+
+// NOTE: spans are left enabled, since they can be used by the other layers of the subscriber.
+#[cfg(feature = "tracing-bridge")]
+fn is_tracing_callsite_enabled(metadata: &Metadata<'_>) -> bool {
+    !metadata.is_event() || is_within_max_level(tracing_level(metadata.level()))
+}
+
+#[cfg(feature = "tracing-bridge")]
+#[derive(Default)]
+struct TracingEventVisitor {
+    message: Option<String>,
+    fields: BridgedFields,
+}
+
+#[cfg(feature = "tracing-bridge")]
+impl Visit for TracingEventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.record(field, format!("{value:?}"));
+    }
+}
+
+#[cfg(feature = "tracing-bridge")]
+impl TracingEventVisitor {
+    fn record(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "message" => self.message = Some(value),
+            name => self.fields.0.push((name, value)),
+        }
+    }
+}
+
+#[cfg(feature = "tracing-bridge")]
+fn tracing_level(level: &tracing_core::Level) -> Level {
+    match *level {
+        tracing_core::Level::ERROR => Level::Error,
+        tracing_core::Level::WARN => Level::Warning,
+        tracing_core::Level::INFO => Level::Info,
+        tracing_core::Level::DEBUG => Level::Debug,
+        tracing_core::Level::TRACE => Level::Trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::settings::LogVerbosity;
+    use crate::telemetry::{TestTelemetryContext, log, log::TestLogRecord};
+    use foundations_macros::with_test_telemetry;
+
+    #[cfg(feature = "log-bridge")]
+    #[with_test_telemetry(test, crate_path = "crate")]
+    fn log_crate_records(mut ctx: TestTelemetryContext) {
+        use ::log::Log as _;
+
+        ctx.set_logging_settings(LoggingSettings {
+            verbosity: LogVerbosity::Info,
+            ..Default::default()
+        });
+
+        log::add_fields! { "request_id" => 42 }
+
+        let kvs = [("user", "jane")];
+
+        for (level, msg) in [(::log::Level::Warn, "warn"), (::log::Level::Debug, "debug")] {
+            LogCrateBridge.log(
+                &::log::Record::builder()
+                    .level(level)
+                    .module_path_static(Some("dependency::client"))
+                    .file_static(Some("src/client.rs"))
+                    .line(Some(7))
+                    .args(format_args!("{msg}"))
+                    .key_values(&kvs)
+                    .build(),
+            );
+        }
+
+        assert_eq!(
+            *ctx.log_records(),
+            vec![TestLogRecord {
+                level: Level::Warning,
+                message: "warn".into(),
+                fields: vec![
+                    ("request_id".into(), "42".into()),
+                    ("user".into(), "jane".into()),
+                ]
+            }]
+        );
+    }
+
+    #[cfg(feature = "tracing-bridge")]
+    #[with_test_telemetry(test, crate_path = "crate")]
+    fn tracing_events(mut ctx: TestTelemetryContext) {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        ctx.set_logging_settings(LoggingSettings {
+            verbosity: LogVerbosity::Info,
+            ..Default::default()
+        });
+
+        log::add_fields! { "request_id" => 42 }
+
+        let subscriber = tracing_subscriber::registry().with(TracingBridgeLayer);
+
+        ::tracing::subscriber::with_default(subscriber, || {
+            ::tracing::error!(user = "jane", attempt = 2, "failed to connect to {}", "db");
+            ::tracing::trace!("filtered out");
+        });
+
+        assert_eq!(
+            *ctx.log_records(),
+            vec![TestLogRecord {
+                level: Level::Error,
+                message: "failed to connect to db".into(),
+                fields: vec![
+                    ("request_id".into(), "42".into()),
+                    ("user".into(), "jane".into()),
+                    ("attempt".into(), "2".into()),
+                ]
+            }]
+        );
+    }
+
+    #[with_test_telemetry(test, crate_path = "crate")]
+    fn max_level_raised_by_verbosity(mut ctx: TestTelemetryContext) {
+        ctx.set_logging_settings(LoggingSettings {
+            verbosity: LogVerbosity::Debug,
+            ..Default::default()
+        });
+
+        assert!(max_level().is_some_and(|level| Level::Debug.is_at_least(level)));
+
+        log::set_verbosity(LogVerbosity::Trace).unwrap();
+
+        assert_eq!(max_level(), Some(Level::Trace));
+    }
+
+    #[test]
+    fn interned_locations() {
+        let location = intern_location(("a::b", "src/b.rs", 1));
+
+        assert!(std::ptr::eq(
+            location,
+            intern_location(("a::b", "src/b.rs", 1))
+        ));
+        assert!(!std::ptr::eq(
+            location,
+            intern_location(("a::b", "src/b.rs", 2))
+        ));
+    }
+
+    #[cfg(feature = "log-bridge")]
+    #[test]
+    fn interned_strs() {
+        assert!(std::ptr::eq(intern_str("key"), intern_str("key")));
+    }
+}
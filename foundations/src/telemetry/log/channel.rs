@@ -0,0 +1,525 @@
+use crate::telemetry::settings::{LogChannelOverflow, LogChannelSettings};
+use slog::{Drain, Level, Never, OwnedKVList, Record, b, record};
+use slog_async::{AsyncCore, AsyncError, AsyncGuard, AsyncRecord};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "metrics")]
+use crate::telemetry::metrics::{Counter, Gauge};
+
+// NOTE: the interval between the attempts to put a record in a full channel grows up to this
+// value while waiting for the space in it.
+const MAX_RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+#[cfg(feature = "metrics")]
+#[crate::telemetry::metrics::metrics(crate_path = "crate")]
+mod foundations {
+    /// The number of log records dropped because the log channel of the output was full.
+    pub fn log_channel_records_dropped(output: usize, level: &'static str) -> Counter;
+
+    /// The number of log records waiting in the log channel to be written to the output.
+    pub fn log_channel_depth(output: usize) -> Gauge;
+}
+
+/// A drain that passes the log records through a bounded [`slog_async`] channel to the inner
+/// drain running in a separate thread.
+///
+/// Unlike [`slog_async::Async`], allows choosing what to do with the records on the channel
+/// overflow and counts the dropped records per level. The inner drain is flushed each time the
+/// channel is emptied, so the drains that batch the records don't keep them indefinitely.
+///
+/// The outputs are identified in the metrics by their index: `0` is the main output and the
+/// additional outputs follow in the order of the settings.
+pub(crate) struct ChannelDrain {
+    channel: Arc<Channel>,
+}
+
+impl ChannelDrain {
+    pub(crate) fn new<D>(
+        drain: D,
+        settings: &LogChannelSettings,
+        _output: usize,
+    ) -> (Self, ChannelGuard)
+    where
+        D: Drain<Ok = (), Err = Never> + Send + 'static,
+    {
+        let mut async_guard = None;
+
+        let channel = Arc::new_cyclic(|channel| {
+            let drain = ChannelOutputDrain {
+                inner: drain,
+                channel: Weak::clone(channel),
+            };
+
+            let (core, guard) = AsyncCore::custom(drain)
+                .chan_size(settings.size.max(1))
+                .thread_name("foundations-log".into())
+                .build_with_guard();
+
+            async_guard = Some(guard);
+
+            Channel {
+                core,
+                overflow: settings.overflow,
+                block_timeout: Duration::from_millis(settings.block_timeout_ms),
+                capacity: settings.size.max(1),
+                pending: Default::default(),
+                has_pending: AtomicBool::new(false),
+                dropped: AtomicUsize::new(0),
+                queued: AtomicUsize::new(0),
+                #[cfg(feature = "metrics")]
+                output: _output,
+                #[cfg(feature = "metrics")]
+                depth: foundations::log_channel_depth(_output),
+            }
+        });
+
+        let guard = ChannelGuard {
+            channel: Arc::clone(&channel),
+            async_guard,
+            chained: vec![],
+        };
+
+        (Self { channel }, guard)
+    }
+}
+
+impl Drain for ChannelDrain {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        self.channel.log(record, values);
+
+        Ok(())
+    }
+}
+
+struct Channel {
+    core: AsyncCore,
+    overflow: LogChannelOverflow,
+    block_timeout: Duration,
+    capacity: usize,
+    // NOTE: the records waiting for the space in the channel with
+    // `LogChannelOverflow::DropOldest`. They are put in the channel by the output thread once it
+    // takes the records from it.
+    pending: Mutex<VecDeque<(Level, AsyncRecord)>>,
+    has_pending: AtomicBool,
+    dropped: AtomicUsize,
+    queued: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    output: usize,
+    #[cfg(feature = "metrics")]
+    depth: Gauge,
+}
+
+impl Channel {
+    fn log(&self, record: &Record, values: &OwnedKVList) {
+        self.send_dropped_report();
+
+        // NOTE: the new records wait behind the pending ones to keep the order.
+        if self.has_pending.load(Ordering::Acquire) {
+            return self.push_pending(record.level(), AsyncRecord::from(record, values));
+        }
+
+        match self.try_send(record, values) {
+            Err(AsyncError::Full) => {}
+            _ => return,
+        }
+
+        match self.overflow {
+            LogChannelOverflow::DropNewest => self.drop_record(record.level()),
+            LogChannelOverflow::DropOldest => {
+                self.push_pending(record.level(), AsyncRecord::from(record, values))
+            }
+            LogChannelOverflow::Block => {
+                if !self.retry_until_timeout(|| self.try_send(record, values)) {
+                    self.drop_record(record.level());
+                }
+            }
+        }
+    }
+
+    fn try_send(&self, record: &Record, values: &OwnedKVList) -> Result<(), AsyncError> {
+        // NOTE: counted before sending, so the output thread doesn't see the channel as empty
+        // while the record is being sent.
+        self.queued.fetch_add(1, Ordering::AcqRel);
+
+        #[cfg(feature = "metrics")]
+        self.depth.inc();
+
+        let res = self.core.log(record, values);
+
+        if res.is_err() {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
+
+            #[cfg(feature = "metrics")]
+            self.depth.dec();
+        }
+
+        res
+    }
+
+    /// Calls `try_send` until it succeeds or the block timeout expires. Returns `false` if the
+    /// timeout expired.
+    fn retry_until_timeout(&self, mut try_send: impl FnMut() -> Result<(), AsyncError>) -> bool {
+        let deadline = Instant::now() + self.block_timeout;
+        let mut interval = Duration::from_micros(50);
+
+        loop {
+            match try_send() {
+                Err(AsyncError::Full) => {}
+                _ => return true,
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                return false;
+            }
+
+            thread::sleep(interval.min(deadline - now));
+            interval = (interval * 2).min(MAX_RETRY_INTERVAL);
+        }
+    }
+
+    fn push_pending(&self, level: Level, record: AsyncRecord) {
+        let mut pending = self.pending.lock().unwrap();
+
+        if pending.len() >= self.capacity
+            && let Some((level, _)) = pending.pop_front()
+        {
+            self.drop_record(level);
+        }
+
+        pending.push_back((level, record));
+        self.has_pending.store(true, Ordering::Release);
+
+        // NOTE: the output thread could have emptied the channel before the record was added.
+        self.send_pending(&mut pending);
+    }
+
+    /// Puts the pending records in the channel while there's space in it.
+    fn send_pending(&self, pending: &mut VecDeque<(Level, AsyncRecord)>) {
+        while let Some((_, record)) = pending.front() {
+            let mut res = Ok(());
+            record.as_record_values(|record, values| res = self.try_send(record, values));
+
+            if let Err(AsyncError::Full) = res {
+                return;
+            }
+
+            pending.pop_front();
+        }
+
+        self.has_pending.store(false, Ordering::Release);
+    }
+
+    /// Called by the output thread once it takes a record from the channel.
+    fn on_record_taken(&self) {
+        self.queued.fetch_sub(1, Ordering::AcqRel);
+
+        #[cfg(feature = "metrics")]
+        self.depth.dec();
+
+        if self.has_pending.load(Ordering::Acquire) {
+            self.send_pending(&mut self.pending.lock().unwrap());
+        }
+
+        self.send_dropped_report();
+    }
+
+    fn drop_record(&self, _level: Level) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        foundations::log_channel_records_dropped(self.output, _level.as_str()).inc();
+    }
+
+    /// Sends the report of the dropped records, once there's space in the channel and no
+    /// pending records.
+    fn send_dropped_report(&self) {
+        if self.dropped.load(Ordering::Relaxed) == 0 || self.has_pending.load(Ordering::Acquire) {
+            return;
+        }
+
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+
+        if dropped == 0 {
+            return;
+        }
+
+        // NOTE: the report doesn't belong to any of the loggers, so it has no values of its own.
+        let res = self.try_send(
+            &record!(
+                Level::Error,
+                "",
+                &format_args!("dropped {dropped} log records due to the channel overflow"),
+                b!("dropped_count" => dropped)
+            ),
+            &OwnedKVList::from(slog::o!()),
+        );
+
+        if res.is_err() {
+            self.dropped.fetch_add(dropped, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Notifies the channel once the output thread takes a record from it and flushes the inner
+/// drain once the channel is empty.
+struct ChannelOutputDrain<D> {
+    inner: D,
+    channel: Weak<Channel>,
+}
+
+impl<D: Drain<Ok = (), Err = Never>> Drain for ChannelOutputDrain<D> {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let Some(channel) = self.channel.upgrade() else {
+            return self.inner.log(record, values);
+        };
+
+        channel.on_record_taken();
+
+        let res = self.inner.log(record, values);
+
+        if channel.queued.load(Ordering::Acquire) == 0 {
+            let _ = self.inner.flush();
+        }
+
+        res
+    }
+
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.inner.is_enabled(level)
+    }
+
+    #[inline]
+    fn flush(&self) -> Result<(), slog::FlushError> {
+        self.inner.flush()
+    }
+}
+
+/// Writes the remaining records in the channel and stops the output thread on drop.
+pub(crate) struct ChannelGuard {
+    channel: Arc<Channel>,
+    async_guard: Option<AsyncGuard>,
+    // NOTE: dropped after this guard's channel is stopped.
+    chained: Vec<ChannelGuard>,
+}
+
+impl ChannelGuard {
+    /// Makes the guard also stop the channel of the other guard on drop.
+    pub(crate) fn chain(&mut self, other: ChannelGuard) {
+        self.chained.push(other);
+    }
+}
+
+impl Drop for ChannelGuard {
+    fn drop(&mut self) {
+        // NOTE: the pending records and the report need to get in the channel before the output
+        // thread is stopped.
+        let channel = &self.channel;
+
+        channel.retry_until_timeout(|| match channel.has_pending.load(Ordering::Acquire) {
+            true => Err(AsyncError::Full),
+            false => Ok(()),
+        });
+
+        channel.retry_until_timeout(|| {
+            channel.send_dropped_report();
+
+            match channel.dropped.load(Ordering::Relaxed) {
+                0 => Ok(()),
+                _ => Err(AsyncError::Full),
+            }
+        });
+
+        self.async_guard.take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{KV, Logger};
+    use std::sync::mpsc;
+
+    struct BlockingDrain {
+        messages: Arc<Mutex<Vec<String>>>,
+        started: mpsc::Sender<()>,
+        release: mpsc::Receiver<()>,
+    }
+
+    impl Drain for BlockingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+            let mut message = record.msg().to_string();
+
+            // NOTE: the first record blocks the output thread until released, so the channel
+            // can be filled up.
+            if message == "1" {
+                self.started.send(()).unwrap();
+                self.release.recv().unwrap();
+            }
+
+            // NOTE: the report must not get the values of the logger of the next record.
+            let mut values_count = ValuesCount(0);
+            values.serialize(record, &mut values_count).unwrap();
+
+            if message.starts_with("dropped") && values_count.0 > 0 {
+                message.push_str(" with values");
+            }
+
+            self.messages.lock().unwrap().push(message);
+
+            Ok(())
+        }
+    }
+
+    struct FlushCountingDrain(Arc<AtomicUsize>);
+
+    impl Drain for FlushCountingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, _record: &Record, _values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), slog::FlushError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+
+            Ok(())
+        }
+    }
+
+    struct ValuesCount(usize);
+
+    impl slog::Serializer for ValuesCount {
+        fn emit_arguments(&mut self, _key: slog::Key, _val: &std::fmt::Arguments) -> slog::Result {
+            self.0 += 1;
+
+            Ok(())
+        }
+    }
+
+    fn log_with_overflow(
+        overflow: LogChannelOverflow,
+        capacity: usize,
+        count: usize,
+    ) -> Vec<String> {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+
+        let drain = BlockingDrain {
+            messages: Arc::clone(&messages),
+            started: started_tx,
+            release: release_rx,
+        };
+
+        let settings = LogChannelSettings {
+            size: capacity,
+            overflow,
+            block_timeout_ms: 10,
+        };
+
+        let (drain, guard) = ChannelDrain::new(drain, &settings, 0);
+        let log = Logger::root(drain, slog::o!("key" => "value"));
+
+        slog::info!(log, "1");
+        started_rx.recv().unwrap();
+
+        for i in 2..=count {
+            slog::info!(log, "{}", i);
+        }
+
+        release_tx.send(()).unwrap();
+
+        // NOTE: wait for the dropped records report, so the last record isn't dropped.
+        while !messages
+            .lock()
+            .unwrap()
+            .last()
+            .is_some_and(|msg| msg.starts_with("dropped"))
+        {
+            thread::yield_now();
+        }
+
+        slog::info!(log, "{}", count + 1);
+        drop(guard);
+
+        Arc::try_unwrap(messages).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn flush_when_emptied() {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let drain = FlushCountingDrain(Arc::clone(&flushes));
+        let (drain, guard) = ChannelDrain::new(drain, &Default::default(), 0);
+        let log = Logger::root(drain, slog::o!());
+
+        slog::info!(log, "1");
+
+        while flushes.load(Ordering::Relaxed) == 0 {
+            thread::yield_now();
+        }
+
+        drop(guard);
+    }
+
+    #[test]
+    fn drop_newest() {
+        assert_eq!(
+            log_with_overflow(LogChannelOverflow::DropNewest, 2, 5),
+            [
+                "1",
+                "2",
+                "3",
+                "dropped 2 log records due to the channel overflow",
+                "6"
+            ]
+        );
+    }
+
+    #[test]
+    fn drop_oldest() {
+        assert_eq!(
+            log_with_overflow(LogChannelOverflow::DropOldest, 2, 7),
+            [
+                "1",
+                "2",
+                "3",
+                "6",
+                "7",
+                "dropped 2 log records due to the channel overflow",
+                "8"
+            ]
+        );
+    }
+
+    #[test]
+    fn block_with_timeout() {
+        assert_eq!(
+            log_with_overflow(LogChannelOverflow::Block, 3, 5),
+            [
+                "1",
+                "2",
+                "3",
+                "4",
+                "dropped 1 log records due to the channel overflow",
+                "6"
+            ]
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use crate::telemetry::settings::LogCollapseSettings;
+use slog::{
+    Drain, KV, Key, Level, OwnedKVList, Record, RecordLocation, RecordStatic, Serializer, b,
+};
+use std::fmt::Arguments;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use thread_local::ThreadLocal;
+
+#[cfg(feature = "metrics")]
+use crate::telemetry::metrics::Counter;
+
+#[cfg(feature = "metrics")]
+#[crate::telemetry::metrics::metrics(crate_path = "crate")]
+mod foundations {
+    /// The number of log records collapsed into the previous identical record.
+    pub fn log_records_collapsed(level: &'static str) -> Counter;
+}
+
+/// A run of consecutive identical records.
+struct Run {
+    hash: u64,
+    started_at: Instant,
+    repeated: u64,
+    location: RecordLocation,
+    tag: String,
+    level: Level,
+    values: OwnedKVList,
+}
+
+/// A drain that collapses runs of identical log records into the first record of the run and a
+/// summary with the number of the repeated records.
+///
+/// The runs are tracked per thread, so the records of the other threads don't break them. The
+/// summaries of the runs that are not finished by a different record are logged once their
+/// window expires, on the next record of any thread, or when the drain is dropped.
+pub(crate) struct CollapsingDrain<D: Drain> {
+    inner: D,
+    window: Option<Duration>,
+    runs: ThreadLocal<Mutex<Option<Run>>>,
+    // NOTE: the number of the runs with repeated records, whose summaries are not logged yet.
+    pending: AtomicUsize,
+    created_at: Instant,
+    // NOTE: in milliseconds since `created_at`.
+    next_sweep_at: AtomicU64,
+}
+
+impl<D: Drain> CollapsingDrain<D> {
+    pub(crate) fn new(inner: D, settings: &LogCollapseSettings) -> Self {
+        Self {
+            inner,
+            window: settings
+                .enabled
+                .then(|| Duration::from_millis(settings.window_ms)),
+            runs: ThreadLocal::new(),
+            pending: AtomicUsize::new(0),
+            created_at: Instant::now(),
+            next_sweep_at: AtomicU64::new(0),
+        }
+    }
+
+    fn log_summary(&self, run: &Run) -> Result<(), D::Err> {
+        let record_static = RecordStatic {
+            location: &run.location,
+            tag: &run.tag,
+            level: run.level,
+        };
+
+        self.inner
+            .log(
+                &Record::new(
+                    &record_static,
+                    &format_args!("last message repeated {} times", run.repeated),
+                    b!("repeated_count" => run.repeated),
+                ),
+                &run.values,
+            )
+            .map(|_| ())
+    }
+
+    /// Logs the summaries of the runs of all the threads whose window has expired. The runs are
+    /// checked at most once per window.
+    fn log_expired_summaries(&self, now: Instant, window: Duration) -> Result<(), D::Err> {
+        if self.pending.load(Ordering::Relaxed) == 0 {
+            return Ok(());
+        }
+
+        let now_ms = now.duration_since(self.created_at).as_millis() as u64;
+        let next_sweep_at = self.next_sweep_at.load(Ordering::Relaxed);
+
+        if now_ms < next_sweep_at
+            || self
+                .next_sweep_at
+                .compare_exchange(
+                    next_sweep_at,
+                    now_ms + window.as_millis() as u64,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return Ok(());
+        }
+
+        for run in self.runs.iter() {
+            // NOTE: the run is being updated by its thread, which also checks its window.
+            let Ok(mut run) = run.try_lock() else {
+                continue;
+            };
+
+            let expired =
+                run.take_if(|run| run.repeated > 0 && now.duration_since(run.started_at) >= window);
+
+            drop(run);
+
+            if let Some(expired) = expired {
+                self.pending.fetch_sub(1, Ordering::Relaxed);
+                self.log_summary(&expired)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Logs the record as if it was logged at `now`, so the tests can control the time.
+    fn log_at(&self, record: &Record, values: &OwnedKVList, now: Instant) -> Result<(), D::Err> {
+        let Some(window) = self.window else {
+            return self.inner.log(record, values).map(|_| ());
+        };
+
+        self.log_expired_summaries(now, window)?;
+
+        // NOTE: the records with the fields that can't be serialized are never collapsed.
+        let Some(hash) = record_hash(record, values) else {
+            return self.inner.log(record, values).map(|_| ());
+        };
+
+        let mut run = self
+            .runs
+            .get_or_default()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(run) = &mut *run
+            && run.hash == hash
+            && now.duration_since(run.started_at) < window
+        {
+            if run.repeated == 0 {
+                self.pending.fetch_add(1, Ordering::Relaxed);
+            }
+
+            run.repeated += 1;
+
+            #[cfg(feature = "metrics")]
+            foundations::log_records_collapsed(record.level().as_str()).inc();
+
+            return Ok(());
+        }
+
+        let finished = run.replace(Run {
+            hash,
+            started_at: now,
+            repeated: 0,
+            location: *record.location(),
+            tag: record.tag().to_string(),
+            level: record.level(),
+            values: values.clone(),
+        });
+
+        drop(run);
+
+        if let Some(finished) = finished
+            && finished.repeated > 0
+        {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+            self.log_summary(&finished)?;
+        }
+
+        self.inner.log(record, values).map(|_| ())
+    }
+}
+
+impl<D: Drain> Drain for CollapsingDrain<D> {
+    type Ok = ();
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        self.log_at(record, values, Instant::now())
+    }
+
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        Drain::is_enabled(&self.inner, level)
+    }
+
+    #[inline]
+    fn flush(&self) -> Result<(), slog::FlushError> {
+        Drain::flush(&self.inner)
+    }
+}
+
+impl<D: Drain> Drop for CollapsingDrain<D> {
+    fn drop(&mut self) {
+        let pending: Vec<_> = self
+            .runs
+            .iter_mut()
+            .filter_map(|run| {
+                run.get_mut()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take_if(|run| run.repeated > 0)
+            })
+            .collect();
+
+        for run in pending {
+            let _ = self.log_summary(&run);
+        }
+    }
+}
+
+/// Hashes the level, message and fields of the record.
+fn record_hash(record: &Record, values: &OwnedKVList) -> Option<u64> {
+    let mut hasher = HashingSerializer(DefaultHasher::new());
+
+    record.level().as_usize().hash(&mut hasher.0);
+    record.msg().to_string().hash(&mut hasher.0);
+    record.kv().serialize(record, &mut hasher).ok()?;
+    values.serialize(record, &mut hasher).ok()?;
+
+    Some(hasher.0.finish())
+}
+
+struct HashingSerializer(DefaultHasher);
+
+impl Serializer for HashingSerializer {
+    fn emit_arguments(&mut self, key: Key, val: &Arguments) -> slog::Result {
+        key.hash(&mut self.0);
+        val.to_string().hash(&mut self.0);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{Logger, Never, o};
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct CollectingDrain(Arc<Mutex<Vec<String>>>);
+
+    impl Drain for CollectingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+            self.0.lock().unwrap().push(record.msg().to_string());
+
+            Ok(())
+        }
+    }
+
+    fn collapsing_drain(window_ms: u64) -> (CollapsingDrain<CollectingDrain>, CollectingDrain) {
+        let output = CollectingDrain::default();
+
+        let settings = LogCollapseSettings {
+            enabled: true,
+            window_ms,
+        };
+
+        (CollapsingDrain::new(output.clone(), &settings), output)
+    }
+
+    fn collapsing_log(window_ms: u64) -> (Logger, CollectingDrain) {
+        let (drain, output) = collapsing_drain(window_ms);
+
+        (Logger::root(drain.fuse(), o!()), output)
+    }
+
+    fn log_at(drain: &CollapsingDrain<CollectingDrain>, msg: &str, now: Instant) {
+        let _ = drain.log_at(
+            &slog::record!(Level::Warning, "", &format_args!("{msg}"), b!()),
+            &OwnedKVList::from(o!()),
+            now,
+        );
+    }
+
+    #[test]
+    fn runs_not_broken_by_other_threads() {
+        let (log, output) = collapsing_log(10000);
+
+        for i in 0..3 {
+            slog::warn!(log, "repeated");
+
+            let log = log.clone();
+
+            std::thread::spawn(move || slog::info!(log, "other thread {}", i))
+                .join()
+                .unwrap();
+        }
+
+        slog::info!(log, "done");
+        drop(log);
+
+        assert_eq!(
+            output.0.lock().unwrap().clone(),
+            [
+                "repeated",
+                "other thread 0",
+                "other thread 1",
+                "other thread 2",
+                "last message repeated 2 times",
+                "done"
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_summaries_logged() {
+        let (drain, output) = collapsing_drain(50);
+        let start = drain.created_at;
+        let at = |ms| start + Duration::from_millis(ms);
+
+        log_at(&drain, "repeated", at(0));
+        log_at(&drain, "repeated", at(10));
+
+        // NOTE: the run of this thread is summarized by the record of another thread only once
+        // its window expires.
+        std::thread::scope(|s| {
+            s.spawn(|| log_at(&drain, "other thread", at(40)));
+        });
+
+        std::thread::scope(|s| {
+            s.spawn(|| log_at(&drain, "other thread", at(100)));
+        });
+
+        log_at(&drain, "last", at(110));
+        log_at(&drain, "last", at(120));
+
+        drop(drain);
+
+        assert_eq!(
+            output.0.lock().unwrap().clone(),
+            [
+                "repeated",
+                "other thread",
+                "last message repeated 1 times",
+                "other thread",
+                "last",
+                "last message repeated 1 times"
+            ]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Collection of the log record key-values in the order of their declaration.
+
+use slog::{KV, Key, OwnedKVList, Record, Serializer};
+use std::fmt;
+
+/// A serializer that accumulates the log record key-values.
+pub(crate) trait FieldCollector: Serializer + Default {
+    /// The collected representation of a key-value.
+    type Field;
+
+    /// Returns the key-values in the order they were serialized.
+    fn into_fields(self) -> Vec<Self::Field>;
+}
+
+/// Collects the record and logger key-values, with the record fields followed by the logger
+/// fields, each in the order of their declaration.
+pub(crate) fn collect_fields<C: FieldCollector>(
+    record: &Record,
+    values: &OwnedKVList,
+) -> Result<Vec<C::Field>, slog::Error> {
+    let mut collector = C::default();
+
+    // NOTE: slog serializes the key-values in the reverse order of their declaration, with the
+    // logger fields serialized first, so restore the original order.
+    values.serialize(record, &mut collector)?;
+    record.kv().serialize(record, &mut collector)?;
+
+    let mut fields = collector.into_fields();
+
+    fields.reverse();
+
+    Ok(fields)
+}
+
+/// Collects the log record key-values as strings.
+#[derive(Default)]
+pub(crate) struct StringFields(Vec<(String, String)>);
+
+impl FieldCollector for StringFields {
+    type Field = (String, String);
+
+    fn into_fields(self) -> Vec<Self::Field> {
+        self.0
+    }
+}
+
+impl Serializer for StringFields {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.push((key.to_string(), val.to_string()));
+
+        Ok(())
+    }
+}
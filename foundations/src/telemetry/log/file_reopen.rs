@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use crate::telemetry::settings::LogReopenSignal;
+#[cfg(unix)]
+use crate::telemetry::signal::install_signal_handler;
+#[cfg(unix)]
+use std::ffi::{c_int, c_void};
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+// NOTE: incremented on each reopen request, so the writers can detect the requests that
+// happened since they've (re)opened their files. Also modified from the signal handler, so
+// must be an atomic.
+static REOPEN_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// How often the log file path is checked for being renamed or unlinked.
+const PATH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Requests the file log output to reopen the log file.
+///
+/// The file is reopened on the next log record, so the records emitted after the call are
+/// written to the file currently located at the path specified in [`LogOutput::File`]. This is
+/// useful with external log rotation tools that rename the log file, e.g. [logrotate], as
+/// otherwise the log keeps being written into the renamed file.
+///
+/// The same can be achieved by sending [`LoggingSettings::reopen_signal`] to the process.
+///
+/// Note that the file log output also reopens the file automatically if it detects that the file
+/// has been renamed or removed, but the detection is performed at most once per second.
+///
+/// [`LogOutput::File`]: crate::telemetry::settings::LogOutput::File
+/// [`LoggingSettings::reopen_signal`]: crate::telemetry::settings::LoggingSettings::reopen_signal
+/// [logrotate]: https://man7.org/linux/man-pages/man8/logrotate.8.html
+pub fn reopen_file() {
+    REOPEN_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Installs a handler for the signal that requests log file reopening.
+#[cfg(unix)]
+pub(super) fn install_reopen_signal_handler(signal: LogReopenSignal) -> io::Result<()> {
+    extern "C" fn on_reopen_signal(
+        _signum: c_int,
+        _info: *mut libc::siginfo_t,
+        _ucontext: *mut c_void,
+    ) {
+        reopen_file();
+    }
+
+    install_signal_handler(signal.signum(), on_reopen_signal)
+}
+
+/// Tracks whether a log file needs to be reopened, either due to an explicit request or due to
+/// the file being renamed or removed.
+pub(super) struct ReopenTracker {
+    generation: u64,
+    path_checked_at: Instant,
+    #[cfg(unix)]
+    file_id: Option<(u64, u64)>,
+}
+
+impl ReopenTracker {
+    pub(super) fn new(file: &File) -> Self {
+        #[cfg(not(unix))]
+        let _ = file;
+
+        Self {
+            generation: REOPEN_GENERATION.load(Ordering::SeqCst),
+            path_checked_at: Instant::now(),
+            #[cfg(unix)]
+            file_id: file_id(file),
+        }
+    }
+
+    /// Returns `true` if the file at the path needs to be reopened.
+    pub(super) fn should_reopen(&mut self, path: &Path) -> bool {
+        if REOPEN_GENERATION.load(Ordering::SeqCst) != self.generation {
+            return true;
+        }
+
+        if self.path_checked_at.elapsed() < PATH_CHECK_INTERVAL {
+            return false;
+        }
+
+        self.path_checked_at = Instant::now();
+
+        #[cfg(unix)]
+        {
+            // NOTE: the file might not exist at the path anymore if it was removed or renamed.
+            let path_file_id = path.metadata().ok().map(|meta| (meta.dev(), meta.ino()));
+
+            self.file_id.is_some() && path_file_id != self.file_id
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+
+            false
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(file: &File) -> Option<(u64, u64)> {
+    file.metadata().ok().map(|meta| (meta.dev(), meta.ino()))
+}
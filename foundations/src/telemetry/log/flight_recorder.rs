@@ -0,0 +1,560 @@
+use super::init::SharedDrain;
+use super::internal::{LoggerWithKvNestingTracking, current_log};
+use crate::telemetry::settings::{LogFlightRecorderScope, LogFlightRecorderSettings};
+use chrono::{DateTime, SecondsFormat, Utc};
+use crossbeam_utils::CachePadded;
+use slog::{
+    BorrowedKV, Drain, KV, Key, Level, OwnedKVList, Record, RecordLocation, RecordStatic,
+    Serializer, SingleKV,
+};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+// NOTE: the buffers of the dead contexts are removed once the number of the buffers reaches the
+// threshold, which then grows with the number of the live contexts.
+const MIN_PRUNE_THRESHOLD: usize = 64;
+
+// NOTE: the buffers are split between the shards, so the threads recording the records of
+// different contexts don't contend for the same lock.
+const SHARD_COUNT: usize = 16;
+
+static NEXT_THREAD_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_SHARD: usize = NEXT_THREAD_SHARD.fetch_add(1, Ordering::Relaxed) % SHARD_COUNT;
+}
+
+type WeakLog = Weak<parking_lot::RwLock<LoggerWithKvNestingTracking>>;
+
+/// A log record that was filtered out by the verbosity.
+struct RecordedRecord {
+    location: RecordLocation,
+    tag: String,
+    level: Level,
+    message: String,
+    fields: RecordedFields,
+    values: OwnedKVList,
+    recorded_at: DateTime<Utc>,
+    // NOTE: restores the order of the global records recorded in the different shards.
+    seq: u64,
+}
+
+/// Record fields in the order of their serialization.
+#[derive(Default)]
+struct RecordedFields(Vec<(Key, String)>);
+
+impl Serializer for RecordedFields {
+    fn emit_arguments(&mut self, key: Key, val: &Arguments) -> slog::Result {
+        self.0.push((key, val.to_string()));
+
+        Ok(())
+    }
+}
+
+impl KV for RecordedFields {
+    fn serialize(&self, _record: &Record, serializer: &mut dyn Serializer) -> slog::Result {
+        for (key, value) in &self.0 {
+            serializer.emit_str(key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct RecordBuffer {
+    // NOTE: `None` for the global buffer.
+    log: Option<WeakLog>,
+    records: VecDeque<RecordedRecord>,
+}
+
+impl RecordBuffer {
+    fn is_alive(&self) -> bool {
+        self.log.as_ref().is_none_or(|log| log.strong_count() > 0)
+    }
+}
+
+#[derive(Default)]
+struct RecordBuffers {
+    by_scope: HashMap<usize, RecordBuffer>,
+    prune_threshold: usize,
+}
+
+struct Shard {
+    buffers: Mutex<RecordBuffers>,
+    // NOTE: sequence number of the oldest record in the shard's part of the global buffer, or
+    // `u64::MAX` if there are none, so the oldest record can be found without locking the shards.
+    oldest_global_seq: AtomicU64,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self {
+            buffers: Default::default(),
+            oldest_global_seq: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+impl Shard {
+    fn set_oldest_global(&self, buffer: Option<&RecordBuffer>) {
+        let seq = buffer
+            .and_then(|buffer| buffer.records.front())
+            .map_or(u64::MAX, |recorded| recorded.seq);
+
+        self.oldest_global_seq.store(seq, Ordering::Relaxed);
+    }
+}
+
+/// Keeps the recent log records that were filtered out by the verbosity, so they can be emitted
+/// later if something fails.
+pub(crate) struct FlightRecorder {
+    root_drain: SharedDrain,
+    capacity: usize,
+    level: Level,
+    scope: LogFlightRecorderScope,
+    shards: Box<[CachePadded<Shard>]>,
+    next_seq: AtomicU64,
+    // NOTE: the number of the records in all the parts of the global buffer, so the capacity
+    // bounds the global buffer as a whole rather than each of its parts.
+    global_len: AtomicUsize,
+}
+
+impl FlightRecorder {
+    pub(crate) fn new(
+        settings: &LogFlightRecorderSettings,
+        root_drain: SharedDrain,
+    ) -> Option<Arc<Self>> {
+        if !settings.enabled || settings.capacity == 0 {
+            return None;
+        }
+
+        Some(Arc::new(Self {
+            root_drain,
+            capacity: settings.capacity,
+            level: settings.verbosity.into(),
+            scope: settings.scope,
+            shards: (0..SHARD_COUNT).map(|_| Default::default()).collect(),
+            next_seq: AtomicU64::new(0),
+            global_len: AtomicUsize::new(0),
+        }))
+    }
+
+    /// The most verbose level of the recorded records.
+    pub(crate) fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Adds the record to the buffer of the current scope.
+    pub(crate) fn record(&self, record: &Record, values: &OwnedKVList) {
+        if !record.level().is_at_least(self.level) {
+            return;
+        }
+
+        let mut fields = RecordedFields::default();
+
+        // NOTE: the record is dropped if its fields can't be serialized, the same way as
+        // it would be by the output drains.
+        if record.kv().serialize(record, &mut fields).is_err() {
+            return;
+        }
+
+        let recorded = RecordedRecord {
+            location: *record.location(),
+            tag: record.tag().to_string(),
+            level: record.level(),
+            message: record.msg().to_string(),
+            fields,
+            values: values.clone(),
+            recorded_at: Utc::now(),
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+        };
+
+        let (scope_key, log) = self.current_scope();
+        let shard = self.shard(scope_key);
+        let mut buffers = shard.buffers.lock().unwrap();
+
+        if !buffers.by_scope.contains_key(&scope_key) {
+            buffers.prune();
+        }
+
+        let buffer = buffers
+            .by_scope
+            .entry(scope_key)
+            .or_insert_with(|| RecordBuffer {
+                log,
+                records: VecDeque::with_capacity(self.capacity.min(MIN_PRUNE_THRESHOLD)),
+            });
+
+        if let LogFlightRecorderScope::Context = self.scope {
+            if buffer.records.len() == self.capacity {
+                buffer.records.pop_front();
+            }
+
+            buffer.records.push_back(recorded);
+
+            return;
+        }
+
+        buffer.records.push_back(recorded);
+
+        let is_full = self.global_len.fetch_add(1, Ordering::Relaxed) >= self.capacity;
+        let oldest_shard = self.shard_with_oldest_global();
+
+        if is_full && std::ptr::eq(shard, oldest_shard) {
+            buffer.records.pop_front();
+            self.global_len.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        shard.set_oldest_global(Some(buffer));
+
+        drop(buffers);
+
+        if is_full && !std::ptr::eq(shard, oldest_shard) {
+            self.evict_oldest_global(oldest_shard);
+        }
+    }
+
+    /// Emits the records from the buffer of the current scope. Returns the number of the emitted
+    /// records.
+    pub(crate) fn flush_current(&self) -> usize {
+        let (scope_key, _) = self.current_scope();
+
+        let records = match self.scope {
+            LogFlightRecorderScope::Global => self.take_global(),
+            LogFlightRecorderScope::Context => self
+                .shard(scope_key)
+                .buffers
+                .lock()
+                .unwrap()
+                .by_scope
+                .remove(&scope_key)
+                .map(|buffer| buffer.records)
+                .unwrap_or_default(),
+        };
+
+        self.emit(records)
+    }
+
+    /// Emits the records from all the buffers. Returns the number of the emitted records.
+    #[cfg(feature = "telemetry-server")]
+    pub(crate) fn flush_all(&self) -> usize {
+        if let LogFlightRecorderScope::Global = self.scope {
+            return self.emit(self.take_global());
+        }
+
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                std::mem::take(&mut shard.buffers.lock().unwrap().by_scope).into_values()
+            })
+            .map(|buffer| self.emit(buffer.records))
+            .sum()
+    }
+
+    /// Takes the most recent records of the global buffer from all the shards.
+    fn take_global(&self) -> VecDeque<RecordedRecord> {
+        let mut records: Vec<_> = self
+            .shards
+            .iter()
+            .filter_map(|shard| {
+                let buffer = shard.buffers.lock().unwrap().by_scope.remove(&0);
+
+                shard.set_oldest_global(None);
+
+                buffer
+            })
+            .flat_map(|buffer| buffer.records)
+            .collect();
+
+        self.global_len.fetch_sub(records.len(), Ordering::Relaxed);
+
+        records.sort_unstable_by_key(|recorded| recorded.seq);
+
+        // NOTE: the buffer can briefly exceed the capacity while the records are being recorded
+        // concurrently.
+        let excess = records.len().saturating_sub(self.capacity);
+
+        records.into_iter().skip(excess).collect()
+    }
+
+    fn shard_with_oldest_global(&self) -> &Shard {
+        self.shards
+            .iter()
+            .min_by_key(|shard| shard.oldest_global_seq.load(Ordering::Relaxed))
+            .expect("there is at least one shard")
+    }
+
+    /// Removes the oldest record of the global buffer from the shard.
+    fn evict_oldest_global(&self, shard: &Shard) {
+        let mut buffers = shard.buffers.lock().unwrap();
+        let buffer = buffers.by_scope.get_mut(&0);
+
+        // NOTE: the shard might have been emptied since it was picked, as the shards are never
+        // locked at the same time. The next recorded record evicts one instead.
+        if let Some(buffer) = buffer
+            && buffer.records.pop_front().is_some()
+        {
+            self.global_len.fetch_sub(1, Ordering::Relaxed);
+            shard.set_oldest_global(Some(buffer));
+        }
+    }
+
+    /// Returns the shard with the buffer of the scope.
+    ///
+    /// Each thread has its own part of the global buffer, while the buffers of the contexts are
+    /// spread between the shards by their keys. The parts of the global buffer share
+    /// the capacity, and the oldest record among all of them is evicted once it's reached.
+    fn shard(&self, scope_key: usize) -> &Shard {
+        let idx = match self.scope {
+            LogFlightRecorderScope::Global => THREAD_SHARD.with(|shard| *shard),
+            // NOTE: the keys are the addresses of the logs, so the low bits are always the same.
+            LogFlightRecorderScope::Context => (scope_key >> 4) % SHARD_COUNT,
+        };
+
+        &self.shards[idx]
+    }
+
+    fn current_scope(&self) -> (usize, Option<WeakLog>) {
+        match self.scope {
+            LogFlightRecorderScope::Global => (0, None),
+            LogFlightRecorderScope::Context => {
+                let log = current_log();
+
+                // NOTE: the buffer keeps a weak reference to the log, so the address can't be
+                // reused by another context while the buffer exists.
+                (Arc::as_ptr(&log) as usize, Some(Arc::downgrade(&log)))
+            }
+        }
+    }
+
+    fn emit(&self, records: VecDeque<RecordedRecord>) -> usize {
+        let count = records.len();
+
+        for recorded in records {
+            let record_static = RecordStatic {
+                location: &recorded.location,
+                tag: &recorded.tag,
+                level: recorded.level,
+            };
+
+            let recorded_at = recorded
+                .recorded_at
+                .to_rfc3339_opts(SecondsFormat::Millis, true);
+
+            let kv = (&recorded.fields, SingleKV("recorded_at", recorded_at));
+
+            let _ = self.root_drain.log(
+                &Record::new(
+                    &record_static,
+                    &format_args!("{}", recorded.message),
+                    BorrowedKV(&kv),
+                ),
+                &recorded.values,
+            );
+        }
+
+        count
+    }
+}
+
+impl RecordBuffers {
+    fn prune(&mut self) {
+        if self.by_scope.len() < self.prune_threshold {
+            return;
+        }
+
+        self.by_scope.retain(|_, buffer| buffer.is_alive());
+        self.prune_threshold = (self.by_scope.len() * 2).max(MIN_PRUNE_THRESHOLD);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::settings::{
+        LogFlightRecorderScope, LogFlightRecorderSettings, LogVerbosity, LoggingSettings,
+    };
+    use crate::telemetry::{TelemetryContext, TestTelemetryContext, log};
+    use foundations_macros::with_test_telemetry;
+    use slog::Level;
+
+    #[with_test_telemetry(test, crate_path = "crate")]
+    fn flush_on_error(mut ctx: TestTelemetryContext) {
+        ctx.set_logging_settings(LoggingSettings {
+            verbosity: LogVerbosity::Info,
+            flight_recorder: LogFlightRecorderSettings {
+                enabled: true,
+                capacity: 2,
+                scope: LogFlightRecorderScope::Context,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        log::trace!("not recorded");
+        log::debug!("dropped by the capacity");
+        log::debug!("debug 1"; "attempt" => 1);
+        log::info!("info");
+
+        {
+            let _scope = TelemetryContext::current().with_forked_log().scope();
+
+            log::debug!("other context");
+            log::error!("other error");
+        }
+
+        log::debug!("debug 2");
+
+        let messages = |ctx: &TestTelemetryContext| {
+            ctx.log_records()
+                .iter()
+                .map(|r| (r.level, r.message.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            messages(&ctx),
+            vec![
+                (Level::Info, "info".into()),
+                (Level::Debug, "other context".into()),
+                (Level::Error, "other error".into()),
+            ]
+        );
+
+        log::error!("error");
+
+        assert_eq!(
+            messages(&ctx)[3..],
+            [
+                (Level::Debug, "debug 1".into()),
+                (Level::Debug, "debug 2".into()),
+                (Level::Error, "error".into()),
+            ]
+        );
+
+        let records = ctx.log_records();
+
+        assert_eq!(records[3].fields[0], ("attempt".into(), "1".into()));
+        assert_eq!(records[3].fields[1].0, "recorded_at");
+        assert!(records[5].fields.is_empty());
+        drop(records);
+
+        log::error!("error without records");
+
+        assert_eq!(ctx.log_records().len(), 7);
+    }
+
+    #[with_test_telemetry(test, crate_path = "crate")]
+    fn global_records_from_threads(mut ctx: TestTelemetryContext) {
+        ctx.set_logging_settings(LoggingSettings {
+            verbosity: LogVerbosity::Info,
+            flight_recorder: LogFlightRecorderSettings {
+                enabled: true,
+                capacity: 3,
+                scope: LogFlightRecorderScope::Global,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        for i in 0..4 {
+            let telemetry_ctx = TelemetryContext::current();
+
+            std::thread::spawn(move || {
+                let _scope = telemetry_ctx.scope();
+
+                log::debug!("thread {i}");
+            })
+            .join()
+            .unwrap();
+        }
+
+        log::error!("error");
+
+        let messages: Vec<_> = ctx
+            .log_records()
+            .iter()
+            .map(|r| r.message.clone())
+            .collect();
+
+        assert_eq!(messages, ["thread 1", "thread 2", "thread 3", "error"]);
+    }
+
+    #[test]
+    fn global_capacity_shared_by_threads() {
+        struct RecordingDrain(Arc<FlightRecorder>);
+
+        impl Drain for RecordingDrain {
+            type Ok = ();
+            type Err = slog::Never;
+
+            fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), slog::Never> {
+                self.0.record(record, values);
+
+                Ok(())
+            }
+        }
+
+        let settings = LogFlightRecorderSettings {
+            enabled: true,
+            capacity: 20,
+            scope: LogFlightRecorderScope::Global,
+            ..Default::default()
+        };
+
+        let root_drain: SharedDrain =
+            Arc::new(slog::Discard.map_err(|e| Box::new(e) as Box<dyn std::fmt::Debug>));
+        let recorder = FlightRecorder::new(&settings, root_drain).unwrap();
+        let log = slog::Logger::root(RecordingDrain(Arc::clone(&recorder)), slog::o!());
+
+        // NOTE: more threads than shards, so some threads share the parts of the global buffer.
+        for i in 0..SHARD_COUNT * 2 + 1 {
+            let log = log.clone();
+
+            std::thread::spawn(move || {
+                for j in 0..5 {
+                    slog::debug!(log, "{i}-{j}");
+                }
+            })
+            .join()
+            .unwrap();
+        }
+
+        let buffered: usize = recorder
+            .shards
+            .iter()
+            .filter_map(|shard| {
+                let buffers = shard.buffers.lock().unwrap();
+
+                buffers.by_scope.get(&0).map(|buffer| buffer.records.len())
+            })
+            .sum();
+
+        assert_eq!(buffered, 20);
+
+        let messages: Vec<_> = recorder
+            .take_global()
+            .into_iter()
+            .map(|recorded| recorded.message)
+            .collect();
+
+        let last = SHARD_COUNT * 2;
+        let expected: Vec<_> = (last - 3..=last)
+            .flat_map(|i| (0..5).map(move |j| format!("{i}-{j}")))
+            .collect();
+
+        assert_eq!(messages, expected);
+    }
+
+    #[test]
+    fn default_settings() {
+        let settings = LogFlightRecorderSettings::default();
+
+        assert!(!settings.enabled);
+        assert_eq!(settings.capacity, 1000);
+        assert_eq!(settings.verbosity, LogVerbosity::Debug);
+        assert!(matches!(settings.scope, LogFlightRecorderScope::Global));
+    }
+}
@@ -0,0 +1,514 @@
+use super::fields::{FieldCollector, collect_fields};
+use super::syslog::{hostname, severity};
+use crate::telemetry::settings::{LogFormat, LogTimestampPrecision, LogTimezone, LoggingSettings};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use slog::{Drain, Key, Level, OwnedKVList, Record, Serializer};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+
+// https://www.elastic.co/guide/en/ecs/current/ecs-ecs.html
+const ECS_VERSION: &str = "8.11.0";
+
+/// Format of the log record timestamps resolved from the settings.
+#[derive(Clone, Copy)]
+pub(super) struct TimestampFormat {
+    precision: Option<LogTimestampPrecision>,
+    timezone: LogTimezone,
+}
+
+impl TimestampFormat {
+    pub(super) fn new(settings: &LoggingSettings) -> Self {
+        let default_timezone = match settings.format {
+            LogFormat::Text => LogTimezone::Local,
+            _ => LogTimezone::Utc,
+        };
+
+        Self {
+            precision: settings.timestamp.precision,
+            timezone: settings.timestamp.timezone.unwrap_or(default_timezone),
+        }
+    }
+
+    /// Formats the time as an [RFC 3339] timestamp.
+    ///
+    /// [RFC 3339]: https://datatracker.ietf.org/doc/html/rfc3339
+    pub(super) fn rfc3339(self, time: DateTime<Utc>) -> String {
+        let seconds_format = match self.precision.unwrap_or_default() {
+            LogTimestampPrecision::Seconds => SecondsFormat::Secs,
+            LogTimestampPrecision::Millis => SecondsFormat::Millis,
+            LogTimestampPrecision::Micros => SecondsFormat::Micros,
+            LogTimestampPrecision::Nanos => SecondsFormat::Nanos,
+        };
+
+        match self.timezone {
+            LogTimezone::Local => time
+                .with_timezone(&Local)
+                .to_rfc3339_opts(seconds_format, false),
+            LogTimezone::Utc => time.to_rfc3339_opts(seconds_format, true),
+        }
+    }
+
+    /// Formats the time in the human-readable format of the text logs, e.g. `Jan 02 15:04:05.000`.
+    pub(super) fn text(self, time: DateTime<Utc>) -> String {
+        let format = match self.precision.unwrap_or_default() {
+            LogTimestampPrecision::Seconds => "%b %d %H:%M:%S",
+            LogTimestampPrecision::Millis => "%b %d %H:%M:%S%.3f",
+            LogTimestampPrecision::Micros => "%b %d %H:%M:%S%.6f",
+            LogTimestampPrecision::Nanos => "%b %d %H:%M:%S%.9f",
+        };
+
+        match self.timezone {
+            LogTimezone::Local => time.with_timezone(&Local).format(format).to_string(),
+            LogTimezone::Utc => time.format(format).to_string(),
+        }
+    }
+
+    /// Formats the time as Unix timestamp in seconds with the fractional part.
+    fn unix(self, time: DateTime<Utc>) -> String {
+        let secs = time.timestamp();
+        let nanos = time.timestamp_subsec_nanos();
+
+        match self.precision.unwrap_or_default() {
+            LogTimestampPrecision::Seconds => secs.to_string(),
+            LogTimestampPrecision::Millis => format!("{secs}.{:03}", nanos / 1_000_000),
+            LogTimestampPrecision::Micros => format!("{secs}.{:06}", nanos / 1_000),
+            LogTimestampPrecision::Nanos => format!("{secs}.{nanos:09}"),
+        }
+    }
+}
+
+/// Log record field value that preserves the primitive types.
+enum FieldValue {
+    Str(String),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    Bool(bool),
+}
+
+macro_rules! emit {
+    ($($name:ident: $ty:ty => $variant:ident),*) => {
+        $(
+            fn $name(&mut self, key: Key, val: $ty) -> slog::Result {
+                self.0.push((key.to_string(), FieldValue::$variant(val.into())));
+
+                Ok(())
+            }
+        )*
+    };
+}
+
+/// Collects the log record key-values with their types preserved.
+#[derive(Default)]
+struct TypedFields(Vec<(String, FieldValue)>);
+
+impl FieldCollector for TypedFields {
+    type Field = (String, FieldValue);
+
+    fn into_fields(self) -> Vec<Self::Field> {
+        self.0
+    }
+}
+
+impl Serializer for TypedFields {
+    emit!(
+        emit_i8: i8 => Int, emit_i16: i16 => Int, emit_i32: i32 => Int, emit_i64: i64 => Int,
+        emit_u8: u8 => Uint, emit_u16: u16 => Uint, emit_u32: u32 => Uint, emit_u64: u64 => Uint,
+        emit_f32: f32 => Float, emit_f64: f64 => Float, emit_bool: bool => Bool,
+        emit_str: &str => Str
+    );
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.0
+            .push((key.to_string(), FieldValue::Str(val.to_string())));
+
+        Ok(())
+    }
+}
+
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Critical => "critical",
+        Level::Error => "error",
+        Level::Warning => "warn",
+        Level::Info => "info",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+    }
+}
+
+/// A drain that writes log records in the format specified by [`LineFormat`], one record
+/// per line.
+pub(super) struct LineDrain<W: Write> {
+    output: RefCell<W>,
+    format: LineFormat,
+    timestamp: TimestampFormat,
+}
+
+/// Line-based formats that are not supported by the `slog` ecosystem crates.
+pub(super) enum LineFormat {
+    Logfmt,
+    Ecs,
+    Gelf { host: String },
+}
+
+impl LineFormat {
+    /// Returns the line format for the log format, if it's one of the line formats.
+    pub(super) fn new(format: LogFormat) -> Option<Self> {
+        match format {
+            LogFormat::Text | LogFormat::Json => None,
+            LogFormat::Logfmt => Some(Self::Logfmt),
+            LogFormat::Ecs => Some(Self::Ecs),
+            LogFormat::Gelf => Some(Self::Gelf { host: hostname() }),
+        }
+    }
+}
+
+impl<W: Write> LineDrain<W> {
+    pub(super) fn new(output: W, format: LineFormat, timestamp: TimestampFormat) -> Self {
+        Self {
+            output: RefCell::new(output),
+            format,
+            timestamp,
+        }
+    }
+
+    fn format_record(
+        &self,
+        record: &Record,
+        values: &OwnedKVList,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<u8>, slog::Error> {
+        let fields = collect_fields::<TypedFields>(record, values)?;
+        let mut line = vec![];
+
+        match &self.format {
+            LineFormat::Logfmt => self.write_logfmt(&mut line, record, &fields, now)?,
+            LineFormat::Ecs => self.write_ecs(&mut line, record, &fields, now)?,
+            LineFormat::Gelf { host } => self.write_gelf(&mut line, record, &fields, host, now)?,
+        }
+
+        line.push(b'\n');
+
+        Ok(line)
+    }
+
+    fn write_logfmt(
+        &self,
+        line: &mut Vec<u8>,
+        record: &Record,
+        fields: &[(String, FieldValue)],
+        now: DateTime<Utc>,
+    ) -> io::Result<()> {
+        write!(
+            line,
+            "ts={} level={} msg=",
+            self.timestamp.rfc3339(now),
+            level_name(record.level())
+        )?;
+        write_logfmt_str(line, &record.msg().to_string())?;
+
+        for (key, value) in fields {
+            let key: String = key
+                .chars()
+                .map(|c| match c {
+                    '=' | '"' => '_',
+                    c if c.is_whitespace() || c.is_control() => '_',
+                    c => c,
+                })
+                .collect();
+
+            write!(line, " {key}=")?;
+
+            match value {
+                FieldValue::Str(val) => write_logfmt_str(line, val)?,
+                FieldValue::Int(val) => write!(line, "{val}")?,
+                FieldValue::Uint(val) => write!(line, "{val}")?,
+                FieldValue::Float(val) => write!(line, "{val}")?,
+                FieldValue::Bool(val) => write!(line, "{val}")?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_ecs(
+        &self,
+        line: &mut Vec<u8>,
+        record: &Record,
+        fields: &[(String, FieldValue)],
+        now: DateTime<Utc>,
+    ) -> io::Result<()> {
+        let mut obj = JsonObjectWriter::new(line)?;
+
+        obj.str("@timestamp", &self.timestamp.rfc3339(now))?;
+        obj.str("log.level", level_name(record.level()))?;
+        obj.str("message", &record.msg().to_string())?;
+        obj.str("ecs.version", ECS_VERSION)?;
+        obj.str("log.logger", record.module())?;
+        obj.str("log.origin.file.name", record.file())?;
+        obj.raw("log.origin.file.line", &record.line().to_string())?;
+
+        if !record.function().is_empty() {
+            obj.str("log.origin.function", record.function())?;
+        }
+
+        for (key, value) in fields {
+            obj.field(key, value)?;
+        }
+
+        obj.end()
+    }
+
+    fn write_gelf(
+        &self,
+        line: &mut Vec<u8>,
+        record: &Record,
+        fields: &[(String, FieldValue)],
+        host: &str,
+        now: DateTime<Utc>,
+    ) -> io::Result<()> {
+        let mut obj = JsonObjectWriter::new(line)?;
+
+        obj.str("version", "1.1")?;
+        obj.str("host", host)?;
+        obj.str("short_message", &record.msg().to_string())?;
+        obj.raw("timestamp", &self.timestamp.unix(now))?;
+        obj.raw("level", &severity(record.level()).to_string())?;
+
+        for (key, value) in fields {
+            obj.field(&gelf_field_name(key), value)?;
+        }
+
+        obj.end()
+    }
+}
+
+impl<W: Write> Drain for LineDrain<W> {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let line = self.format_record(record, values, Utc::now())?;
+        let mut output = self.output.borrow_mut();
+
+        output.write_all(&line)?;
+        output.flush()
+    }
+}
+
+/// Writes the logfmt value, quoting and escaping it if necessary.
+fn write_logfmt_str(line: &mut Vec<u8>, value: &str) -> io::Result<()> {
+    let needs_quoting = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == '"' || c == '=' || c == '\\' || c.is_whitespace() || c.is_control());
+
+    if !needs_quoting {
+        return line.write_all(value.as_bytes());
+    }
+
+    line.push(b'"');
+
+    for c in value.chars() {
+        match c {
+            '"' => line.extend_from_slice(b"\\\""),
+            '\\' => line.extend_from_slice(b"\\\\"),
+            '\n' => line.extend_from_slice(b"\\n"),
+            '\r' => line.extend_from_slice(b"\\r"),
+            '\t' => line.extend_from_slice(b"\\t"),
+            c if c.is_control() => write!(line, "\\u{{{:04x}}}", c as u32)?,
+            c => write!(line, "{c}")?,
+        }
+    }
+
+    line.push(b'"');
+
+    Ok(())
+}
+
+/// Converts the log record key to a GELF additional field name.
+///
+/// Additional field names must be prefixed with `_`, can only contain word characters, dots and
+/// dashes, and `_id` is reserved.
+fn gelf_field_name(key: &str) -> String {
+    let name: String = std::iter::once('_')
+        .chain(key.chars().map(|c| match c {
+            c if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') => c,
+            _ => '_',
+        }))
+        .collect();
+
+    if name == "_id" { "__id".into() } else { name }
+}
+
+/// Writes a flat JSON object with escaped keys and values.
+struct JsonObjectWriter<'l> {
+    line: &'l mut Vec<u8>,
+    first: bool,
+}
+
+impl<'l> JsonObjectWriter<'l> {
+    fn new(line: &'l mut Vec<u8>) -> io::Result<Self> {
+        line.push(b'{');
+
+        Ok(Self { line, first: true })
+    }
+
+    fn key(&mut self, key: &str) -> io::Result<()> {
+        if !self.first {
+            self.line.push(b',');
+        }
+
+        self.first = false;
+        serde_json::to_writer(&mut *self.line, key)?;
+        self.line.push(b':');
+
+        Ok(())
+    }
+
+    fn str(&mut self, key: &str, value: &str) -> io::Result<()> {
+        self.key(key)?;
+
+        Ok(serde_json::to_writer(&mut *self.line, value)?)
+    }
+
+    /// Writes the value that is already valid JSON, e.g. a number.
+    fn raw(&mut self, key: &str, value: &str) -> io::Result<()> {
+        self.key(key)?;
+
+        self.line.write_all(value.as_bytes())
+    }
+
+    fn field(&mut self, key: &str, value: &FieldValue) -> io::Result<()> {
+        self.key(key)?;
+
+        let res = match value {
+            FieldValue::Str(val) => serde_json::to_writer(&mut *self.line, val),
+            FieldValue::Int(val) => serde_json::to_writer(&mut *self.line, val),
+            FieldValue::Uint(val) => serde_json::to_writer(&mut *self.line, val),
+            // NOTE: non-finite numbers are written as `null`.
+            FieldValue::Float(val) => serde_json::to_writer(&mut *self.line, val),
+            FieldValue::Bool(val) => serde_json::to_writer(&mut *self.line, val),
+        };
+
+        Ok(res?)
+    }
+
+    fn end(self) -> io::Result<()> {
+        self.line.push(b'}');
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{Logger, o};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_line(format: LogFormat, timestamp: TimestampFormat) -> String {
+        let buf = SharedBuf::default();
+        let drain = LineDrain::new(buf.clone(), LineFormat::new(format).unwrap(), timestamp);
+        let log = Logger::root(Mutex::new(drain).fuse(), o!("version" => "1.0.0"));
+
+        slog::warn!(log, "Hello \"world\"\n"; "count" => 42, "ratio" => 0.5, "ok" => true, "key with=space" => "a b", "id" => "x");
+
+        String::from_utf8(buf.0.lock().unwrap().clone()).unwrap()
+    }
+
+    fn utc_millis() -> TimestampFormat {
+        TimestampFormat {
+            precision: Some(LogTimestampPrecision::Millis),
+            timezone: LogTimezone::Utc,
+        }
+    }
+
+    fn time() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 123_456_789).unwrap()
+    }
+
+    #[test]
+    fn timestamp_formats() {
+        let mut format = utc_millis();
+
+        assert_eq!(format.rfc3339(time()), "2023-11-14T22:13:20.123Z");
+        assert_eq!(format.text(time()), "Nov 14 22:13:20.123");
+        assert_eq!(format.unix(time()), "1700000000.123");
+
+        format.precision = Some(LogTimestampPrecision::Seconds);
+
+        assert_eq!(format.rfc3339(time()), "2023-11-14T22:13:20Z");
+        assert_eq!(format.text(time()), "Nov 14 22:13:20");
+        assert_eq!(format.unix(time()), "1700000000");
+
+        format.precision = Some(LogTimestampPrecision::Nanos);
+
+        assert_eq!(format.rfc3339(time()), "2023-11-14T22:13:20.123456789Z");
+        assert_eq!(format.unix(time()), "1700000000.123456789");
+    }
+
+    #[test]
+    fn logfmt() {
+        let line = log_line(LogFormat::Logfmt, utc_millis());
+        let (ts, rest) = line.strip_prefix("ts=").unwrap().split_once(' ').unwrap();
+
+        assert!(ts.parse::<DateTime<Utc>>().is_ok());
+        assert_eq!(
+            rest,
+            "level=warn msg=\"Hello \\\"world\\\"\\n\" count=42 ratio=0.5 ok=true \
+             key_with_space=\"a b\" id=x version=1.0.0\n"
+        );
+    }
+
+    #[test]
+    fn ecs() {
+        let line = log_line(LogFormat::Ecs, utc_millis());
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+        assert!(json["@timestamp"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(json["log.level"], "warn");
+        assert_eq!(json["message"], "Hello \"world\"\n");
+        assert_eq!(json["log.logger"], module_path!());
+        assert_eq!(json["log.origin.file.name"], file!());
+        assert!(json["log.origin.file.line"].is_u64());
+        assert_eq!(json["count"], 42);
+        assert_eq!(json["ratio"], 0.5);
+        assert_eq!(json["ok"], true);
+        assert_eq!(json["key with=space"], "a b");
+        assert_eq!(json["version"], "1.0.0");
+    }
+
+    #[test]
+    fn gelf() {
+        let line = log_line(LogFormat::Gelf, utc_millis());
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(json["version"], "1.1");
+        assert_eq!(json["host"], hostname());
+        assert_eq!(json["short_message"], "Hello \"world\"\n");
+        assert!(json["timestamp"].is_f64());
+        assert_eq!(json["level"], 4);
+        assert_eq!(json["_count"], 42);
+        assert_eq!(json["_ratio"], 0.5);
+        assert_eq!(json["_ok"], true);
+        assert_eq!(json["_key_with_space"], "a b");
+        assert_eq!(json["__id"], "x");
+        assert_eq!(json["_version"], "1.0.0");
+    }
+}
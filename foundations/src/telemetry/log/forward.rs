@@ -0,0 +1,661 @@
+use super::fields::{StringFields, collect_fields};
+use crate::ServiceInfo;
+use crate::telemetry::settings::{ForwardAddr, ForwardOutputSettings};
+use chrono::{DateTime, Utc};
+use slog::{Drain, OwnedKVList, Record};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::TcpStream;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+#[cfg(feature = "metrics")]
+use crate::telemetry::metrics::Counter;
+
+// NOTE: the records are sent once the log channel is emptied, or once there are this many of
+// them.
+const MAX_CHUNK_RECORDS: usize = 1000;
+const MAX_CHUNK_SIZE: usize = 1 << 20;
+
+#[cfg(feature = "metrics")]
+#[crate::telemetry::metrics::metrics(crate_path = "crate")]
+mod foundations {
+    /// The number of log records dropped because the spill buffer of the forward output was
+    /// full.
+    pub fn log_forward_records_dropped() -> Counter;
+}
+
+/// A drain that sends log records to a log shipping agent using the [Fluent Forward] protocol.
+///
+/// The records are batched in the forward mode messages, which are sent when the drain is
+/// flushed or the chunk is full. Each message is acknowledged by the agent as a whole if
+/// acknowledgements are enabled.
+///
+/// [Fluent Forward]: https://github.com/fluent/fluentd/wiki/Forward-Protocol-Specification-v1
+pub(crate) struct FluentForwardDrain {
+    tag: String,
+    ack: bool,
+    chunk_prefix: String,
+    state: Mutex<FluentForwardState>,
+}
+
+struct FluentForwardState {
+    connection: ForwardConnection,
+    next_chunk_id: u64,
+    // NOTE: the encoded `[time, record]` entries of the next message.
+    entries: Vec<u8>,
+    entry_count: usize,
+}
+
+impl FluentForwardDrain {
+    pub(crate) fn new(service_info: &ServiceInfo, settings: &ForwardOutputSettings) -> Self {
+        // NOTE: chunk IDs only need to be unique per client, so the process start time is
+        // used to distinguish them between restarts.
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        Self {
+            tag: settings
+                .tag
+                .clone()
+                .unwrap_or_else(|| service_info.name.into()),
+            ack: settings.ack,
+            chunk_prefix: format!("{:x}-{:x}", started_at, std::process::id()),
+            state: Mutex::new(FluentForwardState {
+                connection: ForwardConnection::new(settings),
+                next_chunk_id: 0,
+                entries: Vec::with_capacity(4096),
+                entry_count: 0,
+            }),
+        }
+    }
+
+    /// Encodes the `[time, record]` entry of the forward mode message.
+    fn encode_entry(
+        buf: &mut Vec<u8>,
+        record: &Record,
+        values: &OwnedKVList,
+        now: DateTime<Utc>,
+    ) -> Result<(), slog::Error> {
+        let fields = collect_fields::<StringFields>(record, values)?;
+
+        msgpack::write_array_len(buf, 2);
+        msgpack::write_event_time(buf, now);
+        msgpack::write_map_len(buf, fields.len() + 2);
+        msgpack::write_str(buf, "level");
+        msgpack::write_str(buf, record.level().as_str());
+        msgpack::write_str(buf, "msg");
+        msgpack::write_str(buf, &record.msg().to_string());
+
+        for (key, value) in &fields {
+            msgpack::write_str(buf, key);
+            msgpack::write_str(buf, value);
+        }
+
+        Ok(())
+    }
+
+    /// Sends the batched entries as a single forward mode message.
+    fn send_entries(&self, state: &mut FluentForwardState) {
+        if state.entry_count == 0 {
+            return;
+        }
+
+        let chunk = self.ack.then(|| {
+            state.next_chunk_id += 1;
+
+            format!("{}-{:x}", self.chunk_prefix, state.next_chunk_id)
+        });
+
+        let mut frame = Vec::with_capacity(state.entries.len() + self.tag.len() + 64);
+
+        // NOTE: forward mode, i.e. `[tag, [entry, ...]]` or `[tag, [entry, ...], option]`.
+        msgpack::write_array_len(&mut frame, if chunk.is_some() { 3 } else { 2 });
+        msgpack::write_str(&mut frame, &self.tag);
+        msgpack::write_array_len(&mut frame, state.entry_count);
+        frame.extend_from_slice(&state.entries);
+
+        if let Some(chunk) = &chunk {
+            msgpack::write_map_len(&mut frame, 1);
+            msgpack::write_str(&mut frame, "chunk");
+            msgpack::write_str(&mut frame, chunk);
+        }
+
+        let records = mem::take(&mut state.entry_count);
+
+        state.entries.clear();
+        state.connection.send(Message {
+            frame,
+            chunk,
+            records,
+        });
+    }
+}
+
+impl Drain for FluentForwardDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let len = state.entries.len();
+
+        if let Err(err) = Self::encode_entry(&mut state.entries, record, values, Utc::now()) {
+            state.entries.truncate(len);
+
+            return Err(err.into());
+        }
+
+        state.entry_count += 1;
+
+        if state.entry_count >= MAX_CHUNK_RECORDS || state.entries.len() >= MAX_CHUNK_SIZE {
+            self.send_entries(&mut state);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), slog::FlushError> {
+        self.send_entries(&mut self.state.lock().unwrap_or_else(PoisonError::into_inner));
+
+        Ok(())
+    }
+}
+
+impl Drop for FluentForwardDrain {
+    fn drop(&mut self) {
+        // NOTE: the connection sends the remaining messages when it's dropped.
+        self.send_entries(&mut self.state.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+}
+
+/// A writer that sends each written line to a log shipping agent.
+pub(crate) struct ForwardWriter {
+    connection: ForwardConnection,
+    line: Vec<u8>,
+}
+
+impl ForwardWriter {
+    pub(crate) fn new(settings: &ForwardOutputSettings) -> Self {
+        Self {
+            connection: ForwardConnection::new(settings),
+            line: Vec::with_capacity(256),
+        }
+    }
+}
+
+impl Write for ForwardWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(buf);
+
+        while let Some(end) = self.line.iter().position(|&b| b == b'\n') {
+            let frame = self.line.drain(..=end).collect();
+
+            self.connection.send(Message {
+                frame,
+                chunk: None,
+                records: 1,
+            });
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Message {
+    frame: Vec<u8>,
+    // NOTE: the chunk ID the agent is expected to acknowledge, if any.
+    chunk: Option<String>,
+    records: usize,
+}
+
+/// A connection to a log shipping agent that keeps the messages in a bounded spill buffer while
+/// the agent is unavailable.
+struct ForwardConnection {
+    addr: ForwardAddr,
+    stream: Option<Stream>,
+    spill: VecDeque<Message>,
+    spilled_records: usize,
+    spill_capacity: usize,
+    timeout: Duration,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    backoff: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl ForwardConnection {
+    fn new(settings: &ForwardOutputSettings) -> Self {
+        let min_backoff = Duration::from_millis(settings.reconnect_min_backoff_ms);
+
+        Self {
+            addr: settings.addr.clone(),
+            stream: None,
+            spill: VecDeque::new(),
+            spilled_records: 0,
+            spill_capacity: settings.spill_buffer_size,
+            timeout: Duration::from_millis(settings.timeout_ms.max(1)),
+            min_backoff,
+            max_backoff: Duration::from_millis(settings.reconnect_max_backoff_ms).max(min_backoff),
+            backoff: min_backoff,
+            next_attempt: None,
+        }
+    }
+
+    /// Sends the message after the previously spilled ones. The message is spilled if it can't
+    /// be sent.
+    fn send(&mut self, msg: Message) {
+        self.spilled_records += msg.records;
+        self.spill.push_back(msg);
+        self.send_spilled();
+
+        while self.spilled_records > self.spill_capacity {
+            let Some(msg) = self.spill.pop_front() else {
+                break;
+            };
+
+            self.spilled_records -= msg.records;
+
+            #[cfg(feature = "metrics")]
+            foundations::log_forward_records_dropped().inc_by(msg.records as _);
+        }
+    }
+
+    fn send_spilled(&mut self) {
+        if self.spill.is_empty() || (self.stream.is_none() && !self.reconnect()) {
+            return;
+        }
+
+        while let Some(msg) = self.spill.front() {
+            let Some(stream) = &mut self.stream else {
+                return;
+            };
+
+            // NOTE: drop the broken connection, so we try to reconnect on the next message.
+            // Unacknowledged messages stay in the buffer to be resent.
+            if send_message(stream, msg).is_err() {
+                self.stream = None;
+                return;
+            }
+
+            self.spilled_records -= msg.records;
+            self.spill.pop_front();
+        }
+    }
+
+    /// Reconnects to the agent unless the backoff delay since the last failed attempt hasn't
+    /// elapsed yet.
+    fn reconnect(&mut self) -> bool {
+        if self
+            .next_attempt
+            .is_some_and(|next_attempt| Instant::now() < next_attempt)
+        {
+            return false;
+        }
+
+        match Stream::connect(&self.addr, self.timeout) {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.backoff = self.min_backoff;
+                self.next_attempt = None;
+
+                true
+            }
+            Err(_) => {
+                self.next_attempt = Some(Instant::now() + self.backoff);
+                self.backoff = (self.backoff * 2).min(self.max_backoff);
+
+                false
+            }
+        }
+    }
+}
+
+impl Drop for ForwardConnection {
+    fn drop(&mut self) {
+        self.send_spilled();
+    }
+}
+
+fn send_message(stream: &mut Stream, msg: &Message) -> io::Result<()> {
+    stream.write_all(&msg.frame)?;
+
+    if let Some(chunk) = &msg.chunk
+        && msgpack::read_ack(stream)? != *chunk
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected chunk ID in the acknowledgement",
+        ));
+    }
+
+    Ok(())
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(addr: &ForwardAddr, timeout: Duration) -> io::Result<Self> {
+        match addr {
+            ForwardAddr::Tcp(addr) => {
+                let stream = TcpStream::connect_timeout(addr, timeout)?;
+
+                stream.set_write_timeout(Some(timeout))?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_nodelay(true)?;
+
+                Ok(Self::Tcp(stream))
+            }
+            #[cfg(unix)]
+            ForwardAddr::Unix(path) => {
+                let stream = UnixStream::connect(path)?;
+
+                stream.set_write_timeout(Some(timeout))?;
+                stream.set_read_timeout(Some(timeout))?;
+
+                Ok(Self::Unix(stream))
+            }
+            #[cfg(not(unix))]
+            ForwardAddr::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix socket forward addresses are not supported on this platform",
+            )),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// The subset of [MessagePack] used by the Fluent Forward protocol.
+///
+/// [MessagePack]: https://github.com/msgpack/msgpack/blob/master/spec.md
+mod msgpack {
+    use chrono::{DateTime, Utc};
+    use std::io::{self, Read};
+
+    pub(super) fn write_array_len(buf: &mut Vec<u8>, len: usize) {
+        write_len(buf, len, 0x90, 0xdc);
+    }
+
+    pub(super) fn write_map_len(buf: &mut Vec<u8>, len: usize) {
+        write_len(buf, len, 0x80, 0xde);
+    }
+
+    fn write_len(buf: &mut Vec<u8>, len: usize, fix_marker: u8, marker16: u8) {
+        if len < 16 {
+            buf.push(fix_marker | len as u8);
+        } else if let Ok(len) = u16::try_from(len) {
+            buf.push(marker16);
+            buf.extend_from_slice(&len.to_be_bytes());
+        } else {
+            // NOTE: the 32-bit marker always follows the 16-bit one.
+            buf.push(marker16 + 1);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+
+    pub(super) fn write_str(buf: &mut Vec<u8>, s: &str) {
+        let len = s.len();
+
+        if len < 32 {
+            buf.push(0xa0 | len as u8);
+        } else if let Ok(len) = u8::try_from(len) {
+            buf.push(0xd9);
+            buf.push(len);
+        } else if let Ok(len) = u16::try_from(len) {
+            buf.push(0xda);
+            buf.extend_from_slice(&len.to_be_bytes());
+        } else {
+            buf.push(0xdb);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    /// Writes the time as the `EventTime` extension type with the nanosecond precision.
+    pub(super) fn write_event_time(buf: &mut Vec<u8>, time: DateTime<Utc>) {
+        buf.extend_from_slice(&[0xd7, 0x00]);
+        buf.extend_from_slice(&(time.timestamp() as u32).to_be_bytes());
+        buf.extend_from_slice(&time.timestamp_subsec_nanos().to_be_bytes());
+    }
+
+    /// Reads the `{"ack": <chunk ID>}` response and returns the chunk ID.
+    pub(super) fn read_ack(reader: &mut impl Read) -> io::Result<String> {
+        let len = match read_u8(reader)? {
+            marker @ 0x80..=0x8f => usize::from(marker & 0x0f),
+            0xde => usize::from(u16::from_be_bytes(read_array(reader)?)),
+            _ => return Err(invalid_data("expected a map")),
+        };
+
+        let mut ack = None;
+
+        for _ in 0..len {
+            let key = read_str(reader)?;
+            let value = read_str(reader)?;
+
+            if key == "ack" {
+                ack = Some(value);
+            }
+        }
+
+        ack.ok_or_else(|| invalid_data("missing the `ack` entry"))
+    }
+
+    fn read_str(reader: &mut impl Read) -> io::Result<String> {
+        let len = match read_u8(reader)? {
+            marker @ 0xa0..=0xbf => usize::from(marker & 0x1f),
+            0xc4 | 0xd9 => usize::from(read_u8(reader)?),
+            0xc5 | 0xda => usize::from(u16::from_be_bytes(read_array(reader)?)),
+            0xc6 | 0xdb => u32::from_be_bytes(read_array(reader)?) as usize,
+            _ => return Err(invalid_data("expected a string")),
+        };
+
+        let mut buf = vec![0; len];
+
+        reader.read_exact(&mut buf)?;
+
+        String::from_utf8(buf).map_err(|_| invalid_data("invalid UTF-8 string"))
+    }
+
+    fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+        read_array::<1>(reader).map(|[b]| b)
+    }
+
+    fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+        let mut buf = [0; N];
+
+        reader.read_exact(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    fn invalid_data(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{Logger, o};
+    use std::net::{Ipv4Addr, TcpListener};
+
+    #[test]
+    fn fluent_forward_with_ack() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+        let settings = ForwardOutputSettings {
+            addr: ForwardAddr::Tcp(listener.local_addr().unwrap()),
+            tag: Some("app.log".into()),
+            ack: true,
+            ..Default::default()
+        };
+
+        let server = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let len = conn.read(&mut buf).unwrap();
+            let msg = &buf[..len];
+
+            // NOTE: the chunk ID is the last entry of the message.
+            let chunk_at = msg.windows(6).position(|w| w == b"\xa5chunk").unwrap() + 6;
+            let chunk = &msg[chunk_at..];
+            let mut ack = vec![0x81, 0xa3];
+
+            ack.extend_from_slice(b"ack");
+            ack.extend_from_slice(chunk);
+            conn.write_all(&ack).unwrap();
+
+            msg.to_vec()
+        });
+
+        let drain = FluentForwardDrain::new(&crate::service_info!(), &settings);
+        let log = Logger::root(drain.fuse(), o!("version" => "1.0.0"));
+
+        slog::warn!(log, "first"; "key" => "value");
+        slog::info!(log, "second");
+
+        // NOTE: the batched records are sent as a single message once the drain is flushed.
+        log.flush().unwrap();
+
+        let received = server.join().unwrap();
+        let mut header = vec![0x93];
+
+        msgpack::write_str(&mut header, "app.log");
+        header.extend_from_slice(&[0x92, 0x92, 0xd7, 0x00]);
+
+        let mut record = vec![0x84];
+
+        for s in [
+            "level", "WARNING", "msg", "first", "key", "value", "version", "1.0.0",
+        ] {
+            msgpack::write_str(&mut record, s);
+        }
+
+        // NOTE: the record follows the 8 bytes of the event time.
+        assert!(received.starts_with(&header));
+        assert!(received[header.len() + 8..].starts_with(&record));
+        assert!(received.windows(6).any(|w| w == b"second"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ndjson_spill_and_reconnect() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::UnixListener;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("forward.sock");
+
+        let settings = ForwardOutputSettings {
+            addr: ForwardAddr::Unix(path.clone()),
+            spill_buffer_size: 2,
+            reconnect_min_backoff_ms: 0,
+            ..Default::default()
+        };
+
+        let mut writer = ForwardWriter::new(&settings);
+
+        // NOTE: the agent is not listening yet, so the oldest records are dropped.
+        writer.write_all(b"{\"msg\":\"1\"}\n{\"msg\"").unwrap();
+        writer.write_all(b":\"2\"}\n{\"msg\":\"3\"}\n").unwrap();
+
+        let listener = UnixListener::bind(&path).unwrap();
+
+        writer.write_all(b"{\"msg\":\"4\"}\n").unwrap();
+        drop(writer);
+
+        let (conn, _) = listener.accept().unwrap();
+        let lines: Vec<_> = BufReader::new(conn).lines().map(Result::unwrap).collect();
+
+        assert_eq!(
+            lines,
+            ["{\"msg\":\"2\"}", "{\"msg\":\"3\"}", "{\"msg\":\"4\"}"]
+        );
+    }
+
+    #[test]
+    fn read_ack() {
+        let mut response = vec![];
+
+        msgpack::write_map_len(&mut response, 1);
+        msgpack::write_str(&mut response, "ack");
+        msgpack::write_str(&mut response, &"a".repeat(40));
+
+        assert_eq!(
+            msgpack::read_ack(&mut response.as_slice()).unwrap(),
+            "a".repeat(40)
+        );
+
+        assert!(msgpack::read_ack(&mut &[0x90][..]).is_err());
+    }
+
+    #[test]
+    fn reconnect_backoff() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        drop(listener);
+
+        let settings = ForwardOutputSettings {
+            addr: ForwardAddr::Tcp(addr),
+            reconnect_min_backoff_ms: 100,
+            reconnect_max_backoff_ms: 300,
+            ..Default::default()
+        };
+
+        let mut connection = ForwardConnection::new(&settings);
+
+        for expected_backoff in [200, 300, 300] {
+            connection.next_attempt = None;
+
+            assert!(!connection.reconnect());
+            assert_eq!(connection.backoff, Duration::from_millis(expected_backoff));
+        }
+
+        // NOTE: no attempts are made until the backoff delay elapses.
+        assert!(!connection.reconnect());
+        assert!(connection.next_attempt.unwrap() > Instant::now());
+    }
+}
@@ -0,0 +1,17 @@
+//! Glob patterns of the log record keys and messages.
+
+/// Converts the glob pattern with the `*` and `?` wildcards to an anchored regular expression.
+pub(super) fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
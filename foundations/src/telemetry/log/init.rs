@@ -1,31 +1,55 @@
+use super::channel::{ChannelDrain, ChannelGuard};
+use super::collapse::CollapsingDrain;
 use super::field_dedup::FieldDedupFilterFactory;
 use super::field_filtering::{FieldFilteringDrain, FilterFactory};
 use super::field_redact::FieldRedactFilterFactory;
+#[cfg(unix)]
+use super::file_reopen::install_reopen_signal_handler;
+use super::flight_recorder::FlightRecorder;
+use super::format::{LineDrain, LineFormat, TimestampFormat};
+use super::forward::{FluentForwardDrain, ForwardWriter};
 use super::internal::{LoggerWithKvNestingTracking, SharedLog};
+use super::tee::TeeDrain;
+use super::verbosity::{VerbosityDirectives, VerbosityFilterDrain};
 
 #[cfg(feature = "metrics")]
 use crate::telemetry::log::log_volume::LogVolumeMetricsDrain;
 
+#[cfg(target_os = "linux")]
+use crate::telemetry::log::journald::JournaldDrain;
+#[cfg(feature = "telemetry-otlp-grpc")]
+use crate::telemetry::log::output_otlp_grpc;
 use crate::telemetry::log::rate_limit::RateLimitingDrain;
+#[cfg(feature = "log-redaction")]
+use crate::telemetry::log::redaction::RedactionDrain;
 use crate::telemetry::log::retry_writer::RetryPipeWriter;
+use crate::telemetry::log::rotating_writer::RotatingFileWriter;
+#[cfg(feature = "log-sampling")]
+use crate::telemetry::log::sampling::SamplingDrain;
+use crate::telemetry::log::syslog::SyslogDrain;
+#[cfg(feature = "tracing")]
+use crate::telemetry::log::trace_correlation::TraceCorrelationDrain;
 use crate::telemetry::scope::ScopeStack;
-use crate::telemetry::settings::{LogFormat, LogOutput, LogVerbosity, LoggingSettings};
-use crate::{BootstrapResult, ServiceInfo};
+use crate::telemetry::settings::{ForwardProtocol, LogFormat, LogOutput, LoggingSettings};
+use crate::{BootstrapError, BootstrapResult, ServiceInfo};
+use chrono::Utc;
 use crossbeam_utils::CachePadded;
+use futures_util::future::BoxFuture;
 use slog::{
-    Discard, Drain, FnValue, Logger, OwnedKV, SendSyncRefUnwindSafeDrain, SendSyncRefUnwindSafeKV,
+    Discard, Drain, FnValue, Logger, OwnedKV, PushFnValue, SendSyncRefUnwindSafeDrain,
+    SendSyncRefUnwindSafeKV,
 };
-use slog_async::{Async as AsyncDrain, AsyncGuard};
 use slog_json::{Json as JsonDrain, Json};
-use slog_term::{FullFormat as TextDrain, PlainDecorator, TermDecorator};
+use slog_term::{FullFormat as TextDrain, PlainDecorator, TermDecorator, ThreadSafeTimestampFn};
 use std::fmt::Debug;
 use std::fs::File;
 use std::io;
 use std::io::BufWriter;
+use std::path::Path;
 use std::sync::{Arc, LazyLock, OnceLock};
 
 type BoxedDebug = Box<dyn Debug>;
-type SharedDrain = Arc<dyn SendSyncRefUnwindSafeDrain<Ok = (), Err = BoxedDebug>>;
+pub(crate) type SharedDrain = Arc<dyn SendSyncRefUnwindSafeDrain<Ok = (), Err = BoxedDebug>>;
 
 // These singletons are accessed _very often_, and each access requires an atomic load to
 // ensure initialization. Make sure nobody else invalidates our cache lines.
@@ -40,6 +64,7 @@ static NOOP_HARNESS: CachePadded<LazyLock<LogHarness>> = CachePadded::new(LazyLo
         root_log: Arc::new(parking_lot::RwLock::new(noop_log)),
         settings: Default::default(),
         log_scope_stack: Default::default(),
+        flight_recorder: None,
     }
 }));
 
@@ -48,6 +73,7 @@ pub(crate) struct LogHarness {
     pub(crate) root_drain: SharedDrain,
     pub(crate) settings: LoggingSettings,
     pub(crate) log_scope_stack: ScopeStack<SharedLog>,
+    pub(crate) flight_recorder: Option<Arc<FlightRecorder>>,
 }
 
 impl LogHarness {
@@ -65,20 +91,94 @@ impl LogHarness {
 // even if the buffer isn't full.
 const BUF_SIZE: usize = 4096;
 
+/// Guard of the log channel and the initialization future that needs to be driven by the
+/// telemetry driver for the outputs that require it.
+pub(crate) type LogOutputHandles = (
+    Option<ChannelGuard>,
+    Option<BoxFuture<'static, BootstrapResult<()>>>,
+);
+
 // NOTE: Does nothing if logging has already been initialized in this process.
 pub(crate) fn init(
     service_info: &ServiceInfo,
     settings: &LoggingSettings,
-) -> BootstrapResult<Option<AsyncGuard>> {
+) -> BootstrapResult<LogOutputHandles> {
     // Already initialized
     if HARNESS.get().is_some() {
-        return Ok(None);
+        return Ok((None, None));
+    }
+
+    let verbosity = VerbosityDirectives::from_settings(settings)
+        .map_err(|err| anyhow::anyhow!(err).context("invalid log verbosity directives"))?;
+
+    #[cfg(feature = "telemetry-otlp-grpc")]
+    if let LogOutput::OpenTelemetryGrpc(otlp_settings) = &settings.output {
+        // NOTE: the drain is not async, as the records need to be associated with the
+        // current span on the emitting thread. It exports the records in the background.
+        let (drain, init_fut) =
+            output_otlp_grpc::start(service_info, otlp_settings, settings.channel.size)?;
+
+        let (drain, guard) = add_additional_outputs(service_info, settings, drain)?;
+
+        set_harness(service_info, settings, verbosity, drain)?;
+
+        return Ok((guard, Some(init_fut)));
     }
 
-    // NOTE: OXY-178, default is 128 (https://docs.rs/slog-async/2.7.0/src/slog_async/lib.rs.html#251)
-    const CHANNEL_SIZE: usize = 1024;
+    let (drain, mut guard) = build_output(service_info, settings, 0)?;
+    let (drain, additional_guard) = add_additional_outputs(service_info, settings, drain)?;
 
-    let (async_drain, async_guard) = match (&settings.output, &settings.format) {
+    if let Some(additional_guard) = additional_guard {
+        guard.chain(additional_guard);
+    }
+
+    set_harness(service_info, settings, verbosity, drain)?;
+
+    Ok((Some(guard), None))
+}
+
+/// Adds the [`LoggingSettings::additional_outputs`] to the main output drain.
+fn add_additional_outputs<D>(
+    service_info: &ServiceInfo,
+    settings: &LoggingSettings,
+    drain: D,
+) -> BootstrapResult<(TeeDrain<D>, Option<ChannelGuard>)> {
+    let mut drain = TeeDrain::new(drain);
+    let mut guard: Option<ChannelGuard> = None;
+
+    for (idx, output_settings) in settings.additional_outputs.iter().enumerate() {
+        let settings = LoggingSettings {
+            output: output_settings.output.clone(),
+            format: output_settings.format,
+            ..settings.clone()
+        };
+
+        let (output_drain, output_guard) = build_output(service_info, &settings, idx + 1)?;
+
+        drain
+            .add_output(output_drain, output_settings)
+            .map_err(|err| anyhow::anyhow!(err).context("invalid log redaction settings"))?;
+
+        match &mut guard {
+            Some(guard) => guard.chain(output_guard),
+            None => guard = Some(output_guard),
+        }
+    }
+
+    Ok((drain, guard))
+}
+
+/// Builds the async drain writing the log records to [`LoggingSettings::output`].
+///
+/// The output index identifies the output in the metrics of its channel.
+fn build_output(
+    service_info: &ServiceInfo,
+    settings: &LoggingSettings,
+    output_idx: usize,
+) -> BootstrapResult<(ChannelDrain, ChannelGuard)> {
+    let timestamp = TimestampFormat::new(settings);
+
+    Ok(match (&settings.output, &settings.format) {
         (output @ (LogOutput::Terminal | LogOutput::Stderr), LogFormat::Text) => {
             let decorator = if matches!(output, LogOutput::Terminal) {
                 TermDecorator::new().stdout().build()
@@ -86,39 +186,78 @@ pub(crate) fn init(
                 TermDecorator::new().stderr().build()
             };
 
-            let drain = TextDrain::new(decorator).build();
-            build_async_drain(drain, settings, CHANNEL_SIZE)
+            let drain = TextDrain::new(decorator)
+                .use_custom_timestamp(text_timestamp(timestamp))
+                .build();
+            build_async_drain(drain, settings, output_idx)
         }
-        (output @ (LogOutput::Terminal | LogOutput::Stderr), LogFormat::Json) => {
+        (output @ (LogOutput::Terminal | LogOutput::Stderr), _) => {
             let writer = if matches!(output, LogOutput::Terminal) {
                 stdout_writer_without_line_buffering()
             } else {
                 stderr_writer_without_line_buffering()
             };
-            let drain = build_json_log_drain(writer);
-            build_async_drain(drain, settings, CHANNEL_SIZE)
-        }
-        (LogOutput::File(file_path), LogFormat::Text) => {
-            let file = RetryPipeWriter::new(file_path.into())?;
-            let drain = TextDrain::new(PlainDecorator::new(file)).build();
-            build_async_drain(drain, settings, CHANNEL_SIZE)
+            build_async_writer_drain(writer, settings, output_idx)
         }
-        (LogOutput::File(file_path), LogFormat::Json) => {
-            let file = RetryPipeWriter::new(file_path.into())?;
+        (LogOutput::File(file_path), _) => {
+            let file = file_writer(file_path, settings)?;
             let buf = BufWriter::with_capacity(BUF_SIZE, file);
-            let drain = build_json_log_drain(buf);
-            build_async_drain(drain, settings, CHANNEL_SIZE)
+            build_async_writer_drain(buf, settings, output_idx)
+        }
+        (LogOutput::Syslog(syslog_settings), _) => {
+            let drain = SyslogDrain::new(service_info, syslog_settings)?;
+            build_async_drain(drain, settings, output_idx)
+        }
+        #[cfg(target_os = "linux")]
+        (LogOutput::Journald(journald_settings), _) => {
+            let drain = JournaldDrain::new(service_info, journald_settings)?;
+            build_async_drain(drain, settings, output_idx)
+        }
+        (LogOutput::Forward(forward_settings), _) => match forward_settings.protocol {
+            ForwardProtocol::FluentForward => {
+                let drain = FluentForwardDrain::new(service_info, forward_settings);
+                build_async_drain(drain, settings, output_idx)
+            }
+            ForwardProtocol::NdJson if matches!(settings.format, LogFormat::Text) => {
+                anyhow::bail!(
+                    "the `NdJson` forward protocol can't be used with the `Text` log format"
+                );
+            }
+            ForwardProtocol::NdJson => {
+                build_async_writer_drain(ForwardWriter::new(forward_settings), settings, output_idx)
+            }
+        },
+        #[cfg(feature = "telemetry-otlp-grpc")]
+        (LogOutput::OpenTelemetryGrpc(_), _) => {
+            anyhow::bail!(
+                "the OpenTelemetry gRPC log output can't be used as an additional output"
+            );
         }
         #[cfg(feature = "tracing-rs-compat")]
-        (LogOutput::TracingRsCompat, _) => AsyncDrain::new(tracing_slog::TracingSlogDrain {})
-            .chan_size(CHANNEL_SIZE)
-            .build_with_guard(),
-        (LogOutput::Custom(drain), _) => AsyncDrain::new(Arc::clone(drain))
-            .chan_size(CHANNEL_SIZE)
-            .build_with_guard(),
-    };
+        (LogOutput::TracingRsCompat, _) => ChannelDrain::new(
+            tracing_slog::TracingSlogDrain {},
+            &settings.channel,
+            output_idx,
+        ),
+        (LogOutput::Custom(drain), _) => {
+            ChannelDrain::new(Arc::clone(drain), &settings.channel, output_idx)
+        }
+    })
+}
+
+fn set_harness<D>(
+    service_info: &ServiceInfo,
+    settings: &LoggingSettings,
+    verbosity: VerbosityDirectives,
+    drain: D,
+) -> BootstrapResult<()>
+where
+    D: SendSyncRefUnwindSafeDrain<Ok = ()> + 'static,
+    D::Err: Debug + 'static,
+{
+    let root_drain = wrap_root_drain(settings, drain)
+        .map_err(|err| anyhow::anyhow!(err).context("invalid log redaction settings"))?;
 
-    let root_drain = wrap_root_drain(settings, async_drain);
     let root_kv = slog::o!(
         "module" => FnValue(|record| {
             format!("{}:{}", record.module(), record.line())
@@ -127,7 +266,15 @@ pub(crate) fn init(
         "pid" => std::process::id(),
     );
 
-    let root_log = build_log_with_drain(settings.verbosity, root_kv, Arc::clone(&root_drain));
+    let flight_recorder = FlightRecorder::new(&settings.flight_recorder, Arc::clone(&root_drain));
+
+    let root_log = build_log_with_drain(
+        verbosity,
+        flight_recorder.clone(),
+        root_kv,
+        Arc::clone(&root_drain),
+    );
+
     let harness = LogHarness {
         root_drain,
         root_log: Arc::new(parking_lot::RwLock::new(LoggerWithKvNestingTracking::new(
@@ -135,11 +282,38 @@ pub(crate) fn init(
         ))),
         settings: settings.clone(),
         log_scope_stack: Default::default(),
+        flight_recorder,
     };
 
     let _ = HARNESS.set(harness);
 
-    Ok(Some(async_guard))
+    #[cfg(any(feature = "log-bridge", feature = "tracing-bridge"))]
+    super::bridge::install(settings)
+        .map_err(|err| anyhow::anyhow!(err).context("failed to install the log bridges"))?;
+
+    Ok(())
+}
+
+/// Opens the log file with rotation if it's enabled in the settings.
+fn file_writer(
+    file_path: &Path,
+    settings: &LoggingSettings,
+) -> BootstrapResult<Box<dyn io::Write + Send>> {
+    #[cfg(unix)]
+    if let Some(signal) = settings.reopen_signal {
+        install_reopen_signal_handler(signal).map_err(|e| {
+            BootstrapError::new(e).context(format!("failed to install {signal:?} handler"))
+        })?;
+    }
+
+    if settings.rotation.is_enabled() {
+        Ok(Box::new(RotatingFileWriter::new(
+            file_path.into(),
+            &settings.rotation,
+        )?))
+    } else {
+        Ok(Box::new(RetryPipeWriter::new(file_path.into())?))
+    }
 }
 
 /// Opens fd 1 directly and wraps with a [`BufWriter`] with [`BUF_SIZE`] capacity.
@@ -186,7 +360,7 @@ fn stderr_writer_without_line_buffering() -> BufWriter<File> {
     BufWriter::with_capacity(BUF_SIZE, stderr)
 }
 
-pub(crate) fn wrap_root_drain<D>(settings: &LoggingSettings, drain: D) -> SharedDrain
+pub(crate) fn wrap_root_drain<D>(settings: &LoggingSettings, drain: D) -> crate::Result<SharedDrain>
 where
     D: SendSyncRefUnwindSafeDrain<Ok = ()> + 'static,
     D::Err: Debug + 'static,
@@ -195,55 +369,120 @@ where
         .field_filter(FieldDedupFilterFactory)
         .field_filter(FieldRedactFilterFactory::new(settings.redact_keys.clone()));
 
+    #[cfg(feature = "log-redaction")]
+    let drain = drain.redact(settings)?;
+
+    #[cfg(feature = "tracing")]
+    let drain = drain.trace_correlation(settings);
+
     #[cfg(feature = "metrics")]
     if settings.log_volume_metrics.enabled {
-        return drain.volume_metrics().rate_limit(settings).shared();
+        let drain = drain
+            .volume_metrics()
+            .rate_limit(settings)
+            .collapse_repeated(settings);
+
+        #[cfg(feature = "log-sampling")]
+        let drain = drain.sample(settings);
+
+        return Ok(drain.shared());
     }
 
-    drain.rate_limit(settings).shared()
+    let drain = drain.rate_limit(settings).collapse_repeated(settings);
+
+    #[cfg(feature = "log-sampling")]
+    let drain = drain.sample(settings);
+
+    Ok(drain.shared())
 }
 
 pub(crate) fn build_log_with_drain<K>(
-    verbosity: LogVerbosity,
+    verbosity: VerbosityDirectives,
+    flight_recorder: Option<Arc<FlightRecorder>>,
     kv: OwnedKV<K>,
     drain: SharedDrain,
 ) -> Logger
 where
     K: SendSyncRefUnwindSafeKV + 'static,
 {
-    let drain = drain.filter_level(verbosity.into()).fuse();
-    Logger::root(drain, kv)
+    let drain = VerbosityFilterDrain::new(drain, verbosity, flight_recorder);
+
+    #[cfg(any(feature = "log-bridge", feature = "tracing-bridge"))]
+    super::bridge::raise_max_level(drain.max_level());
+
+    Logger::root(drain.fuse(), kv)
 }
 
 fn build_async_drain<D>(
     drain: D,
     settings: &LoggingSettings,
-    channel_size: usize,
-) -> (AsyncDrain, AsyncGuard)
+    output: usize,
+) -> (ChannelDrain, ChannelGuard)
 where
     D: Drain<Ok = ()> + Send + 'static,
     D::Err: Debug,
 {
     if settings.ignore_io_errors {
-        AsyncDrain::new(drain.ignore_res())
-            .chan_size(channel_size)
-            .build_with_guard()
+        ChannelDrain::new(drain.ignore_res(), &settings.channel, output)
     } else {
-        AsyncDrain::new(drain.fuse())
-            .chan_size(channel_size)
-            .build_with_guard()
+        ChannelDrain::new(drain.fuse(), &settings.channel, output)
+    }
+}
+
+/// Builds the async drain writing the log records in the format specified in the settings.
+fn build_async_writer_drain<W>(
+    writer: W,
+    settings: &LoggingSettings,
+    output: usize,
+) -> (ChannelDrain, ChannelGuard)
+where
+    W: io::Write + Send + 'static,
+{
+    let timestamp = TimestampFormat::new(settings);
+
+    match (settings.format, LineFormat::new(settings.format)) {
+        (_, Some(line_format)) => {
+            let drain = LineDrain::new(writer, line_format, timestamp);
+            build_async_drain(drain, settings, output)
+        }
+        (LogFormat::Json, None) => {
+            let drain = build_json_log_drain(writer, settings);
+            build_async_drain(drain, settings, output)
+        }
+        (_, None) => {
+            let drain = TextDrain::new(PlainDecorator::new(writer))
+                .use_custom_timestamp(text_timestamp(timestamp))
+                .build();
+            build_async_drain(drain, settings, output)
+        }
     }
 }
 
-fn build_json_log_drain<O>(output: O) -> Json<O>
+fn build_json_log_drain<O>(output: O, settings: &LoggingSettings) -> Json<O>
 where
     O: io::Write + Send + 'static,
 {
-    JsonDrain::new(output)
-        .add_default_keys()
-        .set_pretty(false)
-        .set_flush(true)
-        .build()
+    let builder = JsonDrain::new(output);
+
+    // NOTE: keep the default timestamps unless the timestamp format is explicitly specified.
+    let builder = if settings.timestamp.precision.is_none() && settings.timestamp.timezone.is_none()
+    {
+        builder.add_default_keys()
+    } else {
+        let timestamp = TimestampFormat::new(settings);
+
+        builder.add_key_value(slog::o!(
+            "ts" => FnValue(move |_| timestamp.rfc3339(Utc::now())),
+            "level" => FnValue(|record| record.level().as_short_str()),
+            "msg" => PushFnValue(|record, ser| ser.emit(record.msg())),
+        ))
+    };
+
+    builder.set_pretty(false).set_flush(true).build()
+}
+
+fn text_timestamp(timestamp: TimestampFormat) -> impl ThreadSafeTimestampFn {
+    move |io: &mut dyn io::Write| io.write_all(timestamp.text(Utc::now()).as_bytes())
 }
 
 /// [`Drain`] extension trait for easier layering.
@@ -253,15 +492,38 @@ trait DrainExt: Drain + Sized {
         FieldFilteringDrain::new(self, filter_factory)
     }
 
+    /// Layers a [`RedactionDrain`] on top of the current drain.
+    #[cfg(feature = "log-redaction")]
+    fn redact(self, settings: &LoggingSettings) -> crate::Result<RedactionDrain<Self>> {
+        RedactionDrain::new(self, &settings.redaction)
+    }
+
     /// Layers a [`LogVolumeMetricsDrain`] on top of the current drain.
     #[cfg(feature = "metrics")]
     fn volume_metrics(self) -> LogVolumeMetricsDrain<Self> {
         LogVolumeMetricsDrain::new(self)
     }
 
+    /// Layers a [`TraceCorrelationDrain`] on top of the current drain.
+    #[cfg(feature = "tracing")]
+    fn trace_correlation(self, settings: &LoggingSettings) -> TraceCorrelationDrain<Self> {
+        TraceCorrelationDrain::new(self, &settings.trace_correlation)
+    }
+
     /// Layers a [`RateLimitingDrain`] on top of the current drain.
     fn rate_limit(self, settings: &LoggingSettings) -> RateLimitingDrain<Self> {
-        RateLimitingDrain::new(self, &settings.rate_limit)
+        RateLimitingDrain::new(self, &settings.rate_limit, &settings.source_rate_limit)
+    }
+
+    /// Layers a [`CollapsingDrain`] on top of the current drain.
+    fn collapse_repeated(self, settings: &LoggingSettings) -> CollapsingDrain<Self> {
+        CollapsingDrain::new(self, &settings.collapse_repeated)
+    }
+
+    /// Layers a [`SamplingDrain`] on top of the current drain.
+    #[cfg(feature = "log-sampling")]
+    fn sample(self, settings: &LoggingSettings) -> SamplingDrain<Self> {
+        SamplingDrain::new(self, &settings.sampling)
     }
 
     /// Converts the current drain into a [`SharedDrain`] for sharing between
@@ -281,6 +543,7 @@ impl<D: Drain + Sized> DrainExt for D {}
 #[cfg(all(test, feature = "logging"))]
 mod tests {
     use super::*;
+    use crate::telemetry::settings::ForwardOutputSettings;
     use slog::{OwnedKVList, Record};
     use std::sync::Arc;
     use std::sync::atomic::{AtomicUsize, Ordering};
@@ -309,7 +572,7 @@ mod tests {
             ignore_io_errors: true,
             ..Default::default()
         };
-        let (drain, guard) = build_async_drain(drain, &settings, 1024);
+        let (drain, guard) = build_async_drain(drain, &settings, 0);
         let log = Logger::root(drain.fuse(), slog::o!());
 
         slog::error!(log, "first failing write");
@@ -326,7 +589,7 @@ mod tests {
             calls: Arc::clone(&calls),
         };
         let settings = LoggingSettings::default();
-        let (drain, guard) = build_async_drain(drain, &settings, 1024);
+        let (drain, guard) = build_async_drain(drain, &settings, 0);
         let log = Logger::root(drain.ignore_res(), slog::o!());
 
         slog::error!(log, "first failing write");
@@ -335,4 +598,22 @@ mod tests {
 
         assert_eq!(calls.load(Ordering::Relaxed), 1);
     }
+
+    #[test]
+    fn ndjson_forward_with_text_format_is_rejected() {
+        let settings = LoggingSettings {
+            output: LogOutput::Forward(ForwardOutputSettings {
+                protocol: ForwardProtocol::NdJson,
+                ..Default::default()
+            }),
+            format: LogFormat::Text,
+            ..Default::default()
+        };
+
+        let err = build_output(&crate::service_info!(), &settings, 0)
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("`Text` log format"));
+    }
 }
@@ -0,0 +1,341 @@
+use super::fields::{StringFields, collect_fields};
+use super::syslog::severity;
+use crate::ServiceInfo;
+use crate::telemetry::settings::JournaldOutputSettings;
+use slog::{Drain, OwnedKVList, Record};
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+// https://systemd.io/JOURNAL_NATIVE_PROTOCOL/
+const MAX_FIELD_NAME_LEN: usize = 64;
+
+/// A drain that writes log records to journald using its [native protocol].
+///
+/// [native protocol]: https://systemd.io/JOURNAL_NATIVE_PROTOCOL/
+pub(crate) struct JournaldDrain {
+    socket: UnixDatagram,
+    socket_path: PathBuf,
+    syslog_identifier: String,
+}
+
+impl JournaldDrain {
+    pub(crate) fn new(
+        service_info: &ServiceInfo,
+        settings: &JournaldOutputSettings,
+    ) -> io::Result<Self> {
+        // NOTE: the socket is not connected, so we don't need to reconnect if journald restarts.
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            socket_path: settings.socket_path.clone(),
+            syslog_identifier: settings
+                .syslog_identifier
+                .clone()
+                .unwrap_or_else(|| service_info.name.into()),
+        })
+    }
+
+    fn serialize_record(
+        &self,
+        record: &Record,
+        values: &OwnedKVList,
+    ) -> Result<Vec<u8>, slog::Error> {
+        let fields = collect_fields::<StringFields>(record, values)?;
+
+        let mut payload = vec![];
+
+        write_field(&mut payload, "MESSAGE", &record.msg().to_string());
+        write_field(
+            &mut payload,
+            "PRIORITY",
+            &severity(record.level()).to_string(),
+        );
+        write_field(&mut payload, "SYSLOG_IDENTIFIER", &self.syslog_identifier);
+        write_field(&mut payload, "CODE_FILE", record.file());
+        write_field(&mut payload, "CODE_LINE", &record.line().to_string());
+
+        if !record.function().is_empty() {
+            write_field(&mut payload, "CODE_FUNC", record.function());
+        }
+
+        for (key, value) in &fields {
+            if let Some(name) = field_name(key) {
+                write_field(&mut payload, &name, value);
+            }
+        }
+
+        Ok(payload)
+    }
+
+    /// Sends the payload in a sealed memory file, which journald uses for the records that
+    /// exceed the maximum datagram size.
+    fn send_in_memfd(&self, payload: &[u8]) -> io::Result<()> {
+        let fd = unsafe {
+            libc::memfd_create(
+                c"foundations-journald".as_ptr(),
+                libc::MFD_ALLOW_SEALING | libc::MFD_CLOEXEC,
+            )
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: the file descriptor has just been created and is not owned by anything else.
+        let mut memfd = unsafe { File::from_raw_fd(fd) };
+
+        memfd.write_all(payload)?;
+
+        let seals =
+            libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+
+        if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        send_fd(
+            self.socket.as_raw_fd(),
+            &self.socket_path,
+            memfd.as_raw_fd(),
+        )
+    }
+}
+
+impl Drain for JournaldDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let payload = self.serialize_record(record, values)?;
+
+        match self.socket.send_to(&payload, &self.socket_path) {
+            Ok(_) => Ok(()),
+            Err(err) if matches!(err.raw_os_error(), Some(libc::EMSGSIZE | libc::ENOBUFS)) => {
+                self.send_in_memfd(&payload)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Converts the log record key to a journal field name.
+///
+/// Field names can only contain uppercase ASCII letters, digits and underscores, and can't
+/// start with an underscore or a digit. Returns `None` if nothing is left after the conversion.
+fn field_name(key: &str) -> Option<String> {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .skip_while(|&c| c == '_' || c.is_ascii_digit())
+        .take(MAX_FIELD_NAME_LEN)
+        .collect();
+
+    (!name.is_empty()).then_some(name)
+}
+
+fn write_field(payload: &mut Vec<u8>, name: &str, value: &str) {
+    payload.extend_from_slice(name.as_bytes());
+
+    // NOTE: values with new lines are written in the binary form with explicit length.
+    if value.contains('\n') {
+        payload.push(b'\n');
+        payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        payload.push(b'=');
+    }
+
+    payload.extend_from_slice(value.as_bytes());
+    payload.push(b'\n');
+}
+
+/// Sends the file descriptor to the Unix datagram socket at the path.
+fn send_fd(socket: RawFd, path: &Path, fd: RawFd) -> io::Result<()> {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    let path = path.as_os_str().as_bytes();
+
+    // NOTE: the path must be nul-terminated.
+    if path.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "journald socket path is too long",
+        ));
+    }
+
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    for (dst, &src) in addr.sun_path.iter_mut().zip(path) {
+        *dst = src as libc::c_char;
+    }
+
+    let cmsg_space = unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) } as usize;
+    let mut cmsg_buf = vec![0u8; cmsg_space];
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+
+    msg.msg_name = ptr::addr_of_mut!(addr).cast();
+    msg.msg_namelen = mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+    msg.msg_controllen = cmsg_space as _;
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), fd);
+
+        if libc::sendmsg(socket, &msg, libc::MSG_NOSIGNAL) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{Logger, o};
+    use std::io::{Read, Seek};
+    use std::os::fd::OwnedFd;
+    use tempfile::TempDir;
+
+    struct FakeJournald {
+        socket: UnixDatagram,
+        settings: JournaldOutputSettings,
+        _dir: TempDir,
+    }
+
+    impl FakeJournald {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let socket_path = dir.path().join("socket");
+            let socket = UnixDatagram::bind(&socket_path).unwrap();
+
+            Self {
+                socket,
+                settings: JournaldOutputSettings {
+                    socket_path,
+                    syslog_identifier: None,
+                },
+                _dir: dir,
+            }
+        }
+
+        /// Receives the record either in the datagram or in the passed file descriptor.
+        fn recv_record(&self) -> Vec<(String, String)> {
+            let mut buf = vec![0u8; 4096];
+            let cmsg_space = unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) };
+            let mut cmsg_buf = vec![0u8; cmsg_space as usize];
+            let mut iov = libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            };
+            let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+            msg.msg_controllen = cmsg_buf.len() as _;
+
+            let len = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut msg, 0) };
+
+            assert!(len >= 0, "{}", io::Error::last_os_error());
+
+            let cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+
+            let payload = if cmsg.is_null() {
+                buf.truncate(len as usize);
+                buf
+            } else {
+                let fd = unsafe { ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>()) };
+                let mut file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+                let mut payload = vec![];
+
+                // NOTE: the file offset is shared with the sender which has written the payload.
+                file.rewind().unwrap();
+                file.read_to_end(&mut payload).unwrap();
+                payload
+            };
+
+            parse_fields(&payload)
+        }
+    }
+
+    fn parse_fields(mut payload: &[u8]) -> Vec<(String, String)> {
+        let mut fields = vec![];
+
+        while !payload.is_empty() {
+            let line_end = payload.iter().position(|&b| b == b'\n').unwrap();
+            let line = std::str::from_utf8(&payload[..line_end]).unwrap();
+
+            if let Some((name, value)) = line.split_once('=') {
+                fields.push((name.into(), value.into()));
+                payload = &payload[line_end + 1..];
+            } else {
+                let rest = &payload[line_end + 1..];
+                let len = u64::from_le_bytes(rest[..8].try_into().unwrap()) as usize;
+                let value = std::str::from_utf8(&rest[8..8 + len]).unwrap();
+
+                fields.push((line.into(), value.into()));
+                payload = &rest[8 + len + 1..];
+            }
+        }
+
+        fields
+    }
+
+    fn field<'a>(fields: &'a [(String, String)], name: &str) -> &'a str {
+        &fields.iter().find(|(n, _)| n == name).unwrap().1
+    }
+
+    #[test]
+    fn structured_fields() {
+        let journald = FakeJournald::new();
+        let drain = JournaldDrain::new(&crate::service_info!(), &journald.settings).unwrap();
+        let log = Logger::root(drain.fuse(), o!("version" => "1.0.0"));
+
+        slog::warn!(log, "Hello\njournald"; "request-id" => 42, "_trusted" => "no");
+
+        let fields = journald.recv_record();
+
+        assert_eq!(field(&fields, "MESSAGE"), "Hello\njournald");
+        assert_eq!(field(&fields, "PRIORITY"), "4");
+        assert_eq!(field(&fields, "SYSLOG_IDENTIFIER"), "foundations");
+        assert_eq!(field(&fields, "CODE_FILE"), file!());
+        assert!(field(&fields, "CODE_LINE").parse::<u32>().is_ok());
+
+        assert_eq!(
+            fields[fields.len() - 3..],
+            [
+                ("REQUEST_ID".into(), "42".into()),
+                ("TRUSTED".into(), "no".into()),
+                ("VERSION".into(), "1.0.0".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn large_record_in_memfd() {
+        let journald = FakeJournald::new();
+        let drain = JournaldDrain::new(&crate::service_info!(), &journald.settings).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+        let large = "x".repeat(1024 * 1024);
+
+        slog::info!(log, "large"; "payload" => &large);
+
+        let fields = journald.recv_record();
+
+        assert_eq!(field(&fields, "MESSAGE"), "large");
+        assert_eq!(field(&fields, "PAYLOAD"), large);
+    }
+}
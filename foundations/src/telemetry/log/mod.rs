@@ -1,10 +1,19 @@
 //! Logging-related functionality.
 
+mod collapse;
 mod field_dedup;
 mod field_filtering;
 mod field_redact;
+mod file_reopen;
+mod flight_recorder;
+mod format;
+mod forward;
 mod rate_limit;
+mod tee;
+mod verbosity;
 
+pub(crate) mod channel;
+pub(crate) mod fields;
 pub(crate) mod init;
 
 #[cfg(any(test, feature = "testing"))]
@@ -16,6 +25,29 @@ pub mod internal;
 #[cfg(feature = "metrics")]
 pub mod log_volume;
 mod retry_writer;
+mod rotating_writer;
+mod syslog;
+
+#[cfg(target_os = "linux")]
+mod journald;
+
+#[cfg(feature = "telemetry-otlp-grpc")]
+mod output_otlp_grpc;
+
+#[cfg(feature = "tracing")]
+mod trace_correlation;
+
+#[cfg(any(feature = "log-bridge", feature = "tracing-bridge"))]
+mod bridge;
+
+#[cfg(any(test, feature = "testing", feature = "log-redaction"))]
+mod glob;
+
+#[cfg(feature = "log-redaction")]
+mod redaction;
+
+#[cfg(feature = "log-sampling")]
+mod sampling;
 
 use self::init::LogHarness;
 use self::internal::current_log;
@@ -26,8 +58,17 @@ use slog::{Logger, OwnedKV};
 use std::ops::Deref;
 use std::sync::Arc;
 
+pub use self::file_reopen::reopen_file;
+pub use self::verbosity::VerbosityDirectives;
+
+#[cfg(feature = "log-bridge")]
+pub use self::bridge::LogCrateBridge;
+
+#[cfg(feature = "tracing-bridge")]
+pub use self::bridge::TracingBridgeLayer;
+
 #[cfg(any(test, feature = "testing"))]
-pub use self::testing::TestLogRecord;
+pub use self::testing::{LogMatcher, TestLogRecord};
 
 /// Sets current log's verbosity, overriding the settings used in [`init`].
 ///
@@ -39,9 +80,31 @@ pub use self::testing::TestLogRecord;
 /// To avoid this panic, only call `set_verbosity()` when there is an actual change to the
 /// verbosity level.
 ///
+/// Accepts either a single [`LogVerbosity`] for all the modules or [`VerbosityDirectives`] with
+/// per-module verbosity, which replace the [`LoggingSettings::verbosity_directives`]. The modules
+/// that don't match any of the directives use [`LoggingSettings::verbosity`], unless the
+/// directives specify the default level.
+///
+/// # Examples
+/// ```
+/// use foundations::telemetry::log::{self, VerbosityDirectives};
+/// use foundations::telemetry::settings::LogVerbosity;
+///
+/// log::set_verbosity(LogVerbosity::Debug).unwrap();
+///
+/// let directives: VerbosityDirectives = "my_service::proxy=debug,hyper=warn".parse().unwrap();
+///
+/// log::set_verbosity(directives).unwrap();
+/// ```
+///
 /// [`init`]: crate::telemetry::init
-pub fn set_verbosity(verbosity: LogVerbosity) -> Result<()> {
+/// [`LoggingSettings::verbosity`]: crate::telemetry::settings::LoggingSettings::verbosity
+/// [`LoggingSettings::verbosity_directives`]: crate::telemetry::settings::LoggingSettings::verbosity_directives
+pub fn set_verbosity(verbosity: impl Into<VerbosityDirectives>) -> Result<()> {
     let harness = LogHarness::get();
+    let verbosity = verbosity
+        .into()
+        .or_default_verbosity(harness.settings.verbosity);
 
     let current_log = current_log();
     let current_log_lock = current_log.write();
@@ -59,11 +122,40 @@ pub fn set_verbosity(verbosity: LogVerbosity) -> Result<()> {
     };
 
     let kv = OwnedKV(current_log_lock.list().clone());
-    current_log_lock.inner = build_log_with_drain(verbosity, kv, Arc::clone(&harness.root_drain));
+    current_log_lock.inner = build_log_with_drain(
+        verbosity,
+        harness.flight_recorder.clone(),
+        kv,
+        Arc::clone(&harness.root_drain),
+    );
 
     Ok(())
 }
 
+/// Emits the records kept by the flight recorder for the current telemetry context, or the
+/// global ones, depending on [`LogFlightRecorderSettings::scope`].
+///
+/// Does nothing if the flight recorder is not enabled. The records are also emitted
+/// automatically when an error is logged or a panic occurs.
+///
+/// [`LogFlightRecorderSettings::scope`]: crate::telemetry::settings::LogFlightRecorderSettings::scope
+pub fn flush_flight_recorder() {
+    if let Some(recorder) = &LogHarness::get().flight_recorder {
+        recorder.flush_current();
+    }
+}
+
+/// Emits the records kept by the flight recorder for all the telemetry contexts. Returns the
+/// number of the emitted records.
+#[cfg(feature = "telemetry-server")]
+pub(crate) fn flush_all_flight_recorders() -> usize {
+    LogHarness::get()
+        .flight_recorder
+        .as_ref()
+        .map(|recorder| recorder.flush_all())
+        .unwrap_or_default()
+}
+
 /// Gets the current log's verbosity.
 pub fn verbosity() -> LogVerbosity {
     let harness = LogHarness::get();
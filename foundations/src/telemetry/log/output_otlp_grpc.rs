@@ -0,0 +1,152 @@
+use crate::telemetry::otlp_conversion::logs::{convert_record, convert_records};
+use crate::telemetry::settings::OpenTelemetryGrpcOutputSettings;
+use crate::{BootstrapResult, ServiceInfo};
+use anyhow::Context as _;
+use futures_util::future::{BoxFuture, FutureExt as _};
+use http::uri::PathAndQuery;
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs};
+use slog::{Drain, OwnedKVList, Record};
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc};
+use tonic::client::Grpc;
+use tonic::transport::Channel;
+use tonic::{GrpcMethod, Request};
+use tonic_prost::ProstCodec;
+
+static COLLECTOR_PATH: &str = "/opentelemetry.proto.collector.logs.v1.LogsService/Export";
+static LOGS_SERVICE: &str = "opentelemetry.proto.collector.logs.v1.LogsService";
+
+type SharedRecordReceiver = Arc<Mutex<mpsc::Receiver<LogRecord>>>;
+
+/// A drain that passes log records to the OTLP export tasks.
+///
+/// The records are converted on the emitting thread, so they can be associated with the current
+/// span.
+pub(super) struct OtlpLogDrain {
+    record_tx: mpsc::Sender<LogRecord>,
+}
+
+impl Drain for OtlpLogDrain {
+    type Ok = ();
+    type Err = slog::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), slog::Error> {
+        let log_record = convert_record(record, values)?;
+
+        // NOTE: similarly to the async drain of the other outputs, drop the records if the
+        // export can't keep up, rather than blocking the emitting thread.
+        let _ = self.record_tx.try_send(log_record);
+
+        Ok(())
+    }
+}
+
+/// Creates the drain and the future that connects the gRPC channel and spawns the export tasks.
+/// The future must be driven by the telemetry driver.
+///
+/// The export tasks are spawned rather than driven by the telemetry driver, so they keep
+/// exporting the records once the driver completes or shuts down.
+pub(super) fn start(
+    service_info: &ServiceInfo,
+    settings: &OpenTelemetryGrpcOutputSettings,
+    channel_size: usize,
+) -> BootstrapResult<(OtlpLogDrain, BoxFuture<'static, BootstrapResult<()>>)> {
+    let max_batch_size = settings.max_batch_size;
+
+    let grpc_channel = Channel::from_shared(format!("{}/v1/logs", settings.endpoint_url))?
+        .timeout(Duration::from_secs(settings.request_timeout_seconds));
+
+    let (record_tx, record_rx) = mpsc::channel(channel_size);
+    let record_rx = Arc::new(Mutex::new(record_rx));
+
+    let num_tasks = settings.num_tasks;
+    let service_info = service_info.clone();
+
+    // NOTE: don't do any IO or tokio stuff yet - it should be driven by the telemetry driver
+    let init_fut = async move {
+        let grpc_channel = grpc_channel
+            .connect()
+            .await
+            .context("failed to connect gRPC channel for logs")?;
+
+        for _ in 0..num_tasks {
+            let client = Grpc::new(grpc_channel.clone());
+
+            tokio::spawn(do_export(
+                client,
+                service_info.clone(),
+                Arc::clone(&record_rx),
+                max_batch_size,
+            ));
+        }
+
+        anyhow::Ok(())
+    }
+    .boxed();
+
+    Ok((OtlpLogDrain { record_tx }, init_fut))
+}
+
+async fn do_export(
+    mut client: Grpc<Channel>,
+    service_info: ServiceInfo,
+    record_rx: SharedRecordReceiver,
+    max_batch_size: usize,
+) {
+    let mut batch = Vec::with_capacity(max_batch_size);
+
+    loop {
+        // NOTE: release the lock before sending the batch, so other tasks can collect theirs.
+        let received = record_rx
+            .lock()
+            .await
+            .recv_many(&mut batch, max_batch_size)
+            .await;
+
+        if received == 0 {
+            return;
+        }
+
+        let log_records = mem::replace(&mut batch, Vec::with_capacity(max_batch_size));
+        let resource_logs = convert_records(log_records, &service_info);
+
+        if let Err(err) = client.ready().await {
+            reporter_error(err);
+            continue;
+        }
+
+        let send_res = client
+            .unary::<_, ExportLogsServiceResponse, _>(
+                create_request(resource_logs),
+                PathAndQuery::from_static(COLLECTOR_PATH),
+                ProstCodec::default(),
+            )
+            .await;
+
+        if let Err(err) = send_res {
+            reporter_error(err);
+        }
+    }
+}
+
+fn reporter_error(err: impl std::error::Error) {
+    // NOTE: we can't use the log here as the errors would be exported to the same collector.
+    eprintln!("failed to export logs to the OpenTelemetry collector: {err}");
+}
+
+fn create_request(resource_logs: ResourceLogs) -> Request<ExportLogsServiceRequest> {
+    let mut request = Request::new(ExportLogsServiceRequest {
+        resource_logs: vec![resource_logs],
+    });
+
+    request
+        .extensions_mut()
+        .insert(GrpcMethod::new(LOGS_SERVICE, "Export"));
+
+    request
+}
@@ -1,44 +1,173 @@
-use crate::ratelimit::{DirectRateLimiter, Quota, StaticQuantaClock};
-use crate::telemetry::settings::RateLimitingSettings;
-use slog::{Drain, OwnedKVList, Record};
+use crate::ratelimit::{Quota, StaticQuantaClock};
+use crate::telemetry::settings::{
+    LogRateLimitSource, LogSourceRateLimitingSettings, RateLimitingSettings,
+};
+use governor::clock::{Clock, Reference};
+use governor::middleware::NoOpMiddleware;
+use governor::state::{InMemoryState, NotKeyed};
+use slog::{Drain, Level, OwnedKVList, Record, RecordLocation, RecordStatic, b};
+use std::collections::HashMap;
 use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
 
-pub(crate) struct RateLimitingDrain<D> {
+#[cfg(feature = "metrics")]
+use crate::telemetry::metrics::Counter;
+
+#[cfg(feature = "metrics")]
+#[crate::telemetry::metrics::metrics(crate_path = "crate")]
+mod foundations {
+    /// The number of log entries suppressed by the rate limiting.
+    pub fn log_records_suppressed(level: &'static str) -> Counter;
+}
+
+/// A rate limiter that uses the given clock.
+type ClockRateLimiter<C> =
+    governor::RateLimiter<NotKeyed, InMemoryState, C, NoOpMiddleware<<C as Clock>::Instant>>;
+
+type SourceStates<C> = HashMap<(SourceKey, usize), Arc<SourceState<C>>>;
+
+pub(crate) struct RateLimitingDrain<D: Drain, C: Clock + Clone = StaticQuantaClock> {
     inner: D,
-    rate_limiter: Option<DirectRateLimiter>,
+    rate_limiter: Option<ClockRateLimiter<C>>,
+    source_rate_limiter: Option<SourceRateLimiter<C>>,
 }
 
 impl<D: Drain> RateLimitingDrain<D> {
-    pub(crate) fn new(inner: D, settings: &RateLimitingSettings) -> Self {
+    pub(crate) fn new(
+        inner: D,
+        settings: &RateLimitingSettings,
+        source_settings: &LogSourceRateLimitingSettings,
+    ) -> Self {
+        Self::with_clock(
+            inner,
+            settings,
+            source_settings,
+            StaticQuantaClock::default(),
+        )
+    }
+}
+
+impl<D: Drain, C: Clock + Clone> RateLimitingDrain<D, C> {
+    /// Creates the drain with the given clock, so the tests can control the time.
+    fn with_clock(
+        inner: D,
+        settings: &RateLimitingSettings,
+        source_settings: &LogSourceRateLimitingSettings,
+        clock: C,
+    ) -> Self {
         let rate_limiter = if settings.enabled
             && let Some(rate) = NonZeroU32::new(settings.max_events_per_second)
         {
-            Some(DirectRateLimiter::direct_with_clock(
+            Some(ClockRateLimiter::direct_with_clock(
                 Quota::per_second(rate),
-                StaticQuantaClock::default(),
+                clock.clone(),
             ))
         } else {
             None
         };
 
+        let source_rate_limiter = source_settings
+            .enabled
+            .then(|| SourceRateLimiter::new(source_settings, clock));
+
         Self {
             inner,
             rate_limiter,
+            source_rate_limiter,
         }
     }
+
+    fn log_suppression_summary(
+        &self,
+        record_static: &RecordStatic,
+        values: &OwnedKVList,
+        suppressed: Suppressed,
+    ) -> Result<(), D::Err> {
+        self.inner
+            .log(
+                &Record::new(
+                    record_static,
+                    &format_args!(
+                        "suppressed {} similar messages in {:.1?}",
+                        suppressed.count, suppressed.duration
+                    ),
+                    b!("suppressed_count" => suppressed.count),
+                ),
+                values,
+            )
+            .map(|_| ())
+    }
+
+    fn take_suppressed(&self, source: &SourceState<C>) -> Option<Suppressed> {
+        let limiter = self.source_rate_limiter.as_ref()?;
+
+        source.take_suppressed(limiter.clock.now())
+    }
+
+    /// Logs the summaries of the sources that were not followed by a record of the same source.
+    ///
+    /// Such summaries don't have the key-values of the suppressed records, as they are logged
+    /// outside of their context.
+    fn log_pending_suppression_summaries(&self, wait_for_refill: bool) -> Result<(), D::Err> {
+        let Some(limiter) = &self.source_rate_limiter else {
+            return Ok(());
+        };
+
+        for (state, suppressed) in limiter.take_pending(wait_for_refill) {
+            let record_static = RecordStatic {
+                location: &state.location,
+                tag: "",
+                level: state.level,
+            };
+
+            self.log_suppression_summary(
+                &record_static,
+                &OwnedKVList::from(slog::o!()),
+                suppressed,
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
-impl<D: Drain> Drain for RateLimitingDrain<D> {
+impl<D: Drain, C: Clock + Clone> Drain for RateLimitingDrain<D, C> {
     type Ok = ();
     type Err = D::Err;
 
     fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        // NOTE: the source limit is checked first, so the records from a noisy source don't
+        // consume the global limit.
+        let source = match self.source_rate_limiter.as_ref().map(|l| l.check(record)) {
+            Some(SourceCheck::Allowed(source)) => Some(source),
+            Some(SourceCheck::Unlimited) | None => None,
+            Some(SourceCheck::Suppressed) => {
+                count_suppressed(record.level());
+                return Ok(());
+            }
+        };
+
         if let Some(limiter) = &self.rate_limiter
             && limiter.check().is_err()
         {
+            count_suppressed(record.level());
             return Ok(());
         }
 
+        if let Some(suppressed) = source.and_then(|source| self.take_suppressed(&source)) {
+            let record_static = RecordStatic {
+                location: record.location(),
+                tag: record.tag(),
+                level: record.level(),
+            };
+
+            self.log_suppression_summary(&record_static, values, suppressed)?;
+        }
+
+        self.log_pending_suppression_summaries(true)?;
+
         self.inner.log(record, values).map(|_| ())
     }
 
@@ -52,3 +181,344 @@ impl<D: Drain> Drain for RateLimitingDrain<D> {
         Drain::flush(&self.inner)
     }
 }
+
+impl<D: Drain, C: Clock + Clone> Drop for RateLimitingDrain<D, C> {
+    fn drop(&mut self) {
+        let _ = self.log_pending_suppression_summaries(false);
+    }
+}
+
+fn count_suppressed(_level: Level) {
+    #[cfg(feature = "metrics")]
+    foundations::log_records_suppressed(_level.as_str()).inc();
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum SourceKey {
+    Callsite { module: &'static str, line: u32 },
+    MessageTemplate(&'static str),
+}
+
+enum SourceCheck<C: Clock> {
+    /// The record level is not limited.
+    Unlimited,
+    Allowed(Arc<SourceState<C>>),
+    Suppressed,
+}
+
+struct Suppressed {
+    count: u64,
+    duration: Duration,
+}
+
+struct SourceState<C: Clock> {
+    limiter: ClockRateLimiter<C>,
+    refill_interval: Duration,
+    suppressed_count: AtomicU64,
+    suppressed_since: Mutex<Option<C::Instant>>,
+    // NOTE: location and level of the first record of the source, for the summaries that are
+    // logged without a record of the source.
+    location: RecordLocation,
+    level: Level,
+}
+
+impl<C: Clock> SourceState<C> {
+    /// Counts the suppressed record. Returns `true` if it's the first one since the last
+    /// summary.
+    fn suppress(&self, now: C::Instant) -> bool {
+        if self.suppressed_count.fetch_add(1, Ordering::Relaxed) > 0 {
+            return false;
+        }
+
+        *self.suppressed_since.lock().unwrap() = Some(now);
+
+        true
+    }
+
+    /// Checks whether the limiter had the time to allow a record since the first suppressed one.
+    fn is_refilled(&self, now: C::Instant) -> bool {
+        self.suppressed_since
+            .lock()
+            .unwrap()
+            .is_some_and(|since| Duration::from(now.duration_since(since)) >= self.refill_interval)
+    }
+
+    fn take_suppressed(&self, now: C::Instant) -> Option<Suppressed> {
+        let count = self.suppressed_count.swap(0, Ordering::Relaxed);
+
+        if count == 0 {
+            return None;
+        }
+
+        let duration = self
+            .suppressed_since
+            .lock()
+            .unwrap()
+            .take()
+            .map(|since| now.duration_since(since).into())
+            .unwrap_or_default();
+
+        Some(Suppressed { count, duration })
+    }
+}
+
+/// Rate limiter with a separate budget for each source and level of the log records.
+struct SourceRateLimiter<C: Clock> {
+    source: LogRateLimitSource,
+    // NOTE: indexed by `Level::as_usize() - 1`.
+    quotas: [Option<Quota>; 6],
+    states: RwLock<SourceStates<C>>,
+    // NOTE: the sources with the suppressed records that still need a summary.
+    pending: Mutex<Vec<Arc<SourceState<C>>>>,
+    has_pending: AtomicBool,
+    clock: C,
+}
+
+impl<C: Clock + Clone> SourceRateLimiter<C> {
+    fn new(settings: &LogSourceRateLimitingSettings, clock: C) -> Self {
+        let levels = &settings.level_max_events_per_second;
+
+        let quotas = [
+            levels.critical,
+            levels.error,
+            levels.warning,
+            levels.info,
+            levels.debug,
+            levels.trace,
+        ]
+        .map(|rate| {
+            NonZeroU32::new(rate.unwrap_or(settings.max_events_per_second)).map(Quota::per_second)
+        });
+
+        Self {
+            source: settings.source,
+            quotas,
+            states: Default::default(),
+            pending: Default::default(),
+            has_pending: AtomicBool::new(false),
+            clock,
+        }
+    }
+
+    fn check(&self, record: &Record) -> SourceCheck<C> {
+        let level_idx = record.level().as_usize() - 1;
+
+        let Some(quota) = self.quotas[level_idx] else {
+            return SourceCheck::Unlimited;
+        };
+
+        let key = match (self.source, record.msg().as_str()) {
+            (LogRateLimitSource::MessageTemplate, Some(template)) => {
+                SourceKey::MessageTemplate(template)
+            }
+            _ => SourceKey::Callsite {
+                module: record.module(),
+                line: record.line(),
+            },
+        };
+
+        let state = self.state(key, level_idx, quota, record);
+
+        if state.limiter.check().is_err() {
+            if state.suppress(self.clock.now()) {
+                self.pending.lock().unwrap().push(Arc::clone(&state));
+                self.has_pending.store(true, Ordering::Relaxed);
+            }
+
+            return SourceCheck::Suppressed;
+        }
+
+        SourceCheck::Allowed(state)
+    }
+
+    fn state(
+        &self,
+        key: SourceKey,
+        level_idx: usize,
+        quota: Quota,
+        record: &Record,
+    ) -> Arc<SourceState<C>> {
+        if let Some(state) = self.states.read().unwrap().get(&(key, level_idx)) {
+            return Arc::clone(state);
+        }
+
+        let mut states = self.states.write().unwrap();
+        let state = states.entry((key, level_idx)).or_insert_with(|| {
+            Arc::new(SourceState {
+                limiter: ClockRateLimiter::direct_with_clock(quota, self.clock.clone()),
+                refill_interval: quota.replenish_interval(),
+                suppressed_count: AtomicU64::new(0),
+                suppressed_since: Mutex::new(None),
+                location: *record.location(),
+                level: record.level(),
+            })
+        });
+
+        Arc::clone(state)
+    }
+
+    /// Takes the suppressed records of the pending sources, either all of them or only the ones
+    /// that were suppressed for longer than it takes their limiter to refill.
+    fn take_pending(&self, wait_for_refill: bool) -> Vec<(Arc<SourceState<C>>, Suppressed)> {
+        if !self.has_pending.load(Ordering::Relaxed) {
+            return vec![];
+        }
+
+        // NOTE: don't block the logging threads on the other threads taking the summaries.
+        let mut pending = if wait_for_refill {
+            match self.pending.try_lock() {
+                Ok(pending) => pending,
+                Err(_) => return vec![],
+            }
+        } else {
+            self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+        };
+
+        let now = self.clock.now();
+        let mut summaries = vec![];
+
+        pending.retain(|state| {
+            // NOTE: the summary was already logged with a record of the source.
+            if state.suppressed_count.load(Ordering::Relaxed) == 0 {
+                return false;
+            }
+
+            if wait_for_refill && !state.is_refilled(now) {
+                return true;
+            }
+
+            if let Some(suppressed) = state.take_suppressed(now) {
+                summaries.push((Arc::clone(state), suppressed));
+            }
+
+            false
+        });
+
+        self.has_pending
+            .store(!pending.is_empty(), Ordering::Relaxed);
+
+        summaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use governor::clock::FakeRelativeClock;
+    use slog::{Logger, Never, o};
+
+    #[derive(Clone, Default)]
+    struct RecordingDrain(Arc<Mutex<Vec<String>>>);
+
+    impl Drain for RecordingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+            self.0.lock().unwrap().push(record.msg().to_string());
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pending_summaries_logged_on_drop() {
+        let recorded = RecordingDrain::default();
+
+        let drain = RateLimitingDrain::new(
+            recorded.clone(),
+            &Default::default(),
+            &LogSourceRateLimitingSettings {
+                enabled: true,
+                max_events_per_second: 1,
+                ..Default::default()
+            },
+        );
+
+        let log = Logger::root(drain.fuse(), o!());
+
+        for i in 0..3 {
+            slog::warn!(log, "hot loop {}", i);
+        }
+
+        assert_eq!(*recorded.0.lock().unwrap(), ["hot loop 0"]);
+
+        drop(log);
+
+        let recorded = recorded.0.lock().unwrap();
+
+        assert_eq!(recorded.len(), 2);
+        assert!(recorded[1].starts_with("suppressed 2 similar messages in "));
+    }
+
+    fn logger_with_clock(recorded: &RecordingDrain, clock: &FakeRelativeClock) -> Logger {
+        let drain = RateLimitingDrain::with_clock(
+            recorded.clone(),
+            &Default::default(),
+            &LogSourceRateLimitingSettings {
+                enabled: true,
+                max_events_per_second: 2,
+                ..Default::default()
+            },
+            clock.clone(),
+        );
+
+        Logger::root(drain.fuse(), o!())
+    }
+
+    #[test]
+    fn summary_logged_with_next_record_of_source() {
+        let recorded = RecordingDrain::default();
+        let clock = FakeRelativeClock::default();
+        let log = logger_with_clock(&recorded, &clock);
+
+        let hot_loop = || {
+            for i in 0..100 {
+                slog::warn!(log, "hot loop {}", i);
+            }
+        };
+
+        hot_loop();
+
+        assert_eq!(*recorded.0.lock().unwrap(), ["hot loop 0", "hot loop 1"]);
+
+        clock.advance(Duration::from_secs(1));
+        hot_loop();
+
+        assert_eq!(
+            recorded.0.lock().unwrap()[2..],
+            [
+                "suppressed 98 similar messages in 1.0s",
+                "hot loop 0",
+                "hot loop 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_summary_logged_once_source_refilled() {
+        let recorded = RecordingDrain::default();
+        let clock = FakeRelativeClock::default();
+        let log = logger_with_clock(&recorded, &clock);
+
+        for i in 0..3 {
+            slog::warn!(log, "hot loop {}", i);
+        }
+
+        clock.advance(Duration::from_millis(100));
+        slog::warn!(log, "other callsite");
+
+        assert_eq!(
+            *recorded.0.lock().unwrap(),
+            ["hot loop 0", "hot loop 1", "other callsite"]
+        );
+
+        clock.advance(Duration::from_millis(500));
+        slog::warn!(log, "other callsite");
+
+        assert_eq!(
+            recorded.0.lock().unwrap()[3..],
+            ["suppressed 1 similar messages in 600.0ms", "other callsite"]
+        );
+    }
+}
@@ -0,0 +1,648 @@
+use super::glob::glob_to_regex;
+use crate::telemetry::settings::{
+    LogRedactionAction, LogRedactionPattern, LogRedactionRule, LogRedactionSettings,
+};
+use regex::{Captures, Regex};
+use slog::{BorrowedKV, Drain, KV, Key, OwnedKV, OwnedKVList, Record, RecordStatic, Serializer};
+use std::fmt::{Arguments, Display};
+use std::hash::Hasher as _;
+use std::sync::Arc;
+
+const MASK: &str = "****";
+
+/// Redaction applied to a single field value.
+enum Redaction {
+    Keep,
+    Drop,
+    Replace(String),
+}
+
+struct ValueMatcher {
+    regex: Regex,
+    // NOTE: used to filter out the false positives that can't be ruled out by the regex.
+    validate: Option<fn(&str) -> bool>,
+}
+
+impl ValueMatcher {
+    fn new(
+        pattern: Option<LogRedactionPattern>,
+        regex: Option<&str>,
+    ) -> crate::Result<Option<Self>> {
+        let (regex, validate) = match (pattern, regex) {
+            (Some(_), Some(_)) => {
+                return Err("both value pattern and value regex are specified".into());
+            }
+            (Some(LogRedactionPattern::Email), None) => (
+                r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+                None,
+            ),
+            (Some(LogRedactionPattern::BearerToken), None) => {
+                (r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]+=*", None)
+            }
+            (Some(LogRedactionPattern::CardNumber), None) => (
+                r"\b\d(?:[ -]?\d){12,18}\b",
+                Some(is_luhn_valid as fn(&str) -> bool),
+            ),
+            (None, Some(regex)) => (regex, None),
+            (None, None) => return Ok(None),
+        };
+
+        Ok(Some(Self {
+            regex: Regex::new(regex)?,
+            validate,
+        }))
+    }
+
+    fn is_valid(&self, found: &str) -> bool {
+        self.validate.is_none_or(|validate| validate(found))
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        self.regex
+            .find_iter(value)
+            .any(|found| self.is_valid(found.as_str()))
+    }
+}
+
+struct Rule {
+    key: Option<Regex>,
+    value: Option<ValueMatcher>,
+    action: LogRedactionAction,
+}
+
+impl Rule {
+    fn new(settings: &LogRedactionRule) -> crate::Result<Self> {
+        let key = match (&settings.key_glob, &settings.key_regex) {
+            (Some(_), Some(_)) => return Err("both key glob and key regex are specified".into()),
+            (Some(glob), None) => Some(Regex::new(&glob_to_regex(glob))?),
+            (None, Some(regex)) => Some(Regex::new(regex)?),
+            (None, None) => None,
+        };
+
+        let value = ValueMatcher::new(settings.value_pattern, settings.value_regex.as_deref())?;
+
+        if key.is_none() && value.is_none() {
+            return Err("neither key nor value pattern is specified".into());
+        }
+
+        Ok(Self {
+            key,
+            value,
+            action: settings.action,
+        })
+    }
+
+    fn matches_key(&self, key: &str) -> bool {
+        self.key.as_ref().is_none_or(|regex| regex.is_match(key))
+    }
+}
+
+/// Applies the [`LogRedactionSettings`] rules to the log record messages and field values.
+pub(crate) struct Redactor {
+    rules: Vec<Rule>,
+    has_message_rules: bool,
+    hash_key: (u64, u64),
+}
+
+impl Redactor {
+    pub(crate) fn new(settings: &LogRedactionSettings) -> crate::Result<Self> {
+        let rules = settings
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                Rule::new(rule).map_err(|err| format!("invalid redaction rule #{i}: {err}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let uses_hash = rules
+            .iter()
+            .any(|rule| matches!(rule.action, LogRedactionAction::Hash));
+
+        let hash_key = if uses_hash {
+            parse_hash_key(&settings.hash_key)?
+        } else {
+            Default::default()
+        };
+
+        Ok(Self {
+            has_message_rules: rules.iter().any(|rule| rule.key.is_none()),
+            rules,
+            hash_key,
+        })
+    }
+
+    fn redact_message(&self, message: &Arguments) -> Option<String> {
+        if !self.has_message_rules {
+            return None;
+        }
+
+        let mut message = message.to_string();
+        let mut redacted = false;
+
+        for rule in self.rules.iter().filter(|rule| rule.key.is_none()) {
+            let Some(matcher) = &rule.value else {
+                continue;
+            };
+
+            if let Some(replaced) = self.replace_matches(matcher, rule.action, &message) {
+                message = replaced;
+                redacted = true;
+            }
+        }
+
+        redacted.then_some(message)
+    }
+
+    fn redact_field(&self, key: &str, value: &dyn Display) -> Redaction {
+        // NOTE: the value is only formatted if there is a rule that needs it.
+        let mut formatted: Option<String> = None;
+        let mut redacted = false;
+
+        for rule in self.rules.iter().filter(|rule| rule.matches_key(key)) {
+            let Some(matcher) = &rule.value else {
+                return match rule.action {
+                    LogRedactionAction::Drop => Redaction::Drop,
+                    LogRedactionAction::Mask => Redaction::Replace(MASK.into()),
+                    LogRedactionAction::Hash => {
+                        let value = formatted.get_or_insert_with(|| value.to_string());
+
+                        Redaction::Replace(self.hash(value))
+                    }
+                };
+            };
+
+            let value = formatted.get_or_insert_with(|| value.to_string());
+
+            if matches!(rule.action, LogRedactionAction::Drop) {
+                if matcher.is_match(value) {
+                    return Redaction::Drop;
+                }
+
+                continue;
+            }
+
+            if let Some(replaced) = self.replace_matches(matcher, rule.action, value) {
+                *value = replaced;
+                redacted = true;
+            }
+        }
+
+        match formatted {
+            Some(value) if redacted => Redaction::Replace(value),
+            _ => Redaction::Keep,
+        }
+    }
+
+    /// Masks or hashes the parts of the value that match. Returns `None` if nothing matches.
+    fn replace_matches(
+        &self,
+        matcher: &ValueMatcher,
+        action: LogRedactionAction,
+        value: &str,
+    ) -> Option<String> {
+        let mut found = false;
+
+        let replaced = matcher.regex.replace_all(value, |captures: &Captures| {
+            let matched = &captures[0];
+
+            if !matcher.is_valid(matched) {
+                return matched.to_string();
+            }
+
+            found = true;
+
+            match action {
+                LogRedactionAction::Hash => self.hash(matched),
+                LogRedactionAction::Drop | LogRedactionAction::Mask => MASK.to_string(),
+            }
+        });
+
+        found.then(|| replaced.into_owned())
+    }
+
+    fn hash(&self, value: &str) -> String {
+        // NOTE: SipHash is a keyed pseudorandom function, which makes it impossible to recover
+        // the short values, like emails, by hashing the candidates without knowing the key. It's
+        // deprecated in `std` only in favour of `DefaultHasher`, which is not keyed.
+        #[allow(deprecated)]
+        let mut hasher = std::hash::SipHasher::new_with_keys(self.hash_key.0, self.hash_key.1);
+
+        hasher.write(value.as_bytes());
+
+        format!("hash:{:016x}", hasher.finish())
+    }
+}
+
+fn parse_hash_key(key: &str) -> crate::Result<(u64, u64)> {
+    if key.len() != 32 {
+        return Err("redaction hash key must be 32 hexadecimal digits".into());
+    }
+
+    let key = u128::from_str_radix(key, 16)
+        .map_err(|_| "redaction hash key must be 32 hexadecimal digits")?;
+
+    Ok(((key >> 64) as u64, key as u64))
+}
+
+// NOTE: https://en.wikipedia.org/wiki/Luhn_algorithm
+fn is_luhn_valid(number: &str) -> bool {
+    let sum: u32 = number
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
+
+/// A drain that redacts the sensitive data in the log records.
+pub(crate) struct RedactionDrain<D> {
+    inner: D,
+    redactor: Arc<Redactor>,
+}
+
+impl<D> RedactionDrain<D> {
+    pub(crate) fn new(inner: D, settings: &LogRedactionSettings) -> crate::Result<Self> {
+        Ok(Self {
+            inner,
+            redactor: Arc::new(Redactor::new(settings)?),
+        })
+    }
+}
+
+impl<D: Drain> Drain for RedactionDrain<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        if self.redactor.rules.is_empty() {
+            return self.inner.log(record, values);
+        }
+
+        let context_fields_kv = OwnedKV(RedactingKV {
+            inner: values.clone(),
+            redactor: Arc::clone(&self.redactor),
+        })
+        .into();
+
+        let record_fields_kv = RedactingKV {
+            inner: record.kv(),
+            redactor: Arc::clone(&self.redactor),
+        };
+
+        let record_static = RecordStatic {
+            location: record.location(),
+            tag: record.tag(),
+            level: record.level(),
+        };
+
+        match self.redactor.redact_message(record.msg()) {
+            Some(message) => self.inner.log(
+                &Record::new(
+                    &record_static,
+                    &format_args!("{message}"),
+                    BorrowedKV(&record_fields_kv),
+                ),
+                &context_fields_kv,
+            ),
+            None => self.inner.log(
+                &Record::new(&record_static, record.msg(), BorrowedKV(&record_fields_kv)),
+                &context_fields_kv,
+            ),
+        }
+    }
+
+    #[inline]
+    fn is_enabled(&self, level: slog::Level) -> bool {
+        Drain::is_enabled(&self.inner, level)
+    }
+
+    #[inline]
+    fn flush(&self) -> Result<(), slog::FlushError> {
+        Drain::flush(&self.inner)
+    }
+}
+
+struct RedactingKV<K> {
+    inner: K,
+    redactor: Arc<Redactor>,
+}
+
+impl<K: KV> KV for RedactingKV<K> {
+    fn serialize(&self, record: &Record, inner: &mut dyn Serializer) -> slog::Result {
+        let mut serializer = RedactingSerializer {
+            inner,
+            redactor: &self.redactor,
+        };
+
+        self.inner.serialize(record, &mut serializer)
+    }
+}
+
+struct RedactingSerializer<'s> {
+    inner: &'s mut dyn Serializer,
+    redactor: &'s Redactor,
+}
+
+macro_rules! redact {
+    ( $self:ident.$fn:ident($key:expr, $val:expr) ) => {{
+        match $self.redactor.redact_field($key, &$val) {
+            Redaction::Keep => $self.inner.$fn($key, $val),
+            Redaction::Drop => Ok(()),
+            Redaction::Replace(value) => $self.inner.emit_str($key, &value),
+        }
+    }};
+}
+
+impl Serializer for RedactingSerializer<'_> {
+    fn emit_arguments(&mut self, key: Key, val: &Arguments) -> slog::Result {
+        redact!(self.emit_arguments(key, val))
+    }
+
+    fn emit_usize(&mut self, key: Key, val: usize) -> slog::Result {
+        redact!(self.emit_usize(key, val))
+    }
+
+    fn emit_isize(&mut self, key: Key, val: isize) -> slog::Result {
+        redact!(self.emit_isize(key, val))
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        redact!(self.emit_bool(key, val))
+    }
+
+    fn emit_char(&mut self, key: Key, val: char) -> slog::Result {
+        redact!(self.emit_char(key, val))
+    }
+
+    fn emit_u8(&mut self, key: Key, val: u8) -> slog::Result {
+        redact!(self.emit_u8(key, val))
+    }
+
+    fn emit_i8(&mut self, key: Key, val: i8) -> slog::Result {
+        redact!(self.emit_i8(key, val))
+    }
+
+    fn emit_u16(&mut self, key: Key, val: u16) -> slog::Result {
+        redact!(self.emit_u16(key, val))
+    }
+
+    fn emit_i16(&mut self, key: Key, val: i16) -> slog::Result {
+        redact!(self.emit_i16(key, val))
+    }
+
+    fn emit_u32(&mut self, key: Key, val: u32) -> slog::Result {
+        redact!(self.emit_u32(key, val))
+    }
+
+    fn emit_i32(&mut self, key: Key, val: i32) -> slog::Result {
+        redact!(self.emit_i32(key, val))
+    }
+
+    fn emit_f32(&mut self, key: Key, val: f32) -> slog::Result {
+        redact!(self.emit_f32(key, val))
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        redact!(self.emit_u64(key, val))
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        redact!(self.emit_i64(key, val))
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        redact!(self.emit_f64(key, val))
+    }
+
+    #[cfg(integer128)]
+    fn emit_u128(&mut self, key: Key, val: u128) -> slog::Result {
+        redact!(self.emit_u128(key, val))
+    }
+
+    #[cfg(integer128)]
+    fn emit_i128(&mut self, key: Key, val: i128) -> slog::Result {
+        redact!(self.emit_i128(key, val))
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> slog::Result {
+        redact!(self.emit_str(key, val))
+    }
+
+    fn emit_unit(&mut self, key: Key) -> slog::Result {
+        match self.redactor.redact_field(key, &"()") {
+            Redaction::Keep => self.inner.emit_unit(key),
+            Redaction::Drop => Ok(()),
+            Redaction::Replace(value) => self.inner.emit_str(key, &value),
+        }
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        match self.redactor.redact_field(key, &"None") {
+            Redaction::Keep => self.inner.emit_none(key),
+            Redaction::Drop => Ok(()),
+            Redaction::Replace(value) => self.inner.emit_str(key, &value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::settings::LoggingSettings;
+    use crate::telemetry::{TestTelemetryContext, log, log::TestLogRecord};
+    use foundations_macros::with_test_telemetry;
+    use slog::Level;
+
+    fn rule(
+        key_glob: Option<&str>,
+        value_pattern: Option<LogRedactionPattern>,
+        action: LogRedactionAction,
+    ) -> LogRedactionRule {
+        LogRedactionRule {
+            key_glob: key_glob.map(Into::into),
+            value_pattern,
+            action,
+            ..Default::default()
+        }
+    }
+
+    fn redact(redactor: &Redactor, key: &str, value: &str) -> Option<String> {
+        match redactor.redact_field(key, &value) {
+            Redaction::Keep => Some(value.into()),
+            Redaction::Drop => None,
+            Redaction::Replace(value) => Some(value),
+        }
+    }
+
+    #[test]
+    fn value_patterns() {
+        let redactor = Redactor::new(&LogRedactionSettings {
+            rules: vec![
+                rule(
+                    None,
+                    Some(LogRedactionPattern::Email),
+                    LogRedactionAction::Mask,
+                ),
+                rule(
+                    None,
+                    Some(LogRedactionPattern::BearerToken),
+                    LogRedactionAction::Mask,
+                ),
+                rule(
+                    None,
+                    Some(LogRedactionPattern::CardNumber),
+                    LogRedactionAction::Drop,
+                ),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            redact(&redactor, "user", "name: john.doe@example.co.uk").as_deref(),
+            Some("name: ****")
+        );
+        assert_eq!(
+            redact(&redactor, "auth", "Bearer eyJhbGciOi.J9eyJzd=").as_deref(),
+            Some("****")
+        );
+        assert_eq!(redact(&redactor, "card", "4111 1111 1111 1111"), None);
+        assert_eq!(
+            redact(&redactor, "order", "4111 1111 1111 1112").as_deref(),
+            Some("4111 1111 1111 1112")
+        );
+        assert_eq!(
+            redactor
+                .redact_message(&format_args!("Sent email to {}", "jane@example.com"))
+                .as_deref(),
+            Some("Sent email to ****")
+        );
+        assert_eq!(redactor.redact_message(&format_args!("Hello")), None);
+    }
+
+    #[test]
+    fn key_patterns() {
+        let redactor = Redactor::new(&LogRedactionSettings {
+            rules: vec![
+                rule(Some("*password*"), None, LogRedactionAction::Drop),
+                LogRedactionRule {
+                    key_regex: Some("^(user|client)_ip$".into()),
+                    action: LogRedactionAction::Mask,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(redact(&redactor, "db_password_hash", "foo"), None);
+        assert_eq!(
+            redact(&redactor, "client_ip", "192.0.2.1").as_deref(),
+            Some("****")
+        );
+        assert_eq!(
+            redact(&redactor, "server_ip", "192.0.2.2").as_deref(),
+            Some("192.0.2.2")
+        );
+        assert_eq!(redactor.redact_message(&format_args!("password")), None);
+    }
+
+    #[test]
+    fn keyed_hash() {
+        let settings = |hash_key: &str| LogRedactionSettings {
+            rules: vec![rule(Some("email"), None, LogRedactionAction::Hash)],
+            hash_key: hash_key.into(),
+        };
+
+        let redactor1 = Redactor::new(&settings("000102030405060708090a0b0c0d0e0f")).unwrap();
+        let redactor2 = Redactor::new(&settings("0f0e0d0c0b0a09080706050403020100")).unwrap();
+
+        let hash1 = redact(&redactor1, "email", "jane@example.com").unwrap();
+
+        assert!(hash1.starts_with("hash:"));
+        assert_eq!(hash1.len(), "hash:".len() + 16);
+        assert_eq!(
+            redact(&redactor1, "email", "jane@example.com"),
+            Some(hash1.clone())
+        );
+        assert_ne!(
+            redact(&redactor1, "email", "john@example.com"),
+            Some(hash1.clone())
+        );
+        assert_ne!(redact(&redactor2, "email", "jane@example.com"), Some(hash1));
+
+        assert!(Redactor::new(&settings("")).is_err());
+        assert!(Redactor::new(&settings("not a hex key")).is_err());
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let new = |rule| {
+            Redactor::new(&LogRedactionSettings {
+                rules: vec![rule],
+                ..Default::default()
+            })
+        };
+
+        assert!(new(LogRedactionRule::default()).is_err());
+        assert!(
+            new(LogRedactionRule {
+                key_glob: Some("a".into()),
+                key_regex: Some("b".into()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+        assert!(
+            new(LogRedactionRule {
+                value_regex: Some("(".into()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[with_test_telemetry(test, crate_path = "crate")]
+    fn redact_records(mut ctx: TestTelemetryContext) {
+        ctx.set_logging_settings(LoggingSettings {
+            redaction: LogRedactionSettings {
+                rules: vec![
+                    rule(
+                        None,
+                        Some(LogRedactionPattern::Email),
+                        LogRedactionAction::Mask,
+                    ),
+                    rule(Some("*token"), None, LogRedactionAction::Drop),
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        log::add_fields! {
+            "session_token" => "abc", "user" => "jane@example.com"
+        }
+
+        log::warn!("Sent email to {}", "john@example.com"; "attempt" => 1, "access_token" => "def");
+
+        assert_eq!(
+            *ctx.log_records(),
+            vec![TestLogRecord {
+                level: Level::Warning,
+                message: "Sent email to ****".into(),
+                fields: vec![
+                    ("user".into(), "****".into()),
+                    ("attempt".into(), "1".into()),
+                ]
+            }]
+        );
+    }
+}
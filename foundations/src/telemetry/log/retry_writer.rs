@@ -1,4 +1,5 @@
-use std::fs::File;
+use super::file_reopen::ReopenTracker;
+use std::fs::{File, OpenOptions};
 use std::io;
 use std::io::Write;
 use std::path::PathBuf;
@@ -11,13 +12,21 @@ use std::path::PathBuf;
 ///
 /// Writes will be blocked while re-opening the pipe and once connected the next writes
 /// may block until the reader has connected. Blocking is prefered over dropping since this will provide
-/// backpressure to wrappers like [`slog_async::Async`] which by default will drop and report
-/// records once the internal channel is overflowed. Dropped record reports may also be dropped if
-/// the reader does not recover in a timely manner.
+/// backpressure to the log channel which by default will drop and report records once it is
+/// overflowed (see [`LogChannelSettings`]). Dropped record reports may also be dropped if the
+/// reader does not recover in a timely manner.
+///
+/// [`LogChannelSettings`]: crate::telemetry::settings::LogChannelSettings
+///
+/// The file is also reopened on record boundaries, i.e. on the first write after a flush, if it
+/// has been renamed or removed, or if the reopen has been requested via
+/// [`crate::telemetry::log::reopen_file`].
 pub(crate) struct RetryPipeWriter {
     path: PathBuf,
     pipe_file: File,
     max_attempts: i32,
+    reopen_tracker: ReopenTracker,
+    at_record_boundary: bool,
 }
 
 impl RetryPipeWriter {
@@ -25,6 +34,8 @@ impl RetryPipeWriter {
         let file = File::create(&path)?;
         Ok(Self {
             path,
+            reopen_tracker: ReopenTracker::new(&file),
+            at_record_boundary: true,
             pipe_file: file,
             // This number was selected by casually observing unit test failures.
             // It's assumed that this simple approach will cover most cases but
@@ -38,10 +49,29 @@ impl RetryPipeWriter {
         let _ = std::mem::replace(&mut self.pipe_file, File::create(&self.path)?);
         Ok(())
     }
+
+    /// Reopens the file without truncating it, as it might already contain records written by
+    /// this or other processes.
+    fn reopen_file_for_append(&mut self) -> io::Result<()> {
+        self.pipe_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        self.reopen_tracker = ReopenTracker::new(&self.pipe_file);
+
+        Ok(())
+    }
 }
 
 impl Write for RetryPipeWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.at_record_boundary && self.reopen_tracker.should_reopen(&self.path) {
+            self.reopen_file_for_append()?;
+        }
+
+        self.at_record_boundary = false;
+
         let mut attempts = 0;
         while attempts <= self.max_attempts {
             let result = self.pipe_file.write(buf);
@@ -65,19 +95,22 @@ impl Write for RetryPipeWriter {
 
     /// Flushes the file. On *nix this does nothing.
     fn flush(&mut self) -> io::Result<()> {
+        self.at_record_boundary = true;
         self.pipe_file.flush()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::telemetry::log::reopen_file;
     use crate::telemetry::log::retry_writer::RetryPipeWriter;
     use nix::sys::stat;
     use nix::unistd;
     use std::fs::{self, OpenOptions};
     use std::io::{Read, Write};
     use std::thread;
-    use tempfile::NamedTempFile;
+    use std::time::Duration;
+    use tempfile::{NamedTempFile, TempDir};
 
     #[test]
     fn test_regular_file() {
@@ -139,4 +172,46 @@ mod tests {
         handler.join().unwrap();
         fs::remove_file(fifo_path).unwrap();
     }
+
+    #[test]
+    fn test_reopen_on_request() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("service.log");
+        let rotated_path = dir.path().join("service.log.1");
+        let mut writer = RetryPipeWriter::new(path.clone()).unwrap();
+
+        writer.write_all(b"first\n").unwrap();
+        writer.flush().unwrap();
+
+        fs::rename(&path, &rotated_path).unwrap();
+        // Recreate the file like `logrotate` does with the `create` option.
+        fs::write(&path, "").unwrap();
+        reopen_file();
+
+        writer.write_all(b"second\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&rotated_path).unwrap(), "first\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+    }
+
+    #[test]
+    fn test_reopen_removed_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("service.log");
+        let mut writer = RetryPipeWriter::new(path.clone()).unwrap();
+
+        writer.write_all(b"first\n").unwrap();
+        writer.flush().unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        // Wait for the next path check.
+        thread::sleep(Duration::from_millis(1100));
+
+        writer.write_all(b"second\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+    }
 }
@@ -0,0 +1,500 @@
+use super::file_reopen::ReopenTracker;
+use crate::telemetry::settings::LogRotationSettings;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const GZ_EXTENSION: &str = "gz";
+
+/// A log file writer which rotates the file by size and time.
+///
+/// The rotation itself only renames the current file and opens a new one, so it's cheap enough
+/// to be performed on the log drain thread. Compression and retention of the rotated files are
+/// handled by a background thread which is notified on each rotation.
+///
+/// The file is rotated only on record boundaries, i.e. on the first write after a flush, so
+/// a single record never spans multiple files. On record boundaries the file is also reopened if
+/// it has been renamed or removed, or if the reopen has been requested via
+/// [`crate::telemetry::log::reopen_file`].
+pub(crate) struct RotatingFileWriter {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    opened_at: Instant,
+    max_size: Option<u64>,
+    interval: Option<Duration>,
+    at_record_boundary: bool,
+    rotated_files: RotatedFiles,
+    rotation_notifier: mpsc::Sender<()>,
+    reopen_tracker: ReopenTracker,
+}
+
+impl RotatingFileWriter {
+    pub(super) fn new(path: PathBuf, settings: &LogRotationSettings) -> io::Result<Self> {
+        let file = open_file(&path)?;
+        let rotated_files = RotatedFiles::new(&path, settings)?;
+        let (rotation_notifier, rotations) = mpsc::channel();
+
+        let worker_rotated_files = rotated_files.clone();
+
+        thread::Builder::new()
+            .name("foundations-log-rotation".into())
+            .spawn(move || {
+                // NOTE: also process the files that might have been left unprocessed by the
+                // previous runs of the service.
+                worker_rotated_files.process_and_report_errors();
+
+                while rotations.recv().is_ok() {
+                    worker_rotated_files.process_and_report_errors();
+                }
+            })?;
+
+        Ok(Self {
+            size: file.metadata()?.len(),
+            reopen_tracker: ReopenTracker::new(&file),
+            path,
+            file,
+            opened_at: Instant::now(),
+            max_size: settings.max_size_bytes,
+            interval: settings.interval_seconds.map(Duration::from_secs),
+            at_record_boundary: true,
+            rotated_files,
+            rotation_notifier,
+        })
+    }
+
+    fn should_rotate(&self) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+
+        self.max_size.is_some_and(|max_size| self.size >= max_size)
+            || self
+                .interval
+                .is_some_and(|interval| self.opened_at.elapsed() >= interval)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated_path = self.rotated_files.next_rotated_path(SystemTime::now());
+
+        fs::rename(&self.path, rotated_path)?;
+
+        self.file = open_file(&self.path)?;
+        self.size = 0;
+        self.opened_at = Instant::now();
+        self.reopen_tracker = ReopenTracker::new(&self.file);
+
+        // NOTE: the worker thread only exits if the writer is dropped.
+        let _ = self.rotation_notifier.send(());
+
+        Ok(())
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = open_file(&self.path)?;
+        self.size = self.file.metadata()?.len();
+        self.reopen_tracker = ReopenTracker::new(&self.file);
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.at_record_boundary {
+            if self.reopen_tracker.should_reopen(&self.path) {
+                self.reopen()?;
+            }
+
+            if self.should_rotate() {
+                self.rotate()?;
+            }
+        }
+
+        let written = self.file.write(buf)?;
+
+        self.size += written as u64;
+        self.at_record_boundary = false;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.at_record_boundary = true;
+        self.file.flush()
+    }
+}
+
+/// Rotated log files of a log file.
+#[derive(Clone)]
+struct RotatedFiles {
+    dir: PathBuf,
+    file_name: OsString,
+    compress: bool,
+    max_files: Option<usize>,
+    max_age: Option<Duration>,
+}
+
+impl RotatedFiles {
+    fn new(path: &Path, settings: &LogRotationSettings) -> io::Result<Self> {
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("log file path should have a file name: {}", path.display()),
+            )
+        })?;
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        Ok(Self {
+            dir,
+            file_name: file_name.to_os_string(),
+            compress: settings.compress,
+            max_files: settings.max_files,
+            max_age: settings.max_age_seconds.map(Duration::from_secs),
+        })
+    }
+
+    /// Returns a path for the next rotated file that doesn't collide with the existing ones.
+    fn next_rotated_path(&self, now: SystemTime) -> PathBuf {
+        let mut timestamp = unix_millis(now);
+
+        loop {
+            let path = self.rotated_path(timestamp, false);
+
+            if !path.exists() && !self.rotated_path(timestamp, true).exists() {
+                return path;
+            }
+
+            timestamp += 1;
+        }
+    }
+
+    fn rotated_path(&self, timestamp: u64, compressed: bool) -> PathBuf {
+        let mut file_name = self.file_name.clone();
+
+        file_name.push(format!(".{timestamp}"));
+
+        if compressed {
+            file_name.push(format!(".{GZ_EXTENSION}"));
+        }
+
+        self.dir.join(file_name)
+    }
+
+    fn process_and_report_errors(&self) {
+        if let Err(err) = self.process(SystemTime::now()) {
+            // NOTE: we can't use the log here as it might be the source of the error.
+            eprintln!("failed to process rotated log files: {err}");
+        }
+    }
+
+    /// Compresses the rotated files if required and removes the files that exceed the
+    /// retention limits.
+    fn process(&self, now: SystemTime) -> io::Result<()> {
+        // NOTE: the retention limits are applied even if some of the files fail to be processed,
+        // as they are needed the most when the compression fails due to the disk being full.
+        // The first error is reported.
+        let mut res = Ok(());
+
+        if self.compress {
+            for (timestamp, compressed) in self.list()? {
+                if !compressed {
+                    res = res.and(self.compress_file(timestamp));
+                }
+            }
+        }
+
+        let mut files = self.list()?;
+
+        // NOTE: newest files first.
+        files.sort_unstable_by(|a, b| b.cmp(a));
+
+        let now = unix_millis(now);
+        let max_age = self.max_age.map(|max_age| max_age.as_millis() as u64);
+
+        for (i, (timestamp, compressed)) in files.into_iter().enumerate() {
+            let exceeds_max_files = self.max_files.is_some_and(|max_files| i >= max_files);
+
+            let exceeds_max_age =
+                max_age.is_some_and(|max_age| now.saturating_sub(timestamp) > max_age);
+
+            if exceeds_max_files || exceeds_max_age {
+                res = res.and(fs::remove_file(self.rotated_path(timestamp, compressed)));
+            }
+        }
+
+        res
+    }
+
+    /// Lists timestamps of the rotated files along with their compression status.
+    fn list(&self) -> io::Result<Vec<(u64, bool)>> {
+        let mut prefix = self.file_name.clone();
+
+        prefix.push(".");
+
+        let prefix = prefix.to_string_lossy().into_owned();
+        let mut files = vec![];
+
+        for entry in fs::read_dir(&self.dir)? {
+            let file_name = entry?.file_name();
+
+            let Some(suffix) = file_name
+                .to_str()
+                .and_then(|file_name| file_name.strip_prefix(&prefix))
+            else {
+                continue;
+            };
+
+            let (timestamp, compressed) = match suffix.strip_suffix(&format!(".{GZ_EXTENSION}")) {
+                Some(timestamp) => (timestamp, true),
+                None => (suffix, false),
+            };
+
+            if let Ok(timestamp) = timestamp.parse() {
+                files.push((timestamp, compressed));
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn compress_file(&self, timestamp: u64) -> io::Result<()> {
+        let path = self.rotated_path(timestamp, false);
+        let compressed_path = self.rotated_path(timestamp, true);
+
+        if let Err(err) = gzip(&path, &compressed_path) {
+            // NOTE: remove the partially written file, so it's not mistaken for a compressed one.
+            let _ = fs::remove_file(&compressed_path);
+
+            return Err(err);
+        }
+
+        fs::remove_file(path)
+    }
+}
+
+fn gzip(path: &Path, compressed_path: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(compressed_path)?, Compression::default());
+
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
+
+fn open_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn write_record(writer: &mut RotatingFileWriter, record: &str) {
+        writer.write_all(record.as_bytes()).unwrap();
+        writer.flush().unwrap();
+    }
+
+    fn read_rotated_files(rotated_files: &RotatedFiles) -> Vec<String> {
+        let mut files = rotated_files.list().unwrap();
+
+        files.sort_unstable();
+
+        files
+            .into_iter()
+            .map(|(timestamp, compressed)| {
+                let file = File::open(rotated_files.rotated_path(timestamp, compressed)).unwrap();
+                let mut contents = String::new();
+
+                if compressed {
+                    GzDecoder::new(file).read_to_string(&mut contents).unwrap();
+                } else {
+                    (&file).read_to_string(&mut contents).unwrap();
+                }
+
+                contents
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotate_by_size() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("service.log");
+
+        let settings = LogRotationSettings {
+            max_size_bytes: Some(10),
+            ..Default::default()
+        };
+
+        let mut writer = RotatingFileWriter::new(path.clone(), &settings).unwrap();
+
+        write_record(&mut writer, "first\n");
+        write_record(&mut writer, "second\n");
+        write_record(&mut writer, "third\n");
+
+        // NOTE: the record is not split between the files even if it exceeds the size limit.
+        writer.write_all(b"fourth").unwrap();
+        writer.write_all(b" record\n").unwrap();
+        writer.flush().unwrap();
+
+        write_record(&mut writer, "fifth\n");
+
+        assert_eq!(
+            read_rotated_files(&writer.rotated_files),
+            ["first\nsecond\n", "third\nfourth record\n"]
+        );
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fifth\n");
+    }
+
+    #[test]
+    fn rotate_by_time() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("service.log");
+
+        let mut writer = RotatingFileWriter::new(path.clone(), &Default::default()).unwrap();
+
+        writer.interval = Some(Duration::from_millis(100));
+
+        write_record(&mut writer, "first\n");
+        write_record(&mut writer, "second\n");
+
+        thread::sleep(Duration::from_millis(150));
+
+        write_record(&mut writer, "third\n");
+
+        assert_eq!(
+            read_rotated_files(&writer.rotated_files),
+            ["first\nsecond\n"]
+        );
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+    }
+
+    #[test]
+    fn append_to_existing_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("service.log");
+
+        fs::write(&path, "existing\n").unwrap();
+
+        let settings = LogRotationSettings {
+            max_size_bytes: Some(10),
+            ..Default::default()
+        };
+
+        let mut writer = RotatingFileWriter::new(path.clone(), &settings).unwrap();
+
+        write_record(&mut writer, "first\n");
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "existing\nfirst\n");
+    }
+
+    #[test]
+    fn compress_and_retain_max_files() {
+        let dir = TempDir::new().unwrap();
+
+        let settings = LogRotationSettings {
+            compress: true,
+            max_files: Some(2),
+            ..Default::default()
+        };
+
+        let rotated_files = RotatedFiles::new(&dir.path().join("service.log"), &settings).unwrap();
+
+        for timestamp in [1000, 2000, 3000] {
+            fs::write(
+                rotated_files.rotated_path(timestamp, false),
+                format!("{timestamp}\n"),
+            )
+            .unwrap();
+        }
+
+        // NOTE: files of other logs should be left intact.
+        fs::write(dir.path().join("service.log.old"), "").unwrap();
+        fs::write(dir.path().join("other.log.1000"), "").unwrap();
+
+        rotated_files.process(SystemTime::now()).unwrap();
+
+        let mut files = rotated_files.list().unwrap();
+
+        files.sort_unstable();
+
+        assert_eq!(files, [(2000, true), (3000, true)]);
+        assert_eq!(read_rotated_files(&rotated_files), ["2000\n", "3000\n"]);
+        assert!(dir.path().join("service.log.old").exists());
+        assert!(dir.path().join("other.log.1000").exists());
+    }
+
+    #[test]
+    fn retain_max_files_on_compression_failure() {
+        let dir = TempDir::new().unwrap();
+
+        let settings = LogRotationSettings {
+            compress: true,
+            max_files: Some(2),
+            ..Default::default()
+        };
+
+        let rotated_files = RotatedFiles::new(&dir.path().join("service.log"), &settings).unwrap();
+
+        for timestamp in [1000, 2000, 3000] {
+            fs::write(
+                rotated_files.rotated_path(timestamp, false),
+                format!("{timestamp}\n"),
+            )
+            .unwrap();
+        }
+
+        // NOTE: a directory can be opened, but not read, so its compression fails.
+        fs::create_dir(rotated_files.rotated_path(4000, false)).unwrap();
+
+        assert!(rotated_files.process(SystemTime::now()).is_err());
+
+        let mut files = rotated_files.list().unwrap();
+
+        files.sort_unstable();
+
+        assert_eq!(files, [(3000, true), (4000, false)]);
+    }
+
+    #[test]
+    fn remove_expired_files() {
+        let dir = TempDir::new().unwrap();
+
+        let settings = LogRotationSettings {
+            max_age_seconds: Some(60),
+            ..Default::default()
+        };
+
+        let rotated_files = RotatedFiles::new(&dir.path().join("service.log"), &settings).unwrap();
+        let now = SystemTime::now();
+        let expired = unix_millis(now - Duration::from_secs(120));
+        let recent = unix_millis(now - Duration::from_secs(30));
+
+        fs::write(rotated_files.rotated_path(expired, true), "").unwrap();
+        fs::write(rotated_files.rotated_path(recent, false), "").unwrap();
+
+        rotated_files.process(now).unwrap();
+
+        assert_eq!(rotated_files.list().unwrap(), [(recent, false)]);
+    }
+}
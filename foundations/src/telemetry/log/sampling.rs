@@ -0,0 +1,115 @@
+use crate::telemetry::settings::LogSamplingSettings;
+use rand::RngExt as _;
+use slog::{BorrowedKV, Drain, Level, OwnedKVList, Record, RecordStatic, SingleKV};
+
+#[cfg(feature = "tracing")]
+use crate::telemetry::tracing::internal::current_span;
+
+#[cfg(feature = "metrics")]
+use crate::telemetry::metrics::Counter;
+
+#[cfg(feature = "metrics")]
+#[crate::telemetry::metrics::metrics(crate_path = "crate")]
+mod foundations {
+    /// The number of log records dropped by the sampling.
+    pub fn log_records_sampled_out(level: &'static str) -> Counter;
+}
+
+/// A drain that keeps the log records with the probability specified for their level.
+///
+/// Must be called on the thread that emits the record, as the current span is tracked per
+/// thread.
+pub(crate) struct SamplingDrain<D> {
+    inner: D,
+    // NOTE: indexed by `Level::as_usize() - 1`.
+    rates: [Option<f64>; 6],
+}
+
+impl<D: Drain> SamplingDrain<D> {
+    pub(crate) fn new(inner: D, settings: &LogSamplingSettings) -> Self {
+        let rates = &settings.level_sample_rates;
+
+        let rates = if settings.enabled {
+            [
+                rates.critical,
+                rates.error,
+                rates.warning,
+                rates.info,
+                rates.debug,
+                rates.trace,
+            ]
+            .map(|rate| rate.filter(|&rate| rate < 1.0))
+        } else {
+            [None; 6]
+        };
+
+        Self { inner, rates }
+    }
+}
+
+impl<D: Drain> Drain for SamplingDrain<D> {
+    type Ok = ();
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let Some(rate) = self.rates[record.level().as_usize() - 1] else {
+            return self.inner.log(record, values).map(|_| ());
+        };
+
+        if !is_sampled(rate) {
+            count_sampled_out(record.level());
+            return Ok(());
+        }
+
+        let record_kv = (record.kv(), SingleKV("sample_rate", rate));
+
+        let record_static = RecordStatic {
+            location: record.location(),
+            tag: record.tag(),
+            level: record.level(),
+        };
+
+        let record = Record::new(&record_static, record.msg(), BorrowedKV(&record_kv));
+
+        self.inner.log(&record, values).map(|_| ())
+    }
+
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        Drain::is_enabled(&self.inner, level)
+    }
+
+    #[inline]
+    fn flush(&self) -> Result<(), slog::FlushError> {
+        Drain::flush(&self.inner)
+    }
+}
+
+fn is_sampled(rate: f64) -> bool {
+    if rate <= 0.0 {
+        return false;
+    }
+
+    // NOTE: trace IDs are random, so comparing them with the threshold keeps the same share of
+    // the traces as the random sampling, while making the same decision for all the records of
+    // a trace, including the ones emitted by other services with the same rate.
+    #[cfg(feature = "tracing")]
+    if let Some(trace_id) = current_trace_id() {
+        return (trace_id as f64) < rate * u64::MAX as f64;
+    }
+
+    rand::rng().random_range(0.0..1.0) < rate
+}
+
+/// Returns the low 64 bits of the current trace ID, if there is a span with a trace context.
+#[cfg(feature = "tracing")]
+fn current_trace_id() -> Option<u64> {
+    current_span()?
+        .inner
+        .with_read(|span| Some(span.context()?.state().trace_id().low))
+}
+
+fn count_sampled_out(_level: Level) {
+    #[cfg(feature = "metrics")]
+    foundations::log_records_sampled_out(_level.as_str()).inc();
+}
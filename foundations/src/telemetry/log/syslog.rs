@@ -0,0 +1,445 @@
+use super::fields::{StringFields, collect_fields};
+use crate::ServiceInfo;
+use crate::telemetry::settings::{SyslogAddr, SyslogFormat, SyslogOutputSettings};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use slog::{Drain, Level, OwnedKVList, Record};
+use std::fmt::{self, Write as _};
+use std::io;
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixDatagram, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+
+const DEFAULT_STRUCTURED_DATA_ID: &str = "fields@32473";
+const NIL: &str = "-";
+
+// https://datatracker.ietf.org/doc/html/rfc5424#section-6
+const MAX_HOSTNAME_LEN: usize = 255;
+const MAX_APP_NAME_LEN: usize = 48;
+const MAX_PARAM_NAME_LEN: usize = 32;
+
+/// A drain that writes log records to syslog.
+pub(crate) struct SyslogDrain {
+    format: SyslogFormat,
+    facility: u8,
+    hostname: String,
+    app_name: String,
+    pid: u32,
+    structured_data_id: String,
+    transport: Mutex<Transport>,
+}
+
+impl SyslogDrain {
+    pub(crate) fn new(
+        service_info: &ServiceInfo,
+        settings: &SyslogOutputSettings,
+    ) -> io::Result<Self> {
+        let app_name = settings.app_name.as_deref().unwrap_or(service_info.name);
+
+        Ok(Self {
+            format: settings.format,
+            facility: settings.facility.code(),
+            hostname: header_field(&hostname(), MAX_HOSTNAME_LEN),
+            app_name: header_field(app_name, MAX_APP_NAME_LEN),
+            pid: std::process::id(),
+            structured_data_id: settings
+                .structured_data_id
+                .clone()
+                .unwrap_or_else(|| DEFAULT_STRUCTURED_DATA_ID.into()),
+            transport: Mutex::new(Transport::connect(&settings.addr)?),
+        })
+    }
+
+    fn format_message(
+        &self,
+        record: &Record,
+        values: &OwnedKVList,
+        now: DateTime<Utc>,
+    ) -> Result<String, slog::Error> {
+        let fields = collect_fields::<StringFields>(record, values)?;
+
+        let pri = self.facility * 8 + severity(record.level());
+        let mut msg = format!("<{pri}>");
+
+        match self.format {
+            SyslogFormat::Rfc5424 => {
+                write!(
+                    msg,
+                    "1 {} {} {} {} {NIL} ",
+                    now.to_rfc3339_opts(SecondsFormat::Micros, true),
+                    self.hostname,
+                    self.app_name,
+                    self.pid,
+                )?;
+
+                write_structured_data(&mut msg, &self.structured_data_id, &fields)?;
+                write!(msg, " {}", record.msg())?;
+            }
+            SyslogFormat::Rfc3164 => {
+                write!(
+                    msg,
+                    "{} {} {}[{}]: {}",
+                    now.with_timezone(&Local).format("%b %e %H:%M:%S"),
+                    self.hostname,
+                    self.app_name,
+                    self.pid,
+                    record.msg()
+                )?;
+
+                for (key, value) in &fields {
+                    write!(msg, " {key}=")?;
+                    write_text_value(&mut msg, value)?;
+                }
+            }
+        }
+
+        Ok(msg)
+    }
+}
+
+impl Drain for SyslogDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let msg = self.format_message(record, values, Utc::now())?;
+
+        self.transport
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(self.format, msg.as_bytes())
+    }
+}
+
+/// Maps log levels to syslog severities.
+pub(super) fn severity(level: Level) -> u8 {
+    match level {
+        Level::Critical => 2,
+        Level::Error => 3,
+        Level::Warning => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+enum Transport {
+    #[cfg(unix)]
+    UnixDatagram(PathBuf, UnixDatagram),
+    #[cfg(unix)]
+    UnixStream(PathBuf, Option<UnixStream>),
+    Udp(UdpSocket),
+    Tcp(SocketAddr, Option<TcpStream>),
+}
+
+impl Transport {
+    fn connect(addr: &SyslogAddr) -> io::Result<Self> {
+        match addr {
+            #[cfg(unix)]
+            SyslogAddr::Unix(path) => match connect_unix_datagram(path) {
+                Ok(socket) => Ok(Self::UnixDatagram(path.clone(), socket)),
+                Err(err) if err.raw_os_error() == Some(libc::EPROTOTYPE) => Ok(Self::UnixStream(
+                    path.clone(),
+                    Some(UnixStream::connect(path)?),
+                )),
+                Err(err) => Err(err),
+            },
+            #[cfg(not(unix))]
+            SyslogAddr::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix socket syslog addresses are not supported on this platform",
+            )),
+            SyslogAddr::Udp(addr) => {
+                let local_addr: SocketAddr = if addr.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+
+                let socket = UdpSocket::bind(local_addr)?;
+
+                socket.connect(addr)?;
+
+                Ok(Self::Udp(socket))
+            }
+            SyslogAddr::Tcp(addr) => Ok(Self::Tcp(*addr, Some(TcpStream::connect(addr)?))),
+        }
+    }
+
+    /// Sends the message, reconnecting once if the connection has been lost, e.g. due to the
+    /// syslog server restart.
+    fn send(&mut self, format: SyslogFormat, msg: &[u8]) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::UnixDatagram(path, socket) => {
+                if socket.send(msg).is_err() {
+                    *socket = connect_unix_datagram(path)?;
+                    socket.send(msg)?;
+                }
+
+                Ok(())
+            }
+            #[cfg(unix)]
+            Self::UnixStream(path, stream) => {
+                send_to_stream(stream, || UnixStream::connect(&*path), format, msg)
+            }
+            Self::Udp(socket) => socket.send(msg).map(|_| ()),
+            Self::Tcp(addr, stream) => {
+                send_to_stream(stream, || TcpStream::connect(*addr), format, msg)
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn connect_unix_datagram(path: &Path) -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+
+    socket.connect(path)?;
+
+    Ok(socket)
+}
+
+fn send_to_stream<S: io::Write>(
+    stream: &mut Option<S>,
+    connect: impl Fn() -> io::Result<S>,
+    format: SyslogFormat,
+    msg: &[u8],
+) -> io::Result<()> {
+    // NOTE: RFC 5424 messages can contain new lines, so use octet counting framing for them.
+    let frame = match format {
+        SyslogFormat::Rfc5424 => [format!("{} ", msg.len()).as_bytes(), msg].concat(),
+        SyslogFormat::Rfc3164 => [msg, b"\n"].concat(),
+    };
+
+    if let Some(conn) = stream
+        && conn.write_all(&frame).is_ok()
+    {
+        return Ok(());
+    }
+
+    // NOTE: drop the broken connection, so we try to reconnect on the next message if the
+    // reconnect fails.
+    *stream = None;
+
+    let mut conn = connect()?;
+
+    conn.write_all(&frame)?;
+    *stream = Some(conn);
+
+    Ok(())
+}
+
+/// Writes the structured data element with the fields as the parameters.
+fn write_structured_data(msg: &mut String, id: &str, fields: &[(String, String)]) -> fmt::Result {
+    if fields.is_empty() {
+        return msg.write_str(NIL);
+    }
+
+    write!(msg, "[{id}")?;
+
+    for (key, value) in fields {
+        let name: String = key
+            .chars()
+            .map(|c| match c {
+                '=' | ']' | '"' => '_',
+                c if c.is_ascii_graphic() => c,
+                _ => '_',
+            })
+            .take(MAX_PARAM_NAME_LEN)
+            .collect();
+
+        write!(msg, " {name}=\"")?;
+
+        for c in value.chars() {
+            if matches!(c, '"' | '\\' | ']') {
+                msg.write_char('\\')?;
+            }
+
+            msg.write_char(c)?;
+        }
+
+        msg.write_char('"')?;
+    }
+
+    msg.write_char(']')
+}
+
+/// Writes the value quoting it if it contains whitespace or quotes.
+fn write_text_value(msg: &mut String, value: &str) -> fmt::Result {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"') {
+        return msg.write_str(value);
+    }
+
+    write!(msg, "{value:?}")
+}
+
+/// Converts the value to a header field which can only contain printable ASCII characters.
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value
+        .chars()
+        .filter(char::is_ascii_graphic)
+        .take(max_len)
+        .collect();
+
+    if field.is_empty() { NIL.into() } else { field }
+}
+
+#[cfg(unix)]
+pub(super) fn hostname() -> String {
+    let mut buf = [0u8; MAX_HOSTNAME_LEN + 1];
+
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return String::new();
+    }
+
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(not(unix))]
+pub(super) fn hostname() -> String {
+    String::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::settings::SyslogFacility;
+    use slog::{Logger, o};
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn syslog_udp_server() -> (UdpSocket, SyslogAddr) {
+        let server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let addr = SyslogAddr::Udp(server.local_addr().unwrap());
+
+        (server, addr)
+    }
+
+    fn recv_message(server: &UdpSocket) -> String {
+        let mut buf = [0; 4096];
+        let len = server.recv(&mut buf).unwrap();
+
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn rfc5424_message() {
+        let (server, addr) = syslog_udp_server();
+
+        let settings = SyslogOutputSettings {
+            addr,
+            facility: SyslogFacility::Local3,
+            app_name: Some("my app".into()),
+            ..Default::default()
+        };
+
+        let drain = SyslogDrain::new(&crate::service_info!(), &settings).unwrap();
+        let log = Logger::root(drain.fuse(), o!("version" => "1.0.0"));
+
+        slog::warn!(log, "Hello syslog"; "quote" => "a \"b\" [c]", "with space" => 42);
+
+        let msg = recv_message(&server);
+        let (timestamp, rest) = msg
+            .strip_prefix("<156>1 ")
+            .unwrap()
+            .split_once(' ')
+            .unwrap();
+
+        assert!(DateTime::parse_from_rfc3339(timestamp).is_ok(), "{msg}");
+
+        let (_hostname, rest) = rest.split_once(' ').unwrap();
+
+        assert_eq!(
+            rest,
+            format!(
+                "myapp {} - [fields@32473 quote=\"a \\\"b\\\" [c\\]\" with_space=\"42\" \
+                 version=\"1.0.0\"] Hello syslog",
+                std::process::id()
+            )
+        );
+    }
+
+    #[test]
+    fn rfc3164_message() {
+        let (server, addr) = syslog_udp_server();
+
+        let settings = SyslogOutputSettings {
+            addr,
+            format: SyslogFormat::Rfc3164,
+            ..Default::default()
+        };
+
+        let drain = SyslogDrain::new(&crate::service_info!(), &settings).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+
+        slog::error!(log, "Hello syslog"; "key" => "value", "spaced" => "two words");
+
+        let msg = recv_message(&server);
+
+        assert!(msg.starts_with("<11>"), "{msg}");
+
+        assert!(
+            msg.ends_with(&format!(
+                " foundations[{}]: Hello syslog key=value spaced=\"two words\"",
+                std::process::id()
+            )),
+            "{msg}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_stream_socket() {
+        use std::io::Read;
+        use std::os::unix::net::UnixListener;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("syslog.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let settings = SyslogOutputSettings {
+            addr: SyslogAddr::Unix(path),
+            ..Default::default()
+        };
+
+        let drain = SyslogDrain::new(&crate::service_info!(), &settings).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+
+        slog::info!(log, "first");
+        slog::info!(log, "second");
+        drop(log);
+
+        let mut received = String::new();
+
+        listener
+            .accept()
+            .unwrap()
+            .0
+            .read_to_string(&mut received)
+            .unwrap();
+
+        let mut frames = vec![];
+        let mut rest = received.as_str();
+
+        while let Some((len, tail)) = rest.split_once(' ') {
+            let len: usize = len.parse().unwrap();
+
+            frames.push(&tail[..len]);
+            rest = &tail[len..];
+        }
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].starts_with("<14>1 ") && frames[0].ends_with(" - first"));
+        assert!(frames[1].ends_with(" - second"));
+    }
+}
@@ -0,0 +1,126 @@
+use super::field_filtering::FieldFilteringDrain;
+use super::field_redact::FieldRedactFilterFactory;
+use crate::telemetry::settings::LogOutputSettings;
+use slog::{Drain, Level, Never, OwnedKVList, Record, SendSyncRefUnwindSafeDrain};
+use std::sync::Arc;
+
+type OutputDrain = Arc<dyn SendSyncRefUnwindSafeDrain<Ok = (), Err = Never>>;
+
+/// A drain that writes the log records to the main output and to the additional outputs.
+pub(crate) struct TeeDrain<D> {
+    main: D,
+    outputs: Vec<(Option<Level>, OutputDrain)>,
+}
+
+impl<D> TeeDrain<D> {
+    pub(crate) fn new(main: D) -> Self {
+        Self {
+            main,
+            outputs: vec![],
+        }
+    }
+
+    /// Adds an output with the verbosity and the redaction rules from the settings.
+    pub(crate) fn add_output<O>(
+        &mut self,
+        drain: O,
+        settings: &LogOutputSettings,
+    ) -> crate::Result<()>
+    where
+        O: SendSyncRefUnwindSafeDrain<Ok = (), Err = Never> + 'static,
+    {
+        let drain = FieldFilteringDrain::new(
+            drain,
+            FieldRedactFilterFactory::new(settings.redact_keys.clone()),
+        );
+
+        #[cfg(feature = "log-redaction")]
+        let drain = super::redaction::RedactionDrain::new(drain, &settings.redaction)?;
+
+        self.outputs
+            .push((settings.verbosity.map(Level::from), Arc::new(drain)));
+
+        Ok(())
+    }
+}
+
+impl<D: Drain<Ok = ()>> Drain for TeeDrain<D> {
+    type Ok = ();
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let result = self.main.log(record, values);
+
+        for (level, output) in &self.outputs {
+            if level.is_none_or(|level| record.level().is_at_least(level)) {
+                let _ = output.log(record, values);
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::log::fields::{StringFields, collect_fields};
+    use crate::telemetry::settings::LogVerbosity;
+    use slog::{Logger, o};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct RecordingDrain(Arc<Mutex<Vec<(String, Vec<(String, String)>)>>>);
+
+    impl Drain for RecordingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+            let fields = collect_fields::<StringFields>(record, values).unwrap();
+
+            self.0
+                .lock()
+                .unwrap()
+                .push((record.msg().to_string(), fields));
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn outputs_with_own_verbosity_and_redaction() {
+        let main = RecordingDrain::default();
+        let output = RecordingDrain::default();
+        let mut drain = TeeDrain::new(main.clone());
+
+        drain
+            .add_output(
+                output.clone(),
+                &LogOutputSettings {
+                    verbosity: Some(LogVerbosity::Warning),
+                    redact_keys: vec!["secret".into()],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let log = Logger::root(drain.fuse(), o!("secret" => "hunter2"));
+
+        slog::info!(log, "info");
+        slog::warn!(log, "warn"; "key" => "value");
+
+        let secret = ("secret".to_string(), "hunter2".to_string());
+        let key = ("key".to_string(), "value".to_string());
+
+        assert_eq!(
+            *main.0.lock().unwrap(),
+            [
+                ("info".into(), vec![secret.clone()]),
+                ("warn".into(), vec![key.clone(), secret]),
+            ]
+        );
+
+        assert_eq!(*output.0.lock().unwrap(), [("warn".into(), vec![key])]);
+    }
+}
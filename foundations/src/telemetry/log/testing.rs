@@ -1,11 +1,15 @@
+use crate::telemetry::log::flight_recorder::FlightRecorder;
+use crate::telemetry::log::glob::glob_to_regex;
 use crate::telemetry::log::init::{LogHarness, build_log_with_drain, wrap_root_drain};
 use crate::telemetry::log::internal::LoggerWithKvNestingTracking;
+use crate::telemetry::log::verbosity::VerbosityDirectives;
 use crate::telemetry::settings::LoggingSettings;
 use parking_lot::RwLock as ParkingRwLock;
+use regex::Regex;
 use slog::{
     Drain, KV, Key, Level, Never, OwnedKVList, Record, SendSyncRefUnwindSafeDrain, Serializer,
 };
-use std::fmt::Arguments;
+use std::fmt::{self, Arguments, Display, Write as _};
 use std::sync::{Arc, RwLock};
 
 pub(crate) type TestLogRecords = Arc<RwLock<Vec<TestLogRecord>>>;
@@ -25,6 +29,320 @@ pub struct TestLogRecord {
     pub fields: Vec<(String, String)>,
 }
 
+impl Display for TestLogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:?}", self.level.as_str(), self.message)?;
+
+        for (i, (key, value)) in self.fields.iter().enumerate() {
+            let sep = if i == 0 { " {" } else { ", " };
+
+            write!(f, "{sep}{key}={value:?}")?;
+        }
+
+        if !self.fields.is_empty() {
+            f.write_char('}')?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A matcher of the [`TestLogRecord`]s for the log assertions of the [test telemetry context].
+///
+/// A record matches if it satisfies all the specified criteria. The record fields that are not
+/// mentioned in the matcher are ignored.
+///
+/// # Examples
+/// ```
+/// use foundations::telemetry::TelemetryContext;
+/// use foundations::telemetry::log::{self, LogMatcher};
+/// use foundations::telemetry::settings::Level;
+///
+/// let ctx = TelemetryContext::test();
+/// let _scope = ctx.scope();
+///
+/// log::info!("connecting to {}", "db");
+/// log::warn!("failed to connect to {}", "db"; "attempt" => 2, "error" => "timed out");
+///
+/// ctx.assert_logged(
+///     LogMatcher::new()
+///         .level(Level::Warning)
+///         .message_glob("failed to connect to *")
+///         .field("attempt", 2)
+///         .has_field("error"),
+/// );
+///
+/// ctx.assert_not_logged(LogMatcher::new().level(Level::Error));
+///
+/// ctx.assert_logged_in_order([
+///     LogMatcher::new().message("connecting to db"),
+///     LogMatcher::new().message_regex("^failed .* db$"),
+/// ]);
+/// ```
+///
+/// [test telemetry context]: crate::telemetry::TestTelemetryContext
+#[derive(Clone, Debug, Default)]
+pub struct LogMatcher {
+    level: Option<Level>,
+    message: Option<MessagePattern>,
+    fields: Vec<(String, Option<String>)>,
+}
+
+#[derive(Clone, Debug)]
+enum MessagePattern {
+    Exact(String),
+    Glob(String, Regex),
+    Regex(Regex),
+}
+
+impl MessagePattern {
+    fn matches(&self, message: &str) -> bool {
+        match self {
+            Self::Exact(expected) => message == expected,
+            Self::Glob(_, regex) | Self::Regex(regex) => regex.is_match(message),
+        }
+    }
+}
+
+impl Display for MessagePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(message) => write!(f, "{message:?}"),
+            Self::Glob(glob, _) => write!(f, "glob {glob:?}"),
+            Self::Regex(regex) => write!(f, "regex {:?}", regex.as_str()),
+        }
+    }
+}
+
+impl LogMatcher {
+    /// Creates a matcher that matches any record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires the record to have the level.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Requires the record to have the message.
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(MessagePattern::Exact(message.into()));
+        self
+    }
+
+    /// Requires the record message to match the glob pattern.
+    ///
+    /// `*` matches any sequence of characters and `?` matches any single character.
+    pub fn message_glob(mut self, glob: impl Into<String>) -> Self {
+        let glob = glob.into();
+        let regex = Regex::new(&glob_to_regex(&glob)).expect("glob regex should be valid");
+
+        self.message = Some(MessagePattern::Glob(glob, regex));
+        self
+    }
+
+    /// Requires the record message to match the [regular expression].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the regular expression is invalid.
+    ///
+    /// [regular expression]: https://docs.rs/regex/latest/regex/#syntax
+    pub fn message_regex(mut self, regex: &str) -> Self {
+        let regex = Regex::new(regex).unwrap_or_else(|err| panic!("invalid message regex: {err}"));
+
+        self.message = Some(MessagePattern::Regex(regex));
+        self
+    }
+
+    /// Requires the record to have the field with the value.
+    pub fn field(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.fields.push((key.into(), Some(value.to_string())));
+        self
+    }
+
+    /// Requires the record to have the field with any value.
+    pub fn has_field(mut self, key: impl Into<String>) -> Self {
+        self.fields.push((key.into(), None));
+        self
+    }
+
+    /// Returns `true` if the record satisfies all the criteria of the matcher.
+    pub fn matches(&self, record: &TestLogRecord) -> bool {
+        self.mismatches(record).is_empty()
+    }
+
+    /// Describes the criteria that the record doesn't satisfy.
+    fn mismatches(&self, record: &TestLogRecord) -> Vec<String> {
+        let mut mismatches = vec![];
+
+        if let Some(level) = self.level
+            && level != record.level
+        {
+            mismatches.push(format!(
+                "level: expected {}, got {}",
+                level.as_str(),
+                record.level.as_str()
+            ));
+        }
+
+        if let Some(message) = &self.message
+            && !message.matches(&record.message)
+        {
+            mismatches.push(format!(
+                "message: expected {message}, got {:?}",
+                record.message
+            ));
+        }
+
+        for (key, expected) in &self.fields {
+            let values: Vec<_> = record
+                .fields
+                .iter()
+                .filter(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .collect();
+
+            match expected {
+                _ if values.is_empty() => mismatches.push(format!("field `{key}`: missing")),
+                Some(expected) if !values.contains(&expected) => mismatches.push(format!(
+                    "field `{key}`: expected {expected:?}, got {:?}",
+                    values[0]
+                )),
+                _ => (),
+            }
+        }
+
+        mismatches
+    }
+}
+
+impl Display for LogMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut criteria = vec![];
+
+        if let Some(level) = self.level {
+            criteria.push(format!("level {}", level.as_str()));
+        }
+
+        if let Some(message) = &self.message {
+            criteria.push(format!("message {message}"));
+        }
+
+        for (key, value) in &self.fields {
+            match value {
+                Some(value) => criteria.push(format!("field {key}={value:?}")),
+                None => criteria.push(format!("field {key}")),
+            }
+        }
+
+        if criteria.is_empty() {
+            return f.write_str("any record");
+        }
+
+        f.write_str(&criteria.join(", "))
+    }
+}
+
+/// Panics if no record matches. Returns the first matching record.
+#[track_caller]
+pub(crate) fn assert_logged(records: &[TestLogRecord], matcher: &LogMatcher) -> TestLogRecord {
+    match records.iter().find(|record| matcher.matches(record)) {
+        Some(record) => record.clone(),
+        None => panic!(
+            "{}",
+            failure_report(
+                records,
+                0,
+                &format!("no log record matches: {matcher}"),
+                matcher
+            )
+        ),
+    }
+}
+
+/// Panics if any record matches.
+#[track_caller]
+pub(crate) fn assert_not_logged(records: &[TestLogRecord], matcher: &LogMatcher) {
+    if let Some(idx) = records.iter().position(|record| matcher.matches(record)) {
+        panic!(
+            "log record #{idx} unexpectedly matches: {matcher}\n  {}",
+            records[idx]
+        );
+    }
+}
+
+/// Panics if the matchers don't match the records in the order they are specified. Other records
+/// can be present between the matching ones.
+#[track_caller]
+pub(crate) fn assert_logged_in_order(
+    records: &[TestLogRecord],
+    matchers: impl IntoIterator<Item = LogMatcher>,
+) {
+    let mut start = 0;
+
+    for (n, matcher) in matchers.into_iter().enumerate() {
+        match records[start..]
+            .iter()
+            .position(|record| matcher.matches(record))
+        {
+            Some(idx) => start += idx + 1,
+            None => panic!(
+                "{}",
+                failure_report(
+                    records,
+                    start,
+                    &format!(
+                        "no log record from #{start} matches the matcher #{n} in the order: \
+                         {matcher}"
+                    ),
+                    &matcher
+                )
+            ),
+        }
+    }
+}
+
+/// Describes the assertion failure with the closest record to the matcher, starting from the
+/// `start` record, and all the records.
+fn failure_report(
+    records: &[TestLogRecord],
+    start: usize,
+    summary: &str,
+    matcher: &LogMatcher,
+) -> String {
+    let mut report = summary.to_string();
+
+    let closest = records
+        .iter()
+        .enumerate()
+        .skip(start)
+        .map(|(idx, record)| (idx, record, matcher.mismatches(record)))
+        .min_by_key(|(_, _, mismatches)| mismatches.len());
+
+    if let Some((idx, record, mismatches)) = closest {
+        let _ = write!(report, "\n\nclosest log record #{idx}: {record}");
+
+        for mismatch in mismatches {
+            let _ = write!(report, "\n  {mismatch}");
+        }
+    }
+
+    let _ = write!(report, "\n\nlog records:");
+
+    if records.is_empty() {
+        report.push_str(" none");
+    }
+
+    for (idx, record) in records.iter().enumerate() {
+        let _ = write!(report, "\n  #{idx} {record}");
+    }
+
+    report
+}
+
 #[derive(Default)]
 struct TestFieldSerializer {
     fields: Vec<(String, String)>,
@@ -84,9 +402,17 @@ where
         records: Arc::clone(&log_records),
         forward,
     };
-    let drain = wrap_root_drain(settings, drain);
+    let drain = wrap_root_drain(settings, drain).expect("invalid log redaction settings");
 
-    let logger = build_log_with_drain(settings.verbosity, slog::o!(), Arc::clone(&drain));
+    let verbosity =
+        VerbosityDirectives::from_settings(settings).expect("invalid log verbosity directives");
+    let flight_recorder = FlightRecorder::new(&settings.flight_recorder, Arc::clone(&drain));
+    let logger = build_log_with_drain(
+        verbosity,
+        flight_recorder.clone(),
+        slog::o!(),
+        Arc::clone(&drain),
+    );
     let log = LoggerWithKvNestingTracking::new(logger);
 
     let _ = LogHarness::override_for_testing(LogHarness {
@@ -94,6 +420,7 @@ where
         root_drain: drain,
         settings: settings.clone(),
         log_scope_stack: Default::default(),
+        flight_recorder,
     });
 
     (log, log_records)
@@ -0,0 +1,93 @@
+use crate::telemetry::settings::LogTraceCorrelationSettings;
+use crate::telemetry::tracing::internal::current_span;
+use slog::{BorrowedKV, Drain, KV, OwnedKVList, Record, RecordStatic, Serializer};
+
+/// Trace and span IDs of the current span in the W3C Trace Context hex format.
+struct TraceIds {
+    trace_id: String,
+    span_id: String,
+}
+
+impl TraceIds {
+    fn current(include_unsampled: bool) -> Option<Self> {
+        current_span()?.inner.with_read(|span| {
+            let state = span.context()?.state();
+
+            if !state.is_sampled() && !include_unsampled {
+                return None;
+            }
+
+            Some(Self {
+                trace_id: format!(
+                    "{:016x}{:016x}",
+                    state.trace_id().high,
+                    state.trace_id().low
+                ),
+                span_id: format!("{:016x}", state.span_id()),
+            })
+        })
+    }
+}
+
+impl KV for TraceIds {
+    fn serialize(&self, _record: &Record, serializer: &mut dyn Serializer) -> slog::Result {
+        serializer.emit_str("trace_id", &self.trace_id)?;
+        serializer.emit_str("span_id", &self.span_id)
+    }
+}
+
+/// A drain that adds the IDs of the current span to the log records.
+///
+/// Must be called on the thread that emits the record, as the current span is tracked per
+/// thread.
+pub(crate) struct TraceCorrelationDrain<D> {
+    inner: D,
+    settings: LogTraceCorrelationSettings,
+}
+
+impl<D> TraceCorrelationDrain<D> {
+    pub(crate) fn new(inner: D, settings: &LogTraceCorrelationSettings) -> Self {
+        Self {
+            inner,
+            settings: settings.clone(),
+        }
+    }
+}
+
+impl<D: Drain> Drain for TraceCorrelationDrain<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        if !self.settings.enabled {
+            return self.inner.log(record, values);
+        }
+
+        let Some(trace_ids) = TraceIds::current(self.settings.include_unsampled) else {
+            return self.inner.log(record, values);
+        };
+
+        // NOTE: the IDs are serialized after the record fields, but before the logger fields.
+        let record_kv = (record.kv(), trace_ids);
+
+        let record_static = RecordStatic {
+            location: record.location(),
+            tag: record.tag(),
+            level: record.level(),
+        };
+
+        let record = Record::new(&record_static, record.msg(), BorrowedKV(&record_kv));
+
+        self.inner.log(&record, values)
+    }
+
+    #[inline]
+    fn is_enabled(&self, level: slog::Level) -> bool {
+        Drain::is_enabled(&self.inner, level)
+    }
+
+    #[inline]
+    fn flush(&self) -> Result<(), slog::FlushError> {
+        Drain::flush(&self.inner)
+    }
+}
@@ -0,0 +1,287 @@
+use super::flight_recorder::FlightRecorder;
+use crate::Error;
+use crate::telemetry::settings::{LogVerbosity, LoggingSettings};
+use slog::{Drain, Level, OwnedKVList, Record, RecordLocation};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use thread_local::ThreadLocal;
+
+/// Log verbosity with optional per-module overrides.
+///
+/// Can be parsed from [`RUST_LOG`]-style directives, i.e. a comma-separated list of
+/// `module::path=level` entries and optionally a single `level` entry for the rest of the modules.
+/// A module entry applies to the module and all its submodules, and the most specific entry
+/// is used if multiple entries match. An entry without a level enables all the levels for the
+/// module.
+///
+/// # Examples
+/// ```
+/// use foundations::telemetry::log::VerbosityDirectives;
+/// use foundations::telemetry::settings::LogVerbosity;
+///
+/// let directives: VerbosityDirectives = "warn,my_service::proxy=debug,hyper=error"
+///     .parse()
+///     .unwrap();
+///
+/// assert_eq!(directives.default_verbosity(), LogVerbosity::Warning);
+/// ```
+///
+/// [`RUST_LOG`]: https://docs.rs/env_logger/latest/env_logger/#enabling-logging
+#[derive(Clone, Debug)]
+pub struct VerbosityDirectives {
+    default: Option<LogVerbosity>,
+    // NOTE: sorted by the module path length in the descending order, so the first matching
+    // entry is the most specific one.
+    modules: Vec<(String, LogVerbosity)>,
+}
+
+impl VerbosityDirectives {
+    /// Creates the directives from the [`LoggingSettings::verbosity`] and
+    /// [`LoggingSettings::verbosity_directives`].
+    pub(crate) fn from_settings(settings: &LoggingSettings) -> crate::Result<Self> {
+        let directives: Self = settings.verbosity_directives.parse()?;
+
+        Ok(directives.or_default_verbosity(settings.verbosity))
+    }
+
+    /// Sets the verbosity of the modules that don't have their own directive, unless it's
+    /// already specified in the directives.
+    pub(crate) fn or_default_verbosity(mut self, verbosity: LogVerbosity) -> Self {
+        self.default.get_or_insert(verbosity);
+        self
+    }
+
+    /// Returns the verbosity of the modules that don't have their own directive.
+    ///
+    /// Returns [`LogVerbosity::Info`] if it's not specified in the directives.
+    pub fn default_verbosity(&self) -> LogVerbosity {
+        self.default.unwrap_or_default()
+    }
+
+    /// Returns the verbosity of the module.
+    pub fn module_verbosity(&self, module: &str) -> LogVerbosity {
+        self.modules
+            .iter()
+            .find(|(path, _)| {
+                module
+                    .strip_prefix(path.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map(|(_, verbosity)| *verbosity)
+            .unwrap_or_else(|| self.default_verbosity())
+    }
+
+    /// Returns the most verbose level enabled for any of the modules.
+    fn max_level(&self) -> Level {
+        self.modules
+            .iter()
+            .map(|(_, verbosity)| Level::from(*verbosity))
+            .fold(self.default_verbosity().into(), most_verbose)
+    }
+}
+
+impl From<LogVerbosity> for VerbosityDirectives {
+    fn from(verbosity: LogVerbosity) -> Self {
+        Self {
+            default: Some(verbosity),
+            modules: vec![],
+        }
+    }
+}
+
+impl FromStr for VerbosityDirectives {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut directives = Self {
+            default: None,
+            modules: vec![],
+        };
+
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((module, level)) => {
+                    let module = module.trim();
+
+                    if module.is_empty() {
+                        return Err(format!("missing module path in `{entry}`").into());
+                    }
+
+                    directives
+                        .modules
+                        .push((module.into(), parse_verbosity(level.trim())?));
+                }
+                None => match parse_verbosity(entry) {
+                    Ok(verbosity) => directives.default = Some(verbosity),
+                    Err(_) => directives.modules.push((entry.into(), LogVerbosity::Trace)),
+                },
+            }
+        }
+
+        directives
+            .modules
+            .sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+
+        Ok(directives)
+    }
+}
+
+fn most_verbose(a: Level, b: Level) -> Level {
+    if a.is_at_least(b) { b } else { a }
+}
+
+fn parse_verbosity(level: &str) -> Result<LogVerbosity, Error> {
+    Ok(match level.to_ascii_lowercase().as_str() {
+        "critical" | "crit" => LogVerbosity::Critical,
+        "error" => LogVerbosity::Error,
+        "warning" | "warn" => LogVerbosity::Warning,
+        "info" => LogVerbosity::Info,
+        "debug" => LogVerbosity::Debug,
+        "trace" => LogVerbosity::Trace,
+        _ => return Err(format!("unknown log verbosity level `{level}`").into()),
+    })
+}
+
+/// A drain that filters the log records by the verbosity of their module.
+///
+/// The verbosity is resolved once per callsite and cached. The filtered out records are passed
+/// to the flight recorder, if it's enabled.
+pub(crate) struct VerbosityFilterDrain<D> {
+    inner: D,
+    directives: Arc<VerbosityDirectives>,
+    flight_recorder: Option<Arc<FlightRecorder>>,
+    max_level: Level,
+    callsite_levels: ThreadLocal<RefCell<HashMap<usize, Level>>>,
+}
+
+impl<D> VerbosityFilterDrain<D> {
+    pub(crate) fn new(
+        inner: D,
+        directives: VerbosityDirectives,
+        flight_recorder: Option<Arc<FlightRecorder>>,
+    ) -> Self {
+        let max_level = match &flight_recorder {
+            Some(recorder) => most_verbose(directives.max_level(), recorder.level()),
+            None => directives.max_level(),
+        };
+
+        Self {
+            inner,
+            max_level,
+            directives: Arc::new(directives),
+            flight_recorder,
+            callsite_levels: ThreadLocal::new(),
+        }
+    }
+
+    /// Returns the most verbose level of the records passed to the inner drain or the flight
+    /// recorder.
+    #[cfg(any(feature = "log-bridge", feature = "tracing-bridge"))]
+    pub(crate) fn max_level(&self) -> Level {
+        self.max_level
+    }
+
+    fn callsite_level(&self, record: &Record) -> Level {
+        if self.directives.modules.is_empty() {
+            return self.directives.default_verbosity().into();
+        }
+
+        // NOTE: the location is a static that is unique for each callsite.
+        let callsite = record.location() as *const RecordLocation as usize;
+        let mut callsite_levels = self.callsite_levels.get_or_default().borrow_mut();
+
+        *callsite_levels
+            .entry(callsite)
+            .or_insert_with(|| self.directives.module_verbosity(record.module()).into())
+    }
+}
+
+impl<D: Drain<Ok = ()>> Drain for VerbosityFilterDrain<D> {
+    type Ok = ();
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        if !record.level().is_at_least(self.callsite_level(record)) {
+            if let Some(recorder) = &self.flight_recorder {
+                recorder.record(record, values);
+            }
+
+            return Ok(());
+        }
+
+        if let Some(recorder) = &self.flight_recorder
+            && record.level().is_at_least(Level::Error)
+        {
+            recorder.flush_current();
+        }
+
+        self.inner.log(record, values)
+    }
+
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        level.is_at_least(self.max_level) && self.inner.is_enabled(level)
+    }
+
+    #[inline]
+    fn flush(&self) -> Result<(), slog::FlushError> {
+        Drain::flush(&self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_directives() {
+        let directives: VerbosityDirectives = " warn, my_service::proxy = DEBUG ,hyper=error,tokio"
+            .parse()
+            .unwrap();
+
+        assert_eq!(directives.default_verbosity(), LogVerbosity::Warning);
+        assert_eq!(
+            directives.module_verbosity("my_service"),
+            LogVerbosity::Warning
+        );
+        assert_eq!(
+            directives.module_verbosity("my_service::proxy"),
+            LogVerbosity::Debug
+        );
+        assert_eq!(
+            directives.module_verbosity("my_service::proxy::h2"),
+            LogVerbosity::Debug
+        );
+        assert_eq!(
+            directives.module_verbosity("my_service::proxy_v2"),
+            LogVerbosity::Warning
+        );
+        assert_eq!(
+            directives.module_verbosity("hyper::client"),
+            LogVerbosity::Error
+        );
+        assert_eq!(
+            directives.module_verbosity("tokio::net"),
+            LogVerbosity::Trace
+        );
+        assert_eq!(directives.max_level(), Level::Trace);
+
+        assert!("my_service=loud".parse::<VerbosityDirectives>().is_err());
+        assert!("=debug".parse::<VerbosityDirectives>().is_err());
+    }
+
+    #[test]
+    fn most_specific_directive_wins() {
+        let directives: VerbosityDirectives = "a=error,a::b::c=trace,a::b=info".parse().unwrap();
+
+        assert_eq!(directives.module_verbosity("a::x"), LogVerbosity::Error);
+        assert_eq!(directives.module_verbosity("a::b::x"), LogVerbosity::Info);
+        assert_eq!(
+            directives.module_verbosity("a::b::c::x"),
+            LogVerbosity::Trace
+        );
+        assert_eq!(directives.default_verbosity(), LogVerbosity::Info);
+    }
+}
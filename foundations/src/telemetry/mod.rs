@@ -6,6 +6,7 @@
 //! * distributed tracing (backed by [Jaeger])
 //! * metrics (backed by [Prometheus])
 //! * memory profiling (backed by [jemalloc])
+//! * CPU profiling (in [pprof] format)
 //! * monitoring tokio runtimes
 //!
 //! The library strives to minimize the bootstrap code required to set up basic telemetry for a
@@ -49,6 +50,7 @@
 //! [Jaeger]: https://www.jaegertracing.io/
 //! [Prometheus]: https://prometheus.io/
 //! [jemalloc]: https://github.com/jemalloc/jemalloc
+//! [pprof]: https://github.com/google/pprof
 
 use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -73,12 +75,18 @@ mod scope;
 
 mod telemetry_context;
 
-#[cfg(all(
-    feature = "tracing",
-    any(feature = "telemetry-otlp-grpc", feature = "user-tracing")
+#[cfg(any(
+    all(
+        feature = "tracing",
+        any(feature = "telemetry-otlp-grpc", feature = "user-tracing")
+    ),
+    all(feature = "logging", feature = "telemetry-otlp-grpc")
 ))]
 mod otlp_conversion;
 
+#[cfg(feature = "testing")]
+mod snapshot;
+
 #[cfg(feature = "testing")]
 mod testing;
 
@@ -94,6 +102,30 @@ pub mod tracing;
 #[cfg(all(target_os = "linux", feature = "memory-profiling"))]
 mod memory_profiler;
 
+#[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+mod cpu_profiler;
+
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "cpu-profiling", feature = "thread-dump")
+))]
+mod signal_stack;
+
+#[cfg(all(
+    unix,
+    any(
+        feature = "logging",
+        all(
+            target_os = "linux",
+            any(feature = "cpu-profiling", feature = "thread-dump")
+        )
+    )
+))]
+mod signal;
+
+#[cfg(all(target_os = "linux", feature = "thread-dump"))]
+mod thread_dump;
+
 pub mod settings;
 
 #[cfg(all(
@@ -152,11 +184,17 @@ use self::tracing::testing::UserTestTracerScope;
 use self::log::internal::LogScope;
 
 #[cfg(feature = "testing")]
-pub use self::testing::TestTelemetryContext;
+pub use self::{snapshot::TestSnapshotOptions, testing::TestTelemetryContext};
 
 #[cfg(all(target_os = "linux", feature = "memory-profiling"))]
 pub use self::memory_profiler::MemoryProfiler;
 
+#[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+pub use self::cpu_profiler::CpuProfiler;
+
+#[cfg(all(target_os = "linux", feature = "thread-dump"))]
+pub use self::thread_dump::ThreadDumper;
+
 #[cfg(feature = "telemetry-server")]
 pub use self::server::{
     TelemetryRouteBody, TelemetryRouteHandler, TelemetryRouteHandlerFuture, TelemetryServerRoute,
@@ -318,12 +356,29 @@ pub struct TelemetryConfig<'c> {
 /// - `/metrics` - returns service metrics in [Prometheus text format] (requires **metrics** feature).
 /// - `/pprof/heap` - returns [jemalloc] heap profile (requires **memory-profiling** feature).
 /// - `/pprof/heap_stats` returns [jemalloc] heap stats (requires **memory-profiling** feature).
+/// - `/pprof/profile?seconds=N` - collects a CPU profile for `N` seconds (30 by default) and
+///   returns it in gzip-compressed [pprof] format (requires **cpu-profiling** feature).
+/// - `/debug/tasks` - returns traces of the tasks of the runtimes registered with
+///   [`tokio_runtime_metrics::register_runtime`], grouped by identical traces, in plain text or
+///   in JSON with `format=json` query parameter (requires **tokio-task-dump** unstable feature).
+/// - `/debug/threads` - returns native stacks of all the process threads in plain text (requires
+///   **thread-dump** feature and [`ThreadDumpSettings::enabled`] set to `true`).
+/// - `/debug/flight_recorder` - emits the log records kept by the flight recorder on `POST`
+///   requests (requires [`LogFlightRecorderSettings::enabled`] set to `true`).
 ///
 /// Additional custom routes can be added via [`TelemetryConfig::custom_server_routes`].
 ///
+/// The server can listen on multiple addresses simultaneously, with each of the
+/// [`TelemetryServerSettings::additional_listeners`] optionally serving only a subset of the routes.
+///
 /// [Prometheus text format]: https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
 /// [jemalloc]: https://github.com/jemalloc/jemalloc
+/// [pprof]: https://github.com/google/pprof
+/// [`tokio_runtime_metrics::register_runtime`]: `crate::telemetry::tokio_runtime_metrics::register_runtime`
 /// [`TelemetryServerSettings::enabled`]: `crate::telemetry::settings::TelemetryServerSettings::enabled`
+/// [`ThreadDumpSettings::enabled`]: `crate::telemetry::settings::ThreadDumpSettings::enabled`
+/// [`LogFlightRecorderSettings::enabled`]: `crate::telemetry::settings::LogFlightRecorderSettings::enabled`
+/// [`TelemetryServerSettings::additional_listeners`]: `crate::telemetry::settings::TelemetryServerSettings::additional_listeners`
 /// [syscall sandboxing]: `crate::security`
 #[cfg(any(
     feature = "logging",
@@ -343,6 +398,17 @@ pub fn init(config: TelemetryConfig) -> BootstrapResult<TelemetryDriver> {
         )
     }
 
+    #[cfg(all(target_os = "linux", feature = "thread-dump", feature = "logging"))]
+    if config.settings.thread_dump.enabled
+        && let Some(reopen_signal) = config.settings.logging.reopen_signal
+        && let Some(dump_signal) = config.settings.thread_dump.log_signal
+        && reopen_signal.signum() == dump_signal.signum()
+    {
+        anyhow::bail!(
+            "log file `reopen_signal` and thread dump `log_signal` should be different signals"
+        );
+    }
+
     #[cfg(feature = "metrics")]
     self::metrics::init::init(config.service_info, &config.settings.metrics);
 
@@ -352,7 +418,16 @@ pub fn init(config: TelemetryConfig) -> BootstrapResult<TelemetryDriver> {
     let tele_futures: FuturesUnordered<_> = Default::default();
 
     #[cfg(feature = "logging")]
-    let logging_guard = self::log::init::init(config.service_info, &config.settings.logging)?;
+    let logging_guard = {
+        let (guard, initializer) =
+            self::log::init::init(config.service_info, &config.settings.logging)?;
+
+        if let Some(fut) = initializer {
+            tele_futures.push(fut);
+        }
+
+        guard
+    };
 
     #[cfg(feature = "tracing")]
     {
@@ -372,6 +447,9 @@ pub fn init(config: TelemetryConfig) -> BootstrapResult<TelemetryDriver> {
         }
     }
 
+    #[cfg(all(target_os = "linux", feature = "thread-dump"))]
+    ThreadDumper::get_or_init_with(&config.settings.thread_dump)?;
+
     TELEMETRY_INITIALIZED.store(true, Ordering::Relaxed);
 
     #[cfg(feature = "telemetry-server")]
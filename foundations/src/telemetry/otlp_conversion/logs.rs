@@ -0,0 +1,230 @@
+use super::common::{convert_service_info_to_resource, convert_time};
+use crate::ServiceInfo;
+use crate::telemetry::log::fields::{FieldCollector, collect_fields};
+use opentelemetry_proto::tonic as otlp;
+use otlp::common::v1::any_value::Value;
+use otlp::logs::v1::SeverityNumber;
+use slog::{Key, Level, OwnedKVList, Record, Serializer};
+use std::fmt;
+use std::time::SystemTime;
+
+macro_rules! emit_int {
+    ($($name:ident: $ty:ty),*) => {
+        $(
+            fn $name(&mut self, key: Key, val: $ty) -> slog::Result {
+                self.push(key, Value::IntValue(val.into()));
+
+                Ok(())
+            }
+        )*
+    };
+}
+
+/// Collects the log record fields as OTLP attributes, preserving the primitive value types.
+#[derive(Default)]
+struct AttributeCollector(Vec<otlp::common::v1::KeyValue>);
+
+impl AttributeCollector {
+    fn push(&mut self, key: Key, value: Value) {
+        self.0.push(otlp::common::v1::KeyValue {
+            key: key.to_string(),
+            value: Some(otlp::common::v1::AnyValue { value: Some(value) }),
+        });
+    }
+}
+
+impl FieldCollector for AttributeCollector {
+    type Field = otlp::common::v1::KeyValue;
+
+    fn into_fields(self) -> Vec<Self::Field> {
+        self.0
+    }
+}
+
+impl Serializer for AttributeCollector {
+    emit_int!(
+        emit_i8: i8, emit_i16: i16, emit_i32: i32, emit_i64: i64,
+        emit_u8: u8, emit_u16: u16, emit_u32: u32
+    );
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        // NOTE: OTLP only has signed integers, so the values that don't fit are sent as strings.
+        match i64::try_from(val) {
+            Ok(val) => self.push(key, Value::IntValue(val)),
+            Err(_) => self.push(key, Value::StringValue(val.to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn emit_f32(&mut self, key: Key, val: f32) -> slog::Result {
+        self.push(key, Value::DoubleValue(val.into()));
+
+        Ok(())
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        self.push(key, Value::DoubleValue(val));
+
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.push(key, Value::BoolValue(val));
+
+        Ok(())
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> slog::Result {
+        self.push(key, Value::StringValue(val.to_string()));
+
+        Ok(())
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.push(key, Value::StringValue(val.to_string()));
+
+        Ok(())
+    }
+}
+
+// NOTE: https://opentelemetry.io/docs/specs/otel/logs/data-model-appendix/#appendix-b-severitynumber-example-mappings
+fn convert_level(level: Level) -> SeverityNumber {
+    match level {
+        Level::Critical => SeverityNumber::Fatal,
+        Level::Error => SeverityNumber::Error,
+        Level::Warning => SeverityNumber::Warn,
+        Level::Info => SeverityNumber::Info,
+        Level::Debug => SeverityNumber::Debug,
+        Level::Trace => SeverityNumber::Trace,
+    }
+}
+
+/// Sets the trace context of the record from the current span, if there is one.
+#[cfg(feature = "tracing")]
+fn set_trace_context(log_record: &mut otlp::logs::v1::LogRecord) {
+    use super::tracing::{convert_sampled_flag, convert_trace_id};
+    use crate::telemetry::tracing::internal::current_span;
+
+    let Some(span) = current_span() else {
+        return;
+    };
+
+    span.inner.with_read(|span| {
+        if let Some(context) = span.context() {
+            let span_state = context.state();
+
+            log_record.trace_id = convert_trace_id(span_state);
+            log_record.span_id = span_state.span_id().to_be_bytes().to_vec();
+            log_record.flags = convert_sampled_flag(span_state);
+        }
+    });
+}
+
+/// Converts the log record to the OTLP format.
+///
+/// Must be called on the thread that emits the record, so the record can be associated with the
+/// current span.
+pub(crate) fn convert_record(
+    record: &Record,
+    values: &OwnedKVList,
+) -> Result<otlp::logs::v1::LogRecord, slog::Error> {
+    let attributes = collect_fields::<AttributeCollector>(record, values)?;
+
+    let time = convert_time(SystemTime::now());
+
+    #[allow(unused_mut)]
+    let mut log_record = otlp::logs::v1::LogRecord {
+        time_unix_nano: time,
+        observed_time_unix_nano: time,
+        severity_number: convert_level(record.level()).into(),
+        severity_text: record.level().as_str().to_string(),
+        body: Some(otlp::common::v1::AnyValue {
+            value: Some(Value::StringValue(record.msg().to_string())),
+        }),
+        attributes,
+        dropped_attributes_count: Default::default(),
+        flags: Default::default(),
+        trace_id: Default::default(),
+        span_id: Default::default(),
+        event_name: Default::default(),
+    };
+
+    #[cfg(feature = "tracing")]
+    set_trace_context(&mut log_record);
+
+    Ok(log_record)
+}
+
+pub(crate) fn convert_records(
+    log_records: Vec<otlp::logs::v1::LogRecord>,
+    service_info: &ServiceInfo,
+) -> otlp::logs::v1::ResourceLogs {
+    otlp::logs::v1::ResourceLogs {
+        resource: Some(convert_service_info_to_resource(service_info)),
+        schema_url: Default::default(),
+        scope_logs: vec![otlp::logs::v1::ScopeLogs {
+            schema_url: Default::default(),
+            scope: None,
+            log_records,
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{Drain, Logger, o};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct ConvertingDrain(Arc<Mutex<Vec<otlp::logs::v1::LogRecord>>>);
+
+    impl Drain for ConvertingDrain {
+        type Ok = ();
+        type Err = slog::Error;
+
+        fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), slog::Error> {
+            self.0.lock().unwrap().push(convert_record(record, values)?);
+
+            Ok(())
+        }
+    }
+
+    fn attr(key: &str, value: Value) -> otlp::common::v1::KeyValue {
+        otlp::common::v1::KeyValue {
+            key: key.into(),
+            value: Some(otlp::common::v1::AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn record_conversion() {
+        let drain = ConvertingDrain::default();
+        let log = Logger::root(drain.clone().fuse(), o!("version" => "1.0.0"));
+
+        slog::warn!(log, "Hello OTLP"; "count" => 42u64, "ratio" => 0.5, "ok" => true, "big" => u64::MAX);
+
+        let records = drain.0.lock().unwrap();
+        let record = &records[0];
+
+        assert_eq!(record.severity_number, SeverityNumber::Warn as i32);
+        assert_eq!(record.severity_text, "WARNING");
+        assert_eq!(
+            record.body.as_ref().unwrap().value,
+            Some(Value::StringValue("Hello OTLP".into()))
+        );
+        assert_eq!(
+            record.attributes,
+            [
+                attr("count", Value::IntValue(42)),
+                attr("ratio", Value::DoubleValue(0.5)),
+                attr("ok", Value::BoolValue(true)),
+                attr("big", Value::StringValue(u64::MAX.to_string())),
+                attr("version", Value::StringValue("1.0.0".into())),
+            ]
+        );
+        assert!(record.trace_id.is_empty());
+        assert!(record.span_id.is_empty());
+    }
+}
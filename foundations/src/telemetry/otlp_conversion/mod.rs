@@ -1,3 +1,7 @@
 mod common;
 
+#[cfg(feature = "tracing")]
 pub(super) mod tracing;
+
+#[cfg(all(feature = "logging", feature = "telemetry-otlp-grpc"))]
+pub(super) mod logs;
@@ -7,7 +7,7 @@ use cf_rustracing_jaeger::span::FinishedSpan;
 use cf_rustracing_jaeger::span::SpanContextState;
 use opentelemetry_proto::tonic as otlp;
 
-fn convert_trace_id(span_state: &SpanContextState) -> Vec<u8> {
+pub(super) fn convert_trace_id(span_state: &SpanContextState) -> Vec<u8> {
     span_state
         .trace_id()
         .high
@@ -28,7 +28,7 @@ fn convert_parent_span_id(span: &FinishedSpan) -> Vec<u8> {
 }
 
 // NOTE: https://www.w3.org/TR/trace-context/#sampled-flag
-fn convert_sampled_flag(span_state: &SpanContextState) -> u32 {
+pub(super) fn convert_sampled_flag(span_state: &SpanContextState) -> u32 {
     if span_state.is_sampled() { 0x01 } else { 0x00 }
 }
 
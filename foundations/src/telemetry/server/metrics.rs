@@ -0,0 +1,22 @@
+use crate::telemetry::metrics::{Counter, Gauge, Histogram, HistogramBuilder, metrics};
+use std::sync::Arc;
+
+#[metrics(crate_path = "crate")]
+pub(super) mod telemetry_server {
+    /// Number of currently open telemetry server connections.
+    pub fn connections_active() -> Gauge;
+
+    /// Total number of connections accepted by the telemetry server.
+    pub fn connections_total() -> Counter;
+
+    /// Total number of telemetry server requests.
+    ///
+    /// Requests that don't match any route are reported with the `unmatched` route.
+    pub fn requests_total(route: &Arc<str>, status: u16) -> Counter;
+
+    /// Duration of telemetry server requests in seconds.
+    #[ctor = HistogramBuilder {
+        buckets: &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0],
+    }]
+    pub fn request_duration_seconds(route: &Arc<str>) -> Histogram;
+}
@@ -1,24 +1,32 @@
 use super::settings::TelemetrySettings;
 use crate::BootstrapResult;
+#[cfg(unix)]
+use crate::addr::InheritedListener;
 use crate::addr::ListenAddr;
 use crate::telemetry::log;
 use anyhow::Context as _;
-use futures_util::future::FutureExt;
+use futures_util::future::{BoxFuture, FutureExt};
 use futures_util::{pin_mut, ready};
-use hyper_util::rt::TokioIo;
+use hyper::service::{Service as _, service_fn};
+use hyper_util::rt::{TokioIo, TokioTimer};
 use socket2::{Domain, SockAddr, Socket, Type};
 use std::convert::Infallible;
-use std::future::Future;
+use std::future::{Future, poll_fn};
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
 #[cfg(unix)]
 use tokio::net::{TcpStream, UnixListener, UnixStream};
-use tokio::sync::watch;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
+use tokio::time::{Instant, Sleep};
 
+#[cfg(feature = "metrics")]
+mod metrics;
 mod router;
 
 #[cfg(feature = "memory-profiling")]
@@ -79,6 +87,149 @@ impl AsyncWrite for TelemetryStream {
     }
 }
 
+/// A stream wrapper that keeps track of the last I/O activity on the connection.
+struct ActivityTrackingStream {
+    inner: TelemetryStream,
+    tracker: ActivityTracker,
+}
+
+impl ActivityTrackingStream {
+    fn new(inner: TelemetryStream) -> Self {
+        Self {
+            inner,
+            tracker: ActivityTracker {
+                started_at: Instant::now(),
+                last_activity: Default::default(),
+                requests_in_flight: Default::default(),
+            },
+        }
+    }
+
+    fn tracker(&self) -> ActivityTracker {
+        self.tracker.clone()
+    }
+
+    fn record_activity<T>(&self, poll: Poll<T>) -> Poll<T> {
+        if poll.is_ready() {
+            self.tracker.record_activity();
+        }
+
+        poll
+    }
+}
+
+impl AsyncRead for ActivityTrackingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        self.record_activity(poll)
+    }
+}
+
+impl AsyncWrite for ActivityTrackingStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+        self.record_activity(poll)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[derive(Clone)]
+struct ActivityTracker {
+    started_at: Instant,
+    // NOTE: milliseconds since `started_at`.
+    last_activity: Arc<AtomicU64>,
+    requests_in_flight: Arc<AtomicUsize>,
+}
+
+impl ActivityTracker {
+    fn record_activity(&self) {
+        let elapsed = self.started_at.elapsed().as_millis() as u64;
+
+        self.last_activity.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Marks a request as being in flight until the returned guard is dropped.
+    fn start_request(&self) -> InFlightRequest {
+        self.requests_in_flight.fetch_add(1, Ordering::Relaxed);
+
+        InFlightRequest {
+            tracker: self.clone(),
+        }
+    }
+
+    fn idle_deadline(&self, idle_timeout: Duration) -> Instant {
+        // NOTE: request handlers might not do any I/O for a long time (e.g. while collecting
+        // a CPU profile), so the connection is not idle while they run.
+        if self.requests_in_flight.load(Ordering::Relaxed) > 0 {
+            return Instant::now() + idle_timeout;
+        }
+
+        let last_activity = Duration::from_millis(self.last_activity.load(Ordering::Relaxed));
+
+        self.started_at + last_activity + idle_timeout
+    }
+}
+
+struct InFlightRequest {
+    tracker: ActivityTracker,
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.tracker.record_activity();
+        self.tracker
+            .requests_in_flight
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A telemetry server connection slot, released once the connection is closed.
+struct ConnectionSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ConnectionSlot {
+    fn new(permit: OwnedSemaphorePermit) -> Self {
+        #[cfg(feature = "metrics")]
+        {
+            metrics::telemetry_server::connections_total().inc();
+            metrics::telemetry_server::connections_active().inc();
+        }
+
+        Self { _permit: permit }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        #[cfg(feature = "metrics")]
+        metrics::telemetry_server::connections_active().dec();
+    }
+}
+
 enum TelemetryListener {
     Tcp(TcpListener),
     #[cfg(unix)]
@@ -97,20 +248,6 @@ impl TelemetryListener {
         }
     }
 
-    pub(crate) async fn accept(&self) -> std::io::Result<TelemetryStream> {
-        match self {
-            TelemetryListener::Tcp(listener) => listener
-                .accept()
-                .await
-                .map(|(conn, _)| TelemetryStream::Tcp(conn)),
-            #[cfg(unix)]
-            TelemetryListener::Unix(listener) => listener
-                .accept()
-                .await
-                .map(|(conn, _)| TelemetryStream::Unix(conn)),
-        }
-    }
-
     pub(crate) fn poll_accept(
         &mut self,
         cx: &mut std::task::Context<'_>,
@@ -135,9 +272,17 @@ pub use router::{
     TelemetryRouteBody, TelemetryRouteHandler, TelemetryRouteHandlerFuture, TelemetryServerRoute,
 };
 
+/// Delay before accepting connections again after a listener fails to accept a connection.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
 pub(super) struct TelemetryServerFuture {
-    listener: TelemetryListener,
-    router: Router,
+    // NOTE: each listener has its own router, as listeners can serve different sets of routes.
+    listeners: Vec<(TelemetryListener, Router)>,
+    settings: Arc<TelemetrySettings>,
+    connection_limit: Arc<Semaphore>,
+    acquire_permit: Option<BoxFuture<'static, OwnedSemaphorePermit>>,
+    permit: Option<OwnedSemaphorePermit>,
+    accept_backoff: Option<Pin<Box<Sleep>>>,
 }
 
 impl TelemetryServerFuture {
@@ -149,6 +294,15 @@ impl TelemetryServerFuture {
             return Ok(None);
         }
 
+        let max_connections = settings.server.max_connections;
+
+        if max_connections == 0 || max_connections > Semaphore::MAX_PERMITS {
+            anyhow::bail!(
+                "telemetry server `max_connections` should be in the range [1, {}]",
+                Semaphore::MAX_PERMITS
+            );
+        }
+
         let settings = Arc::new(settings);
 
         // Eagerly init the memory profiler so it gets set up before syscalls are sandboxed with seccomp.
@@ -158,44 +312,128 @@ impl TelemetryServerFuture {
                 .map_err(|err| anyhow::anyhow!(err))?;
         }
 
+        // Eagerly init the CPU profiler so its signal handler and profiling thread get set up
+        // before syscalls are sandboxed with seccomp.
+        #[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+        if settings.cpu_profiler.enabled {
+            cpu_profiling::profiler(Arc::clone(&settings)).map_err(|err| anyhow::anyhow!(err))?;
+        }
+
         let router = Router::new(custom_routes, Arc::clone(&settings))
             .context("building telemetry server router")?;
 
-        let listener = match &settings.server.addr {
-            ListenAddr::Tcp(addr) => {
-                let std_listener = std::net::TcpListener::from(
-                    bind_socket(*addr)
-                        .with_context(|| format!("binding to TCP socket {addr:?}"))?,
-                );
-                std_listener.set_nonblocking(true)?;
-                let tokio_listener = tokio::net::TcpListener::from_std(std_listener)?;
-                TelemetryListener::Tcp(tokio_listener)
-            }
+        let listener = open_listener(
+            &settings.server.addr,
             #[cfg(unix)]
-            ListenAddr::Unix(path) => {
-                // Remove existing socket file if it exists to avoid bind errors
-                if path.exists()
-                    && let Err(e) = std::fs::remove_file(path)
-                {
-                    log::warn!("failed to remove existing Unix socket file"; "path" => %path.display(), "error" => e);
+            settings.server.inherited_listener.as_ref(),
+        )?;
+
+        let mut listeners = vec![(listener, router.clone())];
+
+        for listener_settings in &settings.server.additional_listeners {
+            let router = match &listener_settings.routes {
+                Some(routes) => router.with_allowed_routes(routes).with_context(|| {
+                    format!(
+                        "building telemetry server router for {}",
+                        listener_settings.addr
+                    )
+                })?,
+                None => router.clone(),
+            };
+
+            let listener = open_listener(
+                &listener_settings.addr,
+                #[cfg(unix)]
+                listener_settings.inherited_listener.as_ref(),
+            )?;
+
+            listeners.push((listener, router));
+        }
+
+        Ok(Some(TelemetryServerFuture {
+            listeners,
+            settings,
+            connection_limit: Arc::new(Semaphore::new(max_connections)),
+            acquire_permit: None,
+            permit: None,
+            accept_backoff: None,
+        }))
+    }
+
+    pub(super) fn local_addrs(&self) -> Vec<ListenAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|(listener, _)| listener.local_addr().ok())
+            .collect()
+    }
+
+    fn poll_connection_slot(&mut self, cx: &mut Context<'_>) -> Poll<OwnedSemaphorePermit> {
+        if let Some(permit) = self.permit.take() {
+            return Poll::Ready(permit);
+        }
+
+        let acquire_permit = self.acquire_permit.get_or_insert_with(|| {
+            Arc::clone(&self.connection_limit)
+                .acquire_owned()
+                .map(|permit| permit.expect("connection limit semaphore is never closed"))
+                .boxed()
+        });
+
+        let permit = ready!(acquire_permit.poll_unpin(cx));
+
+        self.acquire_permit = None;
+
+        Poll::Ready(permit)
+    }
+
+    fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<(TelemetryStream, Router, ConnectionSlot)> {
+        // NOTE: don't accept new connections until there is a free connection slot, so the
+        // pending connections stay in the listeners' backlog.
+        let permit = ready!(self.poll_connection_slot(cx));
+
+        'poll: loop {
+            if let Some(backoff) = &mut self.accept_backoff {
+                if backoff.as_mut().poll(cx).is_pending() {
+                    self.permit = Some(permit);
+
+                    return Poll::Pending;
                 }
 
-                let unix_listener = UnixListener::bind(path)
-                    .with_context(|| format!("binding to Unix socket {path:?}"))?;
-                TelemetryListener::Unix(unix_listener)
+                self.accept_backoff = None;
             }
-        };
 
-        Ok(Some(TelemetryServerFuture { listener, router }))
-    }
+            for (listener, router) in &mut self.listeners {
+                match listener.poll_accept(cx) {
+                    Poll::Ready(Ok(conn)) => {
+                        return Poll::Ready((conn, router.clone(), ConnectionSlot::new(permit)));
+                    }
+                    Poll::Ready(Err(e)) => {
+                        log::warn!("failed to accept connection"; "error" => e);
+
+                        // NOTE: accept errors are usually persistent (e.g. when the process runs
+                        // out of file descriptors), so back off instead of retrying in a busy
+                        // loop. The backoff timer registers for the wakeup on the next iteration.
+                        self.accept_backoff =
+                            Some(Box::pin(tokio::time::sleep(ACCEPT_ERROR_BACKOFF)));
 
-    pub(super) fn local_addr(&self) -> BootstrapResult<ListenAddr> {
-        self.listener.local_addr()
+                        continue 'poll;
+                    }
+                    Poll::Pending => {}
+                }
+            }
+
+            self.permit = Some(permit);
+
+            return Poll::Pending;
+        }
     }
 
     // Adapted from Hyper 0.14 Server stuff and axum::serve::serve.
     pub(super) async fn with_graceful_shutdown(
-        self,
+        mut self,
         shutdown_signal: impl Future<Output = ()> + Send + Sync + 'static,
     ) {
         let (signal_tx, signal_rx) = watch::channel(());
@@ -208,40 +446,19 @@ impl TelemetryServerFuture {
         });
 
         let (close_tx, close_rx) = watch::channel(());
-        let listener = self.listener;
 
         loop {
-            let socket = tokio::select! {
-                conn = listener.accept() => match conn {
-                    Ok(conn) => TokioIo::new(conn),
-                    Err(e) => {
-                        log::warn!("failed to accept connection"; "error" => e);
-                        continue;
-                    }
-                },
+            let (stream, router, slot) = tokio::select! {
+                accepted = poll_fn(|cx| self.poll_accept(cx)) => accepted,
                 _ = signal_tx.closed() => { break },
             };
 
-            let router = self.router.clone();
             let signal_tx = Arc::clone(&signal_tx);
             let close_rx = close_rx.clone();
+            let settings = Arc::clone(&self.settings);
 
             tokio::spawn(async move {
-                let conn = hyper::server::conn::http1::Builder::new()
-                    .serve_connection(socket, router)
-                    .with_upgrades();
-
-                let signal_closed = signal_tx.closed().fuse();
-
-                pin_mut!(conn);
-                pin_mut!(signal_closed);
-
-                loop {
-                    tokio::select! {
-                        _ = conn.as_mut() => break,
-                        _ = &mut signal_closed => conn.as_mut().graceful_shutdown(),
-                    }
-                }
+                serve_connection(stream, router, slot, &settings, signal_tx.closed()).await;
 
                 drop(close_rx);
             });
@@ -260,24 +477,155 @@ impl Future for TelemetryServerFuture {
         let this = &mut *self;
 
         loop {
-            let socket = match ready!(this.listener.poll_accept(cx)) {
-                Ok(conn) => TokioIo::new(conn),
-                Err(e) => {
-                    log::warn!("failed to accept connection"; "error" => e);
+            let (stream, router, slot) = ready!(this.poll_accept(cx));
+            let settings = Arc::clone(&this.settings);
+
+            tokio::spawn(async move {
+                serve_connection(stream, router, slot, &settings, std::future::pending()).await
+            });
+        }
+    }
+}
+
+async fn serve_connection(
+    stream: TelemetryStream,
+    router: Router,
+    slot: ConnectionSlot,
+    settings: &TelemetrySettings,
+    shutdown_signal: impl Future<Output = ()>,
+) {
+    let header_read_timeout = non_zero_duration(settings.server.header_read_timeout_seconds);
+    let idle_timeout = non_zero_duration(settings.server.idle_timeout_seconds);
+    let stream = ActivityTrackingStream::new(stream);
+    let activity = stream.tracker();
+
+    let service = {
+        let activity = activity.clone();
+
+        service_fn(move |req| {
+            let request = activity.start_request();
+            let res_fut = router.call(req);
+
+            async move {
+                let res = res_fut.await;
+
+                drop(request);
+
+                res
+            }
+        })
+    };
+
+    let conn = hyper::server::conn::http1::Builder::new()
+        .timer(TokioTimer::new())
+        .header_read_timeout(header_read_timeout)
+        // upgrades needed for websockets
+        .serve_connection(TokioIo::new(stream), service)
+        .with_upgrades();
+
+    let shutdown_signal = shutdown_signal.fuse();
+
+    pin_mut!(conn);
+    pin_mut!(shutdown_signal);
+
+    let mut shutting_down = false;
+    let mut idle_shutdown_at = None;
+
+    // NOTE: in-flight requests are allowed to complete after the graceful shutdown, so the
+    // connection is closed if it's still idle one more idle timeout after the shutdown. Otherwise,
+    // peers that don't read the responses could hold the connection slots forever. Connections
+    // with requests in flight are never considered idle.
+    let idle_deadline = |idle_shutdown_at: Option<Instant>| {
+        idle_timeout.map(|timeout| {
+            let deadline = activity.idle_deadline(timeout);
+
+            match idle_shutdown_at {
+                Some(at) => deadline.max(at + timeout),
+                None => deadline,
+            }
+        })
+    };
+
+    loop {
+        let deadline = idle_deadline(idle_shutdown_at);
+
+        tokio::select! {
+            _ = conn.as_mut() => break,
+            _ = &mut shutdown_signal, if !shutting_down => {
+                shutting_down = true;
+                conn.as_mut().graceful_shutdown();
+            },
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)),
+                if deadline.is_some() =>
+            {
+                let now = Instant::now();
+
+                // NOTE: the deadline might have been moved by the connection activity while
+                // we were sleeping.
+                if idle_deadline(idle_shutdown_at).is_none_or(|deadline| deadline > now) {
                     continue;
                 }
-            };
 
-            let router = this.router.clone();
+                if idle_shutdown_at.is_some() {
+                    break;
+                }
 
-            tokio::spawn(
-                hyper::server::conn::http1::Builder::new()
-                    // upgrades needed for websockets
-                    .serve_connection(socket, router)
-                    .with_upgrades(),
-            );
+                idle_shutdown_at = Some(now);
+
+                if !shutting_down {
+                    shutting_down = true;
+                    conn.as_mut().graceful_shutdown();
+                }
+            },
         }
     }
+
+    drop(slot);
+}
+
+fn non_zero_duration(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+fn open_listener(
+    addr: &ListenAddr,
+    #[cfg(unix)] inherited: Option<&InheritedListener>,
+) -> BootstrapResult<TelemetryListener> {
+    #[cfg(unix)]
+    if let Some(inherited) = inherited {
+        return inherited_listener(inherited)
+            .with_context(|| format!("using inherited socket {inherited}"));
+    }
+
+    bind_listener(addr)
+}
+
+fn bind_listener(addr: &ListenAddr) -> BootstrapResult<TelemetryListener> {
+    let listener = match addr {
+        ListenAddr::Tcp(addr) => {
+            let std_listener = std::net::TcpListener::from(
+                bind_socket(*addr).with_context(|| format!("binding to TCP socket {addr:?}"))?,
+            );
+            std_listener.set_nonblocking(true)?;
+            let tokio_listener = tokio::net::TcpListener::from_std(std_listener)?;
+            TelemetryListener::Tcp(tokio_listener)
+        }
+        #[cfg(unix)]
+        ListenAddr::Unix(path) => {
+            // Remove existing socket file if it exists to avoid bind errors
+            if path.exists()
+                && let Err(e) = std::fs::remove_file(path)
+            {
+                log::warn!("failed to remove existing Unix socket file"; "path" => %path.display(), "error" => e);
+            }
+
+            let unix_listener = UnixListener::bind(path)
+                .with_context(|| format!("binding to Unix socket {path:?}"))?;
+            TelemetryListener::Unix(unix_listener)
+        }
+    };
+
+    Ok(listener)
 }
 
 fn bind_socket(addr: SocketAddr) -> BootstrapResult<Socket> {
@@ -300,6 +648,21 @@ fn bind_socket(addr: SocketAddr) -> BootstrapResult<Socket> {
     Ok(socket)
 }
 
+#[cfg(unix)]
+fn inherited_listener(inherited: &InheritedListener) -> BootstrapResult<TelemetryListener> {
+    let socket = Socket::from(inherited.take_fd()?);
+
+    socket.set_nonblocking(true)?;
+
+    let listener = if socket.local_addr()?.is_unix() {
+        TelemetryListener::Unix(UnixListener::from_std(socket.into())?)
+    } else {
+        TelemetryListener::Tcp(TcpListener::from_std(socket.into())?)
+    };
+
+    Ok(listener)
+}
+
 #[cfg(all(target_os = "linux", feature = "memory-profiling"))]
 mod memory_profiling {
     use super::*;
@@ -320,3 +683,95 @@ mod memory_profiling {
         profiler(settings)?.heap_stats()
     }
 }
+
+#[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+mod cpu_profiling {
+    use super::*;
+    use crate::Result;
+    use crate::telemetry::CpuProfiler;
+    use hyper::Request;
+    use hyper::body::Incoming;
+    use std::time::Duration;
+
+    const DEFAULT_PROFILE_DURATION_SECS: u64 = 30;
+
+    pub(super) fn profiler(settings: Arc<TelemetrySettings>) -> Result<CpuProfiler> {
+        CpuProfiler::get_or_init_with(&settings.cpu_profiler)?
+            .ok_or_else(|| "CPU profiling should be enabled in the telemetry settings".into())
+    }
+
+    /// Returns the profile duration from the `seconds` query parameter of the request.
+    pub(super) fn profile_duration(
+        req: &Request<Incoming>,
+    ) -> std::result::Result<Duration, String> {
+        let seconds = req
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|param| param.strip_prefix("seconds="));
+
+        match seconds {
+            Some(seconds) => seconds
+                .parse()
+                .map(Duration::from_secs)
+                .map_err(|_| format!("invalid `seconds` query parameter: {seconds:?}")),
+            None => Ok(Duration::from_secs(DEFAULT_PROFILE_DURATION_SECS)),
+        }
+    }
+
+    pub(super) async fn profile(
+        duration: Duration,
+        settings: Arc<TelemetrySettings>,
+    ) -> Result<Vec<u8>> {
+        profiler(settings)?.profile(duration).await
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "thread-dump"))]
+mod thread_dump {
+    use super::*;
+    use crate::Result;
+    use crate::telemetry::ThreadDumper;
+
+    pub(super) async fn threads(settings: Arc<TelemetrySettings>) -> Result<String> {
+        let dumper = ThreadDumper::get_or_init_with(&settings.thread_dump)?
+            .ok_or("thread dumps should be enabled in the telemetry settings")?;
+
+        tokio::task::spawn_blocking(move || dumper.dump()).await?
+    }
+}
+
+#[cfg(foundations_tokio_task_dump)]
+mod task_dump {
+    use crate::Result;
+    use crate::telemetry::tokio_runtime_metrics::dump_tasks;
+    use hyper::Request;
+    use hyper::body::Incoming;
+    use std::time::Duration;
+
+    const DUMP_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Returns the task dumps of the registered runtimes along with their content type.
+    pub(super) async fn tasks(req: Request<Incoming>) -> (&'static str, Result<String>) {
+        let json = req
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .any(|param| param == "format=json");
+
+        let dumps = dump_tasks(DUMP_TIMEOUT).await;
+
+        if json {
+            (
+                "application/json; charset=utf-8",
+                serde_json::to_string(&dumps).map_err(Into::into),
+            )
+        } else {
+            let text = dumps.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+            ("text/plain; charset=utf-8", Ok(text.join("\n")))
+        }
+    }
+}
@@ -1,8 +1,17 @@
+#[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+use super::cpu_profiling;
 #[cfg(all(target_os = "linux", feature = "memory-profiling"))]
 use super::memory_profiling;
+#[cfg(feature = "metrics")]
+use super::metrics::telemetry_server as server_metrics;
 #[cfg(feature = "memory-profiling")]
 use super::pprof_symbol;
+#[cfg(foundations_tokio_task_dump)]
+use super::task_dump;
+#[cfg(all(target_os = "linux", feature = "thread-dump"))]
+use super::thread_dump;
 use crate::BootstrapResult;
+use crate::telemetry::log;
 #[cfg(feature = "metrics")]
 use crate::telemetry::metrics;
 use crate::telemetry::reexports::http_body_util::{BodyExt, Empty, Full, combinators::BoxBody};
@@ -14,9 +23,10 @@ use hyper::body::{Bytes, Incoming};
 use hyper::service::Service;
 use hyper::{Method, Request, Response, StatusCode, header};
 use percent_encoding::percent_decode_str;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Instant;
 
 /// Body type used in [`TelemetryServerRoute`] responses.
 pub type TelemetryRouteBody = BoxBody<Bytes, crate::Error>;
@@ -50,7 +60,11 @@ type RouteHandlerShared = Arc<
 /// - `/pprof/heap` (`memory-profiling` feature)
 /// - `/pprof/heap_stats` (`memory-profiling` feature)
 /// - `/pprof/symbol` (`memory-profiling` feature)
+/// - `/pprof/profile` (`cpu-profiling` feature)
 /// - `/debug/traces` (`tracing` feature)
+/// - `/debug/tasks` (`tokio-task-dump` feature)
+/// - `/debug/threads` (`thread-dump` feature)
+/// - `/debug/flight_recorder` (`logging.flight_recorder.enabled` setting)
 ///
 /// New built-in routes may be added from time to time. We reserve the `/foundations/`
 /// prefix for this purpose, but other paths may be used if there are existing conventions
@@ -91,13 +105,22 @@ pub struct TelemetryServerRoute {
     pub handler: TelemetryRouteHandler,
 }
 
-struct Routes(HashMap<Method, matchit::Router<RouteHandlerShared>>);
+#[derive(Default)]
+struct Routes {
+    // NOTE: the original route path is stored alongside the handler, so listeners can
+    // restrict the set of served routes.
+    by_method: HashMap<Method, matchit::Router<(Arc<str>, RouteHandlerShared)>>,
+    paths: HashSet<String>,
+}
 
 impl Routes {
-    fn new(custom_routes: Vec<TelemetryServerRoute>) -> BootstrapResult<Self> {
-        let mut map = Self(Default::default());
+    fn new(
+        custom_routes: Vec<TelemetryServerRoute>,
+        settings: &TelemetrySettings,
+    ) -> BootstrapResult<Self> {
+        let mut map = Self::default();
 
-        map.init_built_in_routes()?;
+        map.init_built_in_routes(settings)?;
 
         for route in custom_routes {
             map.set(route)?;
@@ -106,13 +129,30 @@ impl Routes {
         Ok(map)
     }
 
-    fn init_built_in_routes(&mut self) -> BootstrapResult<()> {
+    fn init_built_in_routes(&mut self, settings: &TelemetrySettings) -> BootstrapResult<()> {
         self.set(TelemetryServerRoute {
             path: "/health".into(),
             methods: vec![Method::GET],
             handler: Box::new(|_, _| async { into_response("text/plain", Ok("")) }.boxed()),
         })?;
 
+        // NOTE: flushing changes the state of the flight recorder, so the route is only served
+        // if the flight recorder is enabled.
+        if settings.logging.flight_recorder.enabled {
+            self.set(TelemetryServerRoute {
+                path: "/debug/flight_recorder".into(),
+                methods: vec![Method::POST],
+                handler: Box::new(|_, _| {
+                    async {
+                        let count = log::flush_all_flight_recorders();
+
+                        into_response("text/plain", Ok(format!("flushed {count} log records\n")))
+                    }
+                    .boxed()
+                }),
+            })?;
+        }
+
         #[cfg(feature = "metrics")]
         self.set(TelemetryServerRoute {
             path: "/metrics".into(),
@@ -173,6 +213,55 @@ impl Routes {
             }),
         })?;
 
+        #[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+        self.set(TelemetryServerRoute {
+            path: "/pprof/profile".into(),
+            methods: vec![Method::GET],
+            handler: Box::new(|req, settings| {
+                async move {
+                    let duration = match cpu_profiling::profile_duration(&req) {
+                        Ok(duration) => duration,
+                        Err(err) => return bad_request(err),
+                    };
+
+                    into_response(
+                        "application/octet-stream",
+                        cpu_profiling::profile(duration, settings).await,
+                    )
+                }
+                .boxed()
+            }),
+        })?;
+
+        #[cfg(all(target_os = "linux", feature = "thread-dump"))]
+        self.set(TelemetryServerRoute {
+            path: "/debug/threads".into(),
+            methods: vec![Method::GET],
+            handler: Box::new(|_, settings| {
+                async move {
+                    into_response(
+                        "text/plain; charset=utf-8",
+                        thread_dump::threads(settings).await,
+                    )
+                }
+                .boxed()
+            }),
+        })?;
+
+        #[cfg(foundations_tokio_task_dump)]
+        self.set(TelemetryServerRoute {
+            path: "/debug/tasks".into(),
+            methods: vec![Method::GET],
+            handler: Box::new(|req, _| {
+                async move {
+                    let (content_type, res) = task_dump::tasks(req).await;
+
+                    into_response(content_type, res)
+                }
+                .boxed()
+            }),
+        })?;
+
         #[cfg(feature = "tracing")]
         self.set(TelemetryServerRoute {
             path: "/debug/traces".into(),
@@ -194,6 +283,7 @@ impl Routes {
     #[allow(unused_mut, reason = "conditional mutation")]
     fn set(&mut self, mut route: TelemetryServerRoute) -> BootstrapResult<()> {
         let handler = Arc::from(route.handler);
+        let original_path: Arc<str> = route.path.as_str().into();
 
         #[cfg(not(feature = "telemetry-server-pattern-routing"))]
         {
@@ -202,11 +292,10 @@ impl Routes {
         }
 
         for method in route.methods {
-            let res = self
-                .0
-                .entry(method)
-                .or_default()
-                .insert(route.path.clone(), Arc::clone(&handler));
+            let res = self.by_method.entry(method).or_default().insert(
+                route.path.clone(),
+                (Arc::clone(&original_path), Arc::clone(&handler)),
+            );
 
             match res {
                 Ok(()) => {}
@@ -217,14 +306,22 @@ impl Routes {
             }
         }
 
+        self.paths.insert(original_path.to_string());
+
         Ok(())
     }
 }
 
+/// Route reported in the metrics for the requests that don't match any route.
+#[cfg(feature = "metrics")]
+static UNMATCHED_ROUTE: std::sync::LazyLock<Arc<str>> =
+    std::sync::LazyLock::new(|| "unmatched".into());
+
 #[derive(Clone)]
 pub(super) struct Router {
     routes: Arc<Routes>,
     settings: Arc<TelemetrySettings>,
+    allowed_routes: Option<Arc<HashSet<String>>>,
 }
 
 impl Router {
@@ -233,37 +330,97 @@ impl Router {
         settings: Arc<TelemetrySettings>,
     ) -> BootstrapResult<Self> {
         Ok(Self {
-            routes: Arc::new(Routes::new(custom_routes)?),
+            routes: Arc::new(Routes::new(custom_routes, &settings)?),
             settings,
+            allowed_routes: None,
+        })
+    }
+
+    /// Returns a router that serves only the routes with the given paths.
+    pub(super) fn with_allowed_routes(&self, paths: &[String]) -> BootstrapResult<Self> {
+        if let Some(path) = paths.iter().find(|p| !self.routes.paths.contains(*p)) {
+            anyhow::bail!("unknown telemetry server route `{path}`");
+        }
+
+        Ok(Self {
+            allowed_routes: Some(Arc::new(paths.iter().cloned().collect())),
+            ..self.clone()
         })
     }
 
     async fn handle_request(&self, req: Request<Incoming>) -> Response<TelemetryRouteBody> {
+        let started_at = Instant::now();
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+
+        let (route, res) = self.route_request(req).await;
+        let duration = started_at.elapsed();
+
+        #[cfg(feature = "metrics")]
+        {
+            let route = route.unwrap_or_else(|| Arc::clone(&UNMATCHED_ROUTE));
+
+            server_metrics::requests_total(&route, res.status().as_u16()).inc();
+            server_metrics::request_duration_seconds(&route).observe(duration.as_secs_f64());
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        let _ = route;
+
+        if self.settings.server.access_log {
+            log::info!("telemetry server request";
+                "method" => %method,
+                "uri" => %uri,
+                "status" => res.status().as_u16(),
+                "duration_ms" => duration.as_millis() as u64
+            );
+        }
+
+        res
+    }
+
+    /// Routes the request to its handler, returning the path of the matched route along with
+    /// the response.
+    async fn route_request(
+        &self,
+        req: Request<Incoming>,
+    ) -> (Option<Arc<str>>, Response<TelemetryRouteBody>) {
         let res = Response::builder();
 
         let Ok(path) = percent_decode_str(req.uri().path()).decode_utf8() else {
-            return res
+            let res = res
                 .status(StatusCode::BAD_REQUEST)
                 .body(BoxBody::new(
                     Full::from("can't percent-decode URI path as valid UTF-8").map_err(Into::into),
                 ))
                 .unwrap();
+
+            return (None, res);
         };
 
-        let Some(handler) = self
+        let Some((route_path, handler)) = self
             .routes
-            .0
+            .by_method
             .get(req.method())
             .and_then(|r| Some(r.at(&path).ok()?.value))
+            .filter(|(route_path, _)| {
+                self.allowed_routes
+                    .as_ref()
+                    .is_none_or(|allowed| allowed.contains(&**route_path))
+            })
         else {
-            return res
+            let res = res
                 .status(StatusCode::NOT_FOUND)
                 .body(BoxBody::new(Empty::new().map_err(Into::into)))
                 .unwrap();
+
+            return (None, res);
         };
 
+        let route_path = Arc::clone(route_path);
+
         match (handler)(req, Arc::clone(&self.settings)).await {
-            Ok(res) => res,
+            Ok(res) => (Some(route_path), res),
             Err(e) => match e {},
         }
     }
@@ -281,6 +438,14 @@ impl Service<Request<Incoming>> for Router {
     }
 }
 
+#[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+fn bad_request(msg: String) -> Result<Response<TelemetryRouteBody>, Infallible> {
+    Ok(Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(BoxBody::new(Full::from(msg).map_err(Into::into)))
+        .unwrap())
+}
+
 fn into_response(
     content_type: &str,
     res: crate::Result<impl Into<Full<Bytes>>>,
@@ -0,0 +1,48 @@
+#[cfg(feature = "settings")]
+use crate::settings::settings;
+
+/// CPU profiler settings.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, serde::Deserialize))]
+pub struct CpuProfilerSettings {
+    /// Enables CPU profiling
+    pub enabled: bool,
+
+    /// Number of stack samples taken per second of consumed CPU time, in the range `[1, 1000]`.
+    ///
+    /// Higher frequencies increase profile fidelity, but also increase the overhead
+    /// of profiling.
+    ///
+    /// The default is `100`.
+    #[serde(default = "CpuProfilerSettings::default_sampling_frequency")]
+    pub sampling_frequency: u32,
+
+    /// Maximum duration of a single profile in seconds.
+    ///
+    /// Requests for longer profiles are rejected.
+    ///
+    /// The default is `300` (5 minutes).
+    #[serde(default = "CpuProfilerSettings::default_max_duration_secs")]
+    pub max_duration_secs: u64,
+}
+
+#[cfg(not(feature = "settings"))]
+impl Default for CpuProfilerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sampling_frequency: CpuProfilerSettings::default_sampling_frequency(),
+            max_duration_secs: CpuProfilerSettings::default_max_duration_secs(),
+        }
+    }
+}
+
+impl CpuProfilerSettings {
+    fn default_sampling_frequency() -> u32 {
+        100
+    }
+
+    fn default_max_duration_secs() -> u64 {
+        300
+    }
+}
@@ -0,0 +1,138 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[cfg(feature = "settings")]
+use crate::settings::settings;
+
+/// Log forwarding output settings.
+///
+/// Log records are sent to a log shipping agent, such as [Fluent Bit] or [Vector], over a
+/// stream socket.
+///
+/// [Fluent Bit]: https://fluentbit.io/
+/// [Vector]: https://vector.dev/
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
+pub struct ForwardOutputSettings {
+    /// Address of the log shipping agent.
+    ///
+    /// # Default
+    ///
+    /// Default value is `127.0.0.1:24224`, the default port of the Fluent Forward protocol.
+    pub addr: ForwardAddr,
+
+    /// Protocol used to send the log records.
+    pub protocol: ForwardProtocol,
+
+    /// Tag of the [`ForwardProtocol::FluentForward`] messages.
+    ///
+    /// # Default
+    ///
+    /// Default value is the service name.
+    pub tag: Option<String>,
+
+    /// Whether to wait for the agent to acknowledge each [`ForwardProtocol::FluentForward`]
+    /// message.
+    ///
+    /// The records are batched in the messages, so a single acknowledgement covers all the
+    /// records logged since the previous one. Messages that are not acknowledged in
+    /// [`ForwardOutputSettings::timeout_ms`] are resent after reconnecting, so they can be
+    /// delivered more than once. Ignored for [`ForwardProtocol::NdJson`].
+    pub ack: bool,
+
+    /// Timeout of the writes to the agent and of the acknowledgements, in milliseconds.
+    ///
+    /// # Default
+    ///
+    /// Default value is 5000.
+    pub timeout_ms: u64,
+
+    /// Maximum number of the records kept in memory while the agent is unavailable.
+    ///
+    /// The oldest records are dropped once the limit is reached. The kept records are sent
+    /// before the new ones once the connection is reestablished.
+    ///
+    /// # Default
+    ///
+    /// Default value is 10000.
+    pub spill_buffer_size: usize,
+
+    /// Delay before the first reconnection attempt, in milliseconds.
+    ///
+    /// The delay is doubled after each failed attempt, up to
+    /// [`ForwardOutputSettings::reconnect_max_backoff_ms`].
+    ///
+    /// # Default
+    ///
+    /// Default value is 100.
+    pub reconnect_min_backoff_ms: u64,
+
+    /// Maximum delay between the reconnection attempts, in milliseconds.
+    ///
+    /// # Default
+    ///
+    /// Default value is 30000.
+    pub reconnect_max_backoff_ms: u64,
+}
+
+impl Default for ForwardOutputSettings {
+    fn default() -> Self {
+        Self {
+            addr: Default::default(),
+            protocol: Default::default(),
+            tag: None,
+            ack: false,
+            timeout_ms: 5000,
+            spill_buffer_size: 10000,
+            reconnect_min_backoff_ms: 100,
+            reconnect_max_backoff_ms: 30000,
+        }
+    }
+}
+
+/// Address of a log shipping agent.
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
+pub enum ForwardAddr {
+    /// TCP socket address.
+    Tcp(SocketAddr),
+    /// Unix domain stream socket with the specified path.
+    Unix(PathBuf),
+}
+
+impl Default for ForwardAddr {
+    fn default() -> Self {
+        Self::Tcp(([127, 0, 0, 1], 24224).into())
+    }
+}
+
+/// Protocol used to send the log records to a log shipping agent.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy, PartialEq, Eq)]
+pub enum ForwardProtocol {
+    /// [Fluent Forward] protocol in the forward mode.
+    ///
+    /// Log record fields are written as the record map entries along with the `level` and
+    /// `msg` entries. [`LogFormat`] is ignored for this protocol.
+    ///
+    /// [Fluent Forward]: https://github.com/fluent/fluentd/wiki/Forward-Protocol-Specification-v1
+    /// [`LogFormat`]: crate::telemetry::settings::LogFormat
+    #[default]
+    FluentForward,
+    /// Records formatted according to [`LogFormat`], separated by new lines.
+    ///
+    /// Produces [newline-delimited JSON] with any of the JSON log formats. Can't be used with
+    /// [`LogFormat::Text`].
+    ///
+    /// [`LogFormat`]: crate::telemetry::settings::LogFormat
+    /// [`LogFormat::Text`]: crate::telemetry::settings::LogFormat::Text
+    /// [newline-delimited JSON]: https://github.com/ndjson/ndjson-spec
+    NdJson,
+}
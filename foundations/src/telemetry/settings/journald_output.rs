@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+#[cfg(feature = "settings")]
+use crate::settings::settings;
+
+/// [journald] output settings.
+///
+/// [journald]: https://www.freedesktop.org/software/systemd/man/latest/systemd-journald.service.html
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, serde::Deserialize))]
+pub struct JournaldOutputSettings {
+    /// Path of the journald native protocol socket.
+    ///
+    /// # Default
+    ///
+    /// Default value is `/run/systemd/journal/socket`.
+    #[serde(default = "JournaldOutputSettings::default_socket_path")]
+    pub socket_path: PathBuf,
+
+    /// Value of the `SYSLOG_IDENTIFIER` journal field.
+    ///
+    /// # Default
+    ///
+    /// Default value is the service name.
+    #[serde(default)]
+    pub syslog_identifier: Option<String>,
+}
+
+#[cfg(not(feature = "settings"))]
+impl Default for JournaldOutputSettings {
+    fn default() -> Self {
+        Self {
+            socket_path: JournaldOutputSettings::default_socket_path(),
+            syslog_identifier: None,
+        }
+    }
+}
+
+impl JournaldOutputSettings {
+    fn default_socket_path() -> PathBuf {
+        "/run/systemd/journal/socket".into()
+    }
+}
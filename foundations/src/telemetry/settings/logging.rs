@@ -1,4 +1,11 @@
+use crate::telemetry::settings::forward_output::ForwardOutputSettings;
 use crate::telemetry::settings::rate_limit::RateLimitingSettings;
+use crate::telemetry::settings::syslog_output::SyslogOutputSettings;
+
+#[cfg(feature = "telemetry-otlp-grpc")]
+use crate::telemetry::settings::OpenTelemetryGrpcOutputSettings;
+#[cfg(target_os = "linux")]
+use crate::telemetry::settings::journald_output::JournaldOutputSettings;
 use crate::utils::feature_use;
 
 use std::path::PathBuf;
@@ -27,18 +34,69 @@ pub struct LoggingSettings {
     /// Specifies log output.
     pub output: LogOutput,
 
+    /// Outputs the log records are written to in addition to [`LoggingSettings::output`].
+    ///
+    /// Each output has its own format, verbosity and redaction rules, while the other settings
+    /// are shared with the main output. For example, to write all the records to a JSON file
+    /// and only the `INFO` and more severe ones to stderr as text, set the verbosity to
+    /// [`LogVerbosity::Debug`] for the file output and add the stderr output with
+    /// [`LogVerbosity::Info`].
+    ///
+    /// The OpenTelemetry gRPC output can't be used as an additional output.
+    pub additional_outputs: Vec<LogOutputSettings>,
+
+    /// Log file rotation settings.
+    ///
+    /// Only applies to [`LogOutput::File`], including the additional outputs.
+    pub rotation: LogRotationSettings,
+
+    /// A signal that makes the file output reopen the log file when received by the process.
+    ///
+    /// Useful with external log rotation tools, e.g. [logrotate] with `postrotate kill -HUP`.
+    /// Only applies to [`LogOutput::File`]. Not set by default.
+    ///
+    /// The initialization fails if another handler is already installed for the signal or if
+    /// the signal is also used for the thread dumps.
+    ///
+    /// [logrotate]: https://man7.org/linux/man-pages/man8/logrotate.8.html
+    #[cfg(unix)]
+    pub reopen_signal: Option<LogReopenSignal>,
+
     /// The format to use for log messages.
     pub format: LogFormat,
 
+    /// Format of the log record timestamps.
+    ///
+    /// Only applies to [`LogOutput::Terminal`], [`LogOutput::Stderr`] and [`LogOutput::File`].
+    pub timestamp: LogTimestampSettings,
+
     /// Set the logging verbosity level.
     pub verbosity: LogVerbosity,
 
+    /// Per-module verbosity directives in the [`RUST_LOG`]-style format, e.g.
+    /// `my_service::proxy=debug,hyper=warn`.
+    ///
+    /// The modules that don't match any of the directives use [`LoggingSettings::verbosity`],
+    /// unless the directives contain a level without a module path. See
+    /// [`VerbosityDirectives`] for the details of the format.
+    ///
+    /// [`RUST_LOG`]: https://docs.rs/env_logger/latest/env_logger/#enabling-logging
+    /// [`VerbosityDirectives`]: crate::telemetry::log::VerbosityDirectives
+    pub verbosity_directives: String,
+
     /// A list of field keys to redact when emitting logs.
     ///
     /// This might be useful to hide certain fields in production logs as they may
     /// contain sensitive information, but allow them in testing environment.
     pub redact_keys: Vec<String>,
 
+    /// Redaction of the sensitive data in log records.
+    ///
+    /// Unlike [`LoggingSettings::redact_keys`], allows matching the fields by key patterns and
+    /// values, and masking or hashing the values instead of removing the fields.
+    #[cfg(feature = "log-redaction")]
+    pub redaction: LogRedactionSettings,
+
     /// Whether to ignore I/O errors from built-in log outputs.
     ///
     /// By default, I/O errors from terminal, stderr, and file outputs are treated
@@ -46,11 +104,74 @@ pub struct LoggingSettings {
     /// running if a log sink returns an error such as [`std::io::ErrorKind::BrokenPipe`].
     pub ignore_io_errors: bool,
 
+    /// Settings of the channel that passes the log records to the output thread.
+    pub channel: LogChannelSettings,
+
     /// Settings for rate limiting emission of log events
     pub rate_limit: RateLimitingSettings,
 
+    /// Settings for rate limiting emission of log events from each source separately.
+    ///
+    /// Applied before [`LoggingSettings::rate_limit`], so a single noisy source can't exhaust
+    /// the global limit for the others.
+    pub source_rate_limit: LogSourceRateLimitingSettings,
+
+    /// Settings for collapsing runs of identical log records.
+    pub collapse_repeated: LogCollapseSettings,
+
+    /// Settings for probabilistic sampling of log records by their level.
+    ///
+    /// Applied before [`LoggingSettings::rate_limit`], so the dropped records don't consume the
+    /// rate limiting budget.
+    #[cfg(feature = "log-sampling")]
+    pub sampling: LogSamplingSettings,
+
     /// Configure log volume metrics.
     pub log_volume_metrics: LogVolumeMetricSettings,
+
+    /// Configure the flight recorder of the recent log records filtered out by the verbosity.
+    pub flight_recorder: LogFlightRecorderSettings,
+
+    /// Configure correlation of log records with traces.
+    #[cfg(feature = "tracing")]
+    pub trace_correlation: LogTraceCorrelationSettings,
+
+    /// Configure capturing the log records that dependencies emit through other logging
+    /// libraries.
+    #[cfg(any(feature = "log-bridge", feature = "tracing-bridge"))]
+    pub bridge: LogBridgeSettings,
+}
+
+/// Settings of an additional log output.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+pub struct LogOutputSettings {
+    /// Specifies log output.
+    pub output: LogOutput,
+
+    /// The format to use for log messages.
+    pub format: LogFormat,
+
+    /// The most verbose level of the records written to the output.
+    ///
+    /// The records are filtered by [`LoggingSettings::verbosity`] and
+    /// [`LoggingSettings::verbosity_directives`] first, so the output can't be more verbose
+    /// than that.
+    ///
+    /// # Default
+    ///
+    /// All the records that pass the [`LoggingSettings`] verbosity are written by default.
+    pub verbosity: Option<LogVerbosity>,
+
+    /// A list of field keys to redact in the records written to the output, in addition to
+    /// [`LoggingSettings::redact_keys`].
+    pub redact_keys: Vec<String>,
+
+    /// Redaction of the sensitive data in the records written to the output.
+    ///
+    /// Applied after [`LoggingSettings::redaction`].
+    #[cfg(feature = "log-redaction")]
+    pub redaction: LogRedactionSettings,
 }
 
 /// Log output destination.
@@ -67,9 +188,51 @@ pub enum LogOutput {
     Stderr,
     /// Write log to file with the specified path.
     ///
-    /// File will be created if it doesn't exist and overwritten otherwise.
+    /// File will be created if it doesn't exist and overwritten otherwise. If
+    /// [`LoggingSettings::rotation`] is enabled, an existing file is appended to instead.
     File(PathBuf),
 
+    /// Write log to [syslog].
+    ///
+    /// [`LogFormat`] is ignored for this variant, the format is specified in the syslog
+    /// settings instead.
+    ///
+    /// [syslog]: https://datatracker.ietf.org/doc/html/rfc5424
+    Syslog(SyslogOutputSettings),
+
+    /// Write log to [journald] using its native protocol.
+    ///
+    /// Log record fields are written as journal fields with the names converted to uppercase.
+    /// [`LogFormat`] is ignored for this variant.
+    ///
+    /// [journald]: https://www.freedesktop.org/software/systemd/man/latest/systemd-journald.service.html
+    #[cfg(target_os = "linux")]
+    Journald(JournaldOutputSettings),
+
+    /// Sends log records to a log shipping agent, such as [Fluent Bit] or [Vector], using the
+    /// [Fluent Forward] protocol or as newline-delimited records.
+    ///
+    /// The connection is reestablished with an exponential backoff if it's lost. Records are
+    /// kept in a bounded in-memory buffer while the agent is unavailable.
+    ///
+    /// [Fluent Bit]: https://fluentbit.io/
+    /// [Vector]: https://vector.dev/
+    /// [Fluent Forward]: https://github.com/fluent/fluentd/wiki/Forward-Protocol-Specification-v1
+    Forward(ForwardOutputSettings),
+
+    /// Sends log records to the collector in the [Open Telemetry] format over [gRPC].
+    ///
+    /// Records are exported in batches. Records emitted inside a sampled span carry the span's
+    /// trace and span IDs, so they can be correlated with the traces. [`LogFormat`] is ignored
+    /// for this variant.
+    ///
+    /// Note that records are dropped if they are emitted faster than they can be exported.
+    ///
+    /// [Open Telemetry]: https://opentelemetry.io/docs/specs/otel/logs/
+    /// [gRPC]: https://grpc.io/
+    #[cfg(feature = "telemetry-otlp-grpc")]
+    OpenTelemetryGrpc(OpenTelemetryGrpcOutputSettings),
+
     ///Install a logging drain that forwards to `tracing-rs`
     ///
     ///WARN: If this output format is used, the settings in [`LoggingSettings`] other than the
@@ -112,6 +275,14 @@ impl std::fmt::Debug for LogOutput {
             Self::Terminal => write!(f, "Terminal"),
             Self::Stderr => write!(f, "Stderr"),
             Self::File(path) => f.debug_tuple("File").field(path).finish(),
+            Self::Syslog(settings) => f.debug_tuple("Syslog").field(settings).finish(),
+            #[cfg(target_os = "linux")]
+            Self::Journald(settings) => f.debug_tuple("Journald").field(settings).finish(),
+            Self::Forward(settings) => f.debug_tuple("Forward").field(settings).finish(),
+            #[cfg(feature = "telemetry-otlp-grpc")]
+            Self::OpenTelemetryGrpc(settings) => {
+                f.debug_tuple("OpenTelemetryGrpc").field(settings).finish()
+            }
             #[cfg(feature = "tracing-rs-compat")]
             Self::TracingRsCompat => write!(f, "TracingRsCompat"),
             #[cfg(feature = "logging")]
@@ -130,6 +301,77 @@ pub enum LogFormat {
     Text,
     /// JSON
     Json,
+    /// [logfmt], i.e. a line of `key=value` pairs per record.
+    ///
+    /// Values containing spaces, quotes, `=` or control characters are quoted and escaped.
+    ///
+    /// [logfmt]: https://brandur.org/logfmt
+    Logfmt,
+    /// JSON with the [Elastic Common Schema] field names.
+    ///
+    /// [Elastic Common Schema]: https://www.elastic.co/guide/en/ecs/current/index.html
+    Ecs,
+    /// JSON in the [Graylog Extended Log Format], separated by new lines.
+    ///
+    /// Log record fields are written as additional fields, i.e. with the `_` prefix.
+    ///
+    /// [Graylog Extended Log Format]: https://go2docs.graylog.org/current/getting_in_log_data/gelf.html
+    Gelf,
+}
+
+/// Log record timestamp settings.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+pub struct LogTimestampSettings {
+    /// Precision of the timestamp fractional seconds.
+    ///
+    /// # Default
+    ///
+    /// Default value is [`LogTimestampPrecision::Millis`].
+    ///
+    /// Unless either the precision or the [`timezone`] is specified, [`LogFormat::Json`] keeps
+    /// the UTC timestamps with the full precision of the system clock.
+    ///
+    /// [`timezone`]: LogTimestampSettings::timezone
+    pub precision: Option<LogTimestampPrecision>,
+
+    /// Time zone of the timestamp.
+    ///
+    /// Not used by [`LogFormat::Gelf`], which uses Unix timestamps.
+    ///
+    /// # Default
+    ///
+    /// Default value is [`LogTimezone::Local`] for [`LogFormat::Text`] and [`LogTimezone::Utc`]
+    /// for the other formats.
+    pub timezone: Option<LogTimezone>,
+}
+
+/// Precision of the log record timestamps.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum LogTimestampPrecision {
+    /// Whole seconds.
+    Seconds,
+    /// Milliseconds.
+    #[default]
+    Millis,
+    /// Microseconds.
+    Micros,
+    /// Nanoseconds.
+    Nanos,
+}
+
+/// Time zone of the log record timestamps.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum LogTimezone {
+    /// Local time zone of the system.
+    #[default]
+    Local,
+    /// UTC.
+    Utc,
 }
 
 /// Log verbosity levels which match 1:1 with [`slog::Level`].
@@ -138,7 +380,7 @@ pub enum LogFormat {
     settings(crate_path = "crate", impl_default = false)
 )]
 #[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
-#[derive(Copy, Default)]
+#[derive(Copy, Default, PartialEq, Eq)]
 pub enum LogVerbosity {
     /// See [`slog::Level::Critical`].
     #[cfg_attr(feature = "settings", serde(rename = "CRITICAL"))]
@@ -187,6 +429,486 @@ impl From<LogVerbosity> for slog::Level {
     }
 }
 
+/// Log file rotation settings.
+///
+/// The file is rotated once it reaches [`LogRotationSettings::max_size_bytes`] or once
+/// [`LogRotationSettings::interval_seconds`] have elapsed since it was opened, whichever comes
+/// first. Rotation is disabled if neither of the limits is set.
+///
+/// On rotation the current file is renamed to `<file name>.<unix timestamp in milliseconds>`
+/// and a new file is created at the original path. Compression and removal of the rotated files
+/// happen on a background thread, so logging is not blocked while they are in progress.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogRotationSettings {
+    /// Rotate the file once its size reaches the specified number of bytes.
+    pub max_size_bytes: Option<u64>,
+
+    /// Rotate the file once the specified number of seconds have elapsed since it was opened.
+    pub interval_seconds: Option<u64>,
+
+    /// Whether to compress the rotated files with gzip.
+    ///
+    /// Compressed files get an additional `.gz` extension.
+    pub compress: bool,
+
+    /// Maximum number of the rotated files to retain. The oldest files are removed first.
+    pub max_files: Option<usize>,
+
+    /// Maximum age of the rotated files in seconds. Older files are removed.
+    pub max_age_seconds: Option<u64>,
+}
+
+impl LogRotationSettings {
+    /// Returns `true` if the rotation is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.max_size_bytes.is_some() || self.interval_seconds.is_some()
+    }
+}
+
+/// A signal that triggers log file reopening.
+#[cfg(unix)]
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
+#[derive(Copy, Default)]
+pub enum LogReopenSignal {
+    /// `SIGHUP`
+    #[default]
+    #[cfg_attr(feature = "settings", serde(rename = "SIGHUP"))]
+    Sighup,
+    /// `SIGUSR1`
+    #[cfg_attr(feature = "settings", serde(rename = "SIGUSR1"))]
+    Sigusr1,
+    /// `SIGUSR2`
+    #[cfg_attr(feature = "settings", serde(rename = "SIGUSR2"))]
+    Sigusr2,
+}
+
+#[cfg(all(unix, feature = "logging"))]
+impl LogReopenSignal {
+    pub(crate) fn signum(self) -> libc::c_int {
+        match self {
+            LogReopenSignal::Sighup => libc::SIGHUP,
+            LogReopenSignal::Sigusr1 => libc::SIGUSR1,
+            LogReopenSignal::Sigusr2 => libc::SIGUSR2,
+        }
+    }
+}
+
+/// Settings for redaction of the sensitive data in log records.
+///
+/// Rules are applied to every record field in order. A rule matches a field if both its key
+/// and value patterns match, with the missing patterns matching anything. Rules that only have a
+/// value pattern also apply to record messages.
+#[cfg(feature = "log-redaction")]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogRedactionSettings {
+    /// Redaction rules.
+    pub rules: Vec<LogRedactionRule>,
+
+    /// A 128-bit secret key for [`LogRedactionAction::Hash`] as 32 hexadecimal digits.
+    ///
+    /// Must be set if any of the rules use hashing. The same value always produces the same
+    /// hash with the same key, so the redacted values can still be correlated across records.
+    pub hash_key: String,
+}
+
+/// A rule for redaction of the sensitive data in log records.
+#[cfg(feature = "log-redaction")]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogRedactionRule {
+    /// A glob pattern for the field keys, e.g. `*password*`.
+    ///
+    /// `*` matches any sequence of characters and `?` matches any single character. Can't be
+    /// used together with [`LogRedactionRule::key_regex`].
+    pub key_glob: Option<String>,
+
+    /// A [regular expression] for the field keys.
+    ///
+    /// Can't be used together with [`LogRedactionRule::key_glob`].
+    ///
+    /// [regular expression]: https://docs.rs/regex/latest/regex/#syntax
+    pub key_regex: Option<String>,
+
+    /// A predefined pattern for the sensitive data in the values.
+    ///
+    /// Can't be used together with [`LogRedactionRule::value_regex`].
+    pub value_pattern: Option<LogRedactionPattern>,
+
+    /// A [regular expression] for the sensitive data in the values.
+    ///
+    /// Can't be used together with [`LogRedactionRule::value_pattern`].
+    ///
+    /// [regular expression]: https://docs.rs/regex/latest/regex/#syntax
+    pub value_regex: Option<String>,
+
+    /// Action to apply to the matching data.
+    ///
+    /// If the rule has a value pattern, only the matching parts of the value are masked or
+    /// hashed. Otherwise, the whole value is.
+    pub action: LogRedactionAction,
+}
+
+/// Predefined patterns for the sensitive data in log records.
+#[cfg(feature = "log-redaction")]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum LogRedactionPattern {
+    /// Email addresses.
+    #[default]
+    Email,
+    /// Bearer tokens, e.g. in `Authorization` header values.
+    BearerToken,
+    /// Payment card numbers, optionally with space or dash separators. Only the numbers that
+    /// pass the [Luhn] check are matched.
+    ///
+    /// [Luhn]: https://en.wikipedia.org/wiki/Luhn_algorithm
+    CardNumber,
+}
+
+/// Action to apply to the sensitive data in log records.
+#[cfg(feature = "log-redaction")]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum LogRedactionAction {
+    /// Remove the field from the record.
+    ///
+    /// Record messages can't be removed, so the matching parts of the messages are masked
+    /// instead.
+    Drop,
+    /// Replace the data with `****`.
+    #[default]
+    Mask,
+    /// Replace the data with its keyed hash, e.g. `hash:5cd4a3f1e2b7c8d9`.
+    ///
+    /// See [`LogRedactionSettings::hash_key`].
+    Hash,
+}
+
+/// Settings for rate limiting of log records from each source separately.
+///
+/// Each source gets a separate budget for each log level. Once the records from a source are
+/// allowed again after being suppressed, a summary record with the number of the suppressed
+/// records is emitted before the next record from the source. If the source doesn't emit any
+/// more records, the summary is emitted with the next record from any source, without the
+/// fields of the suppressed records.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogSourceRateLimitingSettings {
+    /// Whether to enable rate limiting of log records per source.
+    pub enabled: bool,
+
+    /// How the sources of the log records are determined.
+    pub source: LogRateLimitSource,
+
+    /// Maximum number of records per second from each source for the levels that don't have
+    /// their own limit in [`LogSourceRateLimitingSettings::level_max_events_per_second`].
+    ///
+    /// `0` disables the limit.
+    pub max_events_per_second: u32,
+
+    /// Maximum number of records per second from each source for specific levels.
+    pub level_max_events_per_second: LogLevelRateLimits,
+}
+
+/// Settings for collapsing runs of identical log records.
+///
+/// Records are identical if they have the same level, message and fields, including the
+/// context fields. Only the first record of a run of consecutive identical records logged by a
+/// thread is emitted, the records of the other threads don't break the run. Once the run ends,
+/// a `last message repeated N times` record with a `repeated_count` field is emitted before the
+/// next record.
+///
+/// A run ends when a different record is logged by the thread or when an identical record is
+/// logged after [`LogCollapseSettings::window_ms`] since the start of the run. In the latter
+/// case the record is emitted and starts a new run, so the repeated records are still reported
+/// periodically. The summaries of the runs that haven't ended by then are emitted with the
+/// records of the other threads once the window expires.
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
+pub struct LogCollapseSettings {
+    /// Whether to collapse runs of identical log records.
+    pub enabled: bool,
+
+    /// Maximum duration of a run of collapsed records in milliseconds.
+    ///
+    /// # Default
+    ///
+    /// Default value is `10000`.
+    pub window_ms: u64,
+}
+
+impl Default for LogCollapseSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_ms: 10000,
+        }
+    }
+}
+
+/// Settings for probabilistic sampling of log records by their level.
+///
+/// Records of each level are kept with the probability specified for the level, e.g. `0.01`
+/// keeps 1% of the records. The kept records get a `sample_rate` field with the probability,
+/// so the original number of the records can be estimated. Records of the levels without a
+/// sample rate are always kept and don't get the field.
+///
+/// If tracing is enabled, the sampling decision for the records emitted inside a span is made
+/// once per trace using its ID, so the records of a trace are either kept or dropped together.
+/// The records of a trace that are kept with some rate are also kept with all the higher rates.
+#[cfg(feature = "log-sampling")]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogSamplingSettings {
+    /// Whether to enable sampling of log records.
+    pub enabled: bool,
+
+    /// Probabilities of keeping the records of each level, from `0.0` to `1.0`.
+    pub level_sample_rates: LogLevelSampleRates,
+}
+
+/// Per-level probabilities of keeping log records, from `0.0` to `1.0`. All the records of the
+/// levels without a sample rate are kept.
+#[cfg(feature = "log-sampling")]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogLevelSampleRates {
+    /// Sample rate of [`slog::Level::Critical`] records.
+    pub critical: Option<f64>,
+
+    /// Sample rate of [`slog::Level::Error`] records.
+    pub error: Option<f64>,
+
+    /// Sample rate of [`slog::Level::Warning`] records.
+    pub warning: Option<f64>,
+
+    /// Sample rate of [`slog::Level::Info`] records.
+    pub info: Option<f64>,
+
+    /// Sample rate of [`slog::Level::Debug`] records.
+    pub debug: Option<f64>,
+
+    /// Sample rate of [`slog::Level::Trace`] records.
+    pub trace: Option<f64>,
+}
+
+/// Source of log records for rate limiting.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum LogRateLimitSource {
+    /// The module and line of the code that emits the record.
+    #[default]
+    Callsite,
+    /// The message template of the record, so the same messages from different callsites share
+    /// the budget.
+    ///
+    /// Templates of the messages with formatting arguments are not available at runtime, so
+    /// such messages are limited by their callsite instead.
+    MessageTemplate,
+}
+
+/// Per-level limits of the log records emitted per second. `0` disables the limit.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogLevelRateLimits {
+    /// Limit for [`slog::Level::Critical`] records.
+    pub critical: Option<u32>,
+
+    /// Limit for [`slog::Level::Error`] records.
+    pub error: Option<u32>,
+
+    /// Limit for [`slog::Level::Warning`] records.
+    pub warning: Option<u32>,
+
+    /// Limit for [`slog::Level::Info`] records.
+    pub info: Option<u32>,
+
+    /// Limit for [`slog::Level::Debug`] records.
+    pub debug: Option<u32>,
+
+    /// Limit for [`slog::Level::Trace`] records.
+    pub trace: Option<u32>,
+}
+
+/// Settings of the log flight recorder.
+///
+/// The flight recorder keeps the recent records that were filtered out by the verbosity in a
+/// bounded in-memory buffer, so the context preceding a failure can be logged without running
+/// with high verbosity all the time. The buffered records are emitted:
+/// - when an error or critical record is logged in the same scope;
+/// - when a panic occurs;
+/// - when requested by the `/debug/flight_recorder` telemetry server endpoint;
+/// - when [`flush_flight_recorder`] is called.
+///
+/// The emitted records get a `recorded_at` field with the time they were originally logged at.
+///
+/// [`flush_flight_recorder`]: crate::telemetry::log::flush_flight_recorder
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
+pub struct LogFlightRecorderSettings {
+    /// Whether to enable the flight recorder.
+    pub enabled: bool,
+
+    /// Maximum number of records kept in each buffer.
+    ///
+    /// With the [`LogFlightRecorderScope::Global`] scope, this is the total number of the records
+    /// kept by all the threads, and the oldest records are discarded first. It can be briefly
+    /// exceeded by the number of the threads that record concurrently.
+    ///
+    /// With the [`LogFlightRecorderScope::Context`] scope, each context keeps up to this number
+    /// of records, and the oldest records of the context are discarded first.
+    ///
+    /// # Default
+    ///
+    /// Default value is `1000`.
+    pub capacity: usize,
+
+    /// The most verbose level of the recorded records.
+    ///
+    /// # Default
+    ///
+    /// Default value is [`LogVerbosity::Debug`].
+    pub verbosity: LogVerbosity,
+
+    /// Scope of the record buffers.
+    pub scope: LogFlightRecorderScope,
+}
+
+impl Default for LogFlightRecorderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 1000,
+            verbosity: LogVerbosity::Debug,
+            scope: Default::default(),
+        }
+    }
+}
+
+/// Scope of the log flight recorder buffers.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum LogFlightRecorderScope {
+    /// A single buffer for the whole process.
+    #[default]
+    Global,
+    /// A separate buffer for each [`TelemetryContext`] with its own log, e.g. created with
+    /// [`TelemetryContext::with_forked_log`].
+    ///
+    /// [`TelemetryContext`]: crate::telemetry::TelemetryContext
+    /// [`TelemetryContext::with_forked_log`]: crate::telemetry::TelemetryContext::with_forked_log
+    Context,
+}
+
+/// Settings of capturing the log records emitted through other logging libraries.
+///
+/// The captured records are routed to the current log, so they get its fields and go through
+/// the verbosity filtering, redaction and rate limiting like the records of the foundations
+/// logger. Their module, level and key-value fields are preserved.
+#[cfg(any(feature = "log-bridge", feature = "tracing-bridge"))]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogBridgeSettings {
+    /// Whether to capture the records of the [`log`] crate by installing a global logger.
+    ///
+    /// The telemetry initialization fails if another logger is already installed. Use
+    /// [`LogCrateBridge`] to combine it with other loggers instead.
+    ///
+    /// [`log`]: https://docs.rs/log
+    /// [`LogCrateBridge`]: crate::telemetry::log::LogCrateBridge
+    #[cfg(feature = "log-bridge")]
+    pub log_crate: bool,
+
+    /// Whether to capture the events of the [`tracing`] crate by installing a global default
+    /// subscriber.
+    ///
+    /// The telemetry initialization fails if another global default subscriber is already
+    /// installed or if the `TracingRsCompat` log output is used, as it would forward the records
+    /// back. Use [`TracingBridgeLayer`] to add the bridge to your own subscriber instead.
+    ///
+    /// [`tracing`]: https://docs.rs/tracing
+    /// [`TracingBridgeLayer`]: crate::telemetry::log::TracingBridgeLayer
+    #[cfg(feature = "tracing-bridge")]
+    pub tracing_events: bool,
+}
+
+/// Settings of the channel that passes the log records to the output thread.
+///
+/// The records that can't be put in the channel are dropped and reported with a record
+/// containing the number of the dropped records, once there's space in the channel again.
+/// The number of the dropped records and the channel depth are also exposed as metrics
+/// labeled with the index of the output if the `metrics` feature is enabled. The main output
+/// has the index `0`, followed by the [`LoggingSettings::additional_outputs`].
+///
+/// The OpenTelemetry output has its own channel and only uses the [`LogChannelSettings::size`]
+/// from these settings.
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
+pub struct LogChannelSettings {
+    /// Maximum number of the log records in the channel.
+    ///
+    /// # Default
+    ///
+    /// Default value is `1024`.
+    pub size: usize,
+
+    /// What to do with the log records when the channel is full.
+    pub overflow: LogChannelOverflow,
+
+    /// Maximum time in milliseconds to wait for the space in the channel with
+    /// [`LogChannelOverflow::Block`], after which the record is dropped.
+    ///
+    /// # Default
+    ///
+    /// Default value is `100`.
+    pub block_timeout_ms: u64,
+}
+
+impl Default for LogChannelSettings {
+    fn default() -> Self {
+        Self {
+            size: 1024,
+            overflow: Default::default(),
+            block_timeout_ms: 100,
+        }
+    }
+}
+
+/// Overflow strategy of the log channel.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy, PartialEq, Eq)]
+pub enum LogChannelOverflow {
+    /// Drop the new records.
+    #[default]
+    DropNewest,
+    /// Keep the new records until there's space in the channel, dropping the oldest of them
+    /// once there are more than [`LogChannelSettings::size`] records waiting.
+    DropOldest,
+    /// Block the logging thread until there's space in the channel, for at most
+    /// [`LogChannelSettings::block_timeout_ms`].
+    Block,
+}
+
 /// Log volume metrics settings
 ///
 /// If enabled, a counter metric will be exposed as <app_name>_foundations_log_record_count
@@ -197,3 +919,26 @@ pub struct LogVolumeMetricSettings {
     /// Whether to enable log volume metrics
     pub enabled: bool,
 }
+
+/// Settings for correlating log records with traces.
+///
+/// If enabled, log records emitted inside a span get `trace_id` and `span_id` fields with the
+/// IDs of the span in the [W3C Trace Context] hex format. Records emitted outside of spans or in
+/// the spans that were not selected by the [sampling] don't get the fields, as such spans don't
+/// have IDs.
+///
+/// [W3C Trace Context]: https://www.w3.org/TR/trace-context/#traceparent-header
+/// [sampling]: crate::telemetry::settings::SamplingStrategy
+#[cfg(feature = "tracing")]
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct LogTraceCorrelationSettings {
+    /// Whether to add the trace and span IDs to log records.
+    pub enabled: bool,
+
+    /// Whether to also add the IDs of the spans that don't have the [sampled flag] set in their
+    /// trace context.
+    ///
+    /// [sampled flag]: https://www.w3.org/TR/trace-context/#sampled-flag
+    pub include_unsampled: bool,
+}
@@ -12,12 +12,27 @@ mod user_tracing;
 #[cfg(feature = "logging")]
 mod logging;
 
+#[cfg(feature = "logging")]
+mod syslog_output;
+
+#[cfg(feature = "logging")]
+mod forward_output;
+
+#[cfg(all(target_os = "linux", feature = "logging"))]
+mod journald_output;
+
 #[cfg(feature = "metrics")]
 mod metrics;
 
 #[cfg(all(target_os = "linux", feature = "memory-profiling"))]
 mod memory_profiler;
 
+#[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+mod cpu_profiler;
+
+#[cfg(all(target_os = "linux", feature = "thread-dump"))]
+mod thread_dump;
+
 #[cfg(any(feature = "logging", feature = "tracing"))]
 mod rate_limit;
 
@@ -36,12 +51,27 @@ pub use self::user_tracing::*;
 #[cfg(feature = "logging")]
 pub use self::logging::*;
 
+#[cfg(feature = "logging")]
+pub use self::syslog_output::*;
+
+#[cfg(feature = "logging")]
+pub use self::forward_output::*;
+
+#[cfg(all(target_os = "linux", feature = "logging"))]
+pub use self::journald_output::*;
+
 #[cfg(feature = "metrics")]
 pub use self::metrics::*;
 
 #[cfg(all(target_os = "linux", feature = "memory-profiling"))]
 pub use self::memory_profiler::*;
 
+#[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+pub use self::cpu_profiler::*;
+
+#[cfg(all(target_os = "linux", feature = "thread-dump"))]
+pub use self::thread_dump::*;
+
 #[cfg(any(feature = "logging", feature = "tracing"))]
 pub use self::rate_limit::RateLimitingSettings;
 
@@ -75,6 +105,14 @@ pub struct TelemetrySettings {
     #[cfg(all(target_os = "linux", feature = "memory-profiling"))]
     pub memory_profiler: MemoryProfilerSettings,
 
+    /// CPU profiler settings
+    #[cfg(all(target_os = "linux", feature = "cpu-profiling"))]
+    pub cpu_profiler: CpuProfilerSettings,
+
+    /// Thread dump settings
+    #[cfg(all(target_os = "linux", feature = "thread-dump"))]
+    pub thread_dump: ThreadDumpSettings,
+
     /// Server settings.
     #[cfg(feature = "telemetry-server")]
     pub server: TelemetryServerSettings,
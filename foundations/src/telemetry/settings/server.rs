@@ -1,3 +1,5 @@
+#[cfg(unix)]
+use crate::addr::InheritedListener;
 use crate::addr::ListenAddr;
 #[cfg(feature = "settings")]
 use crate::settings::settings;
@@ -13,6 +15,52 @@ pub struct TelemetryServerSettings {
     /// Telemetry server address.
     #[serde(default = "TelemetryServerSettings::default_addr")]
     pub addr: ListenAddr,
+
+    /// Listening socket inherited from the parent process, e.g. with systemd socket activation.
+    ///
+    /// If specified, the socket is used instead of binding to [`TelemetryServerSettings::addr`].
+    #[cfg(unix)]
+    #[serde(default)]
+    pub inherited_listener: Option<InheritedListener>,
+
+    /// Additional telemetry server listeners.
+    ///
+    /// The listeners are served alongside the listener on [`TelemetryServerSettings::addr`] and
+    /// can be restricted to a subset of the telemetry server routes. This allows, for example,
+    /// to expose only metrics over TCP, while serving all the routes on a Unix socket for
+    /// local tooling.
+    #[serde(default)]
+    pub additional_listeners: Vec<TelemetryServerListenerSettings>,
+
+    /// Maximum number of concurrent connections across all the listeners.
+    ///
+    /// Once the limit is reached, new connections are not accepted until some of the existing
+    /// connections are closed.
+    ///
+    /// The default is `256`.
+    #[serde(default = "TelemetryServerSettings::default_max_connections")]
+    pub max_connections: usize,
+
+    /// Timeout for reading request headers in seconds. `0` disables the timeout.
+    ///
+    /// The default is `10` seconds.
+    #[serde(default = "TelemetryServerSettings::default_header_read_timeout_seconds")]
+    pub header_read_timeout_seconds: u64,
+
+    /// Timeout in seconds after which connections without any activity are closed. `0` disables
+    /// the timeout.
+    ///
+    /// Connections are not considered idle while a request handler is running, even if it
+    /// doesn't do any I/O. Responses that are still being sent are given one more timeout period
+    /// without any activity to complete, after which the connection is closed regardless.
+    ///
+    /// The default is `60` seconds.
+    #[serde(default = "TelemetryServerSettings::default_idle_timeout_seconds")]
+    pub idle_timeout_seconds: u64,
+
+    /// Enables access logs for the telemetry server requests.
+    #[serde(default)]
+    pub access_log: bool,
 }
 
 #[cfg(not(feature = "settings"))]
@@ -21,6 +69,14 @@ impl Default for TelemetryServerSettings {
         Self {
             enabled: TelemetryServerSettings::default_enabled(),
             addr: ListenAddr::default(),
+            #[cfg(unix)]
+            inherited_listener: None,
+            additional_listeners: Default::default(),
+            max_connections: TelemetryServerSettings::default_max_connections(),
+            header_read_timeout_seconds:
+                TelemetryServerSettings::default_header_read_timeout_seconds(),
+            idle_timeout_seconds: TelemetryServerSettings::default_idle_timeout_seconds(),
+            access_log: false,
         }
     }
 }
@@ -33,4 +89,43 @@ impl TelemetryServerSettings {
     fn default_addr() -> ListenAddr {
         ListenAddr::default()
     }
+
+    fn default_max_connections() -> usize {
+        256
+    }
+
+    fn default_header_read_timeout_seconds() -> u64 {
+        10
+    }
+
+    fn default_idle_timeout_seconds() -> u64 {
+        60
+    }
+}
+
+/// Additional telemetry server listener settings.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(
+    not(feature = "settings"),
+    derive(Clone, Debug, Default, serde::Deserialize)
+)]
+pub struct TelemetryServerListenerSettings {
+    /// Listener address.
+    #[serde(default)]
+    pub addr: ListenAddr,
+
+    /// Listening socket inherited from the parent process, e.g. with systemd socket activation.
+    ///
+    /// If specified, the socket is used instead of binding to
+    /// [`TelemetryServerListenerSettings::addr`].
+    #[cfg(unix)]
+    #[serde(default)]
+    pub inherited_listener: Option<InheritedListener>,
+
+    /// Paths of the routes served by the listener, e.g. `/metrics`.
+    ///
+    /// Paths need to match the paths the routes have been registered with, including the
+    /// parameters for pattern-based routes. If not specified, all the routes are served.
+    #[serde(default)]
+    pub routes: Option<Vec<String>>,
 }
@@ -0,0 +1,171 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[cfg(feature = "settings")]
+use crate::settings::settings;
+
+/// [Syslog] output settings.
+///
+/// [Syslog]: https://datatracker.ietf.org/doc/html/rfc5424
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, Default))]
+pub struct SyslogOutputSettings {
+    /// Address of the syslog server.
+    ///
+    /// # Default
+    ///
+    /// Default value is the `/dev/log` Unix socket.
+    pub addr: SyslogAddr,
+
+    /// Syslog message format.
+    pub format: SyslogFormat,
+
+    /// Syslog facility of the messages.
+    pub facility: SyslogFacility,
+
+    /// Application name of the messages (`APP-NAME` in RFC 5424 and `TAG` in RFC 3164).
+    ///
+    /// # Default
+    ///
+    /// Default value is the service name.
+    pub app_name: Option<String>,
+
+    /// `SD-ID` of the [structured data] element that contains the log record fields.
+    ///
+    /// Only used with the [`SyslogFormat::Rfc5424`] format. The ID must be in the
+    /// `name@<private enterprise number>` form.
+    ///
+    /// # Default
+    ///
+    /// Default value is `fields@32473`, where `32473` is the private enterprise number reserved
+    /// for documentation use by [RFC 5612].
+    ///
+    /// [structured data]: https://datatracker.ietf.org/doc/html/rfc5424#section-6.3
+    /// [RFC 5612]: https://datatracker.ietf.org/doc/html/rfc5612
+    pub structured_data_id: Option<String>,
+}
+
+/// Address of a syslog server.
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug))]
+pub enum SyslogAddr {
+    /// Unix domain socket with the specified path.
+    ///
+    /// Both datagram and stream sockets are supported. Stream sockets use the same framing as
+    /// [`SyslogAddr::Tcp`].
+    Unix(PathBuf),
+    /// UDP socket address.
+    Udp(SocketAddr),
+    /// TCP socket address.
+    ///
+    /// [`SyslogFormat::Rfc5424`] messages are framed with the octet counting method of
+    /// [RFC 6587], while [`SyslogFormat::Rfc3164`] messages are terminated with a new line.
+    ///
+    /// [RFC 6587]: https://datatracker.ietf.org/doc/html/rfc6587#section-3.4.1
+    Tcp(SocketAddr),
+}
+
+impl Default for SyslogAddr {
+    fn default() -> Self {
+        Self::Unix("/dev/log".into())
+    }
+}
+
+/// Syslog message format.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum SyslogFormat {
+    /// [RFC 5424] format.
+    ///
+    /// Log record fields are written as structured data parameters.
+    ///
+    /// [RFC 5424]: https://datatracker.ietf.org/doc/html/rfc5424
+    #[default]
+    Rfc5424,
+    /// Legacy BSD [RFC 3164] format.
+    ///
+    /// Log record fields are appended to the message as `key=value` pairs.
+    ///
+    /// [RFC 3164]: https://datatracker.ietf.org/doc/html/rfc3164
+    Rfc3164,
+}
+
+/// Syslog facility.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Default, Debug))]
+#[derive(Copy)]
+pub enum SyslogFacility {
+    /// Kernel messages.
+    Kern,
+    /// User-level messages.
+    #[default]
+    User,
+    /// Mail system.
+    Mail,
+    /// System daemons.
+    Daemon,
+    /// Security/authorization messages.
+    Auth,
+    /// Messages generated internally by syslogd.
+    Syslog,
+    /// Line printer subsystem.
+    Lpr,
+    /// Network news subsystem.
+    News,
+    /// UUCP subsystem.
+    Uucp,
+    /// Clock daemon.
+    Cron,
+    /// Private security/authorization messages.
+    Authpriv,
+    /// FTP daemon.
+    Ftp,
+    /// Local use 0.
+    Local0,
+    /// Local use 1.
+    Local1,
+    /// Local use 2.
+    Local2,
+    /// Local use 3.
+    Local3,
+    /// Local use 4.
+    Local4,
+    /// Local use 5.
+    Local5,
+    /// Local use 6.
+    Local6,
+    /// Local use 7.
+    Local7,
+}
+
+impl SyslogFacility {
+    /// Returns the numerical code of the facility.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Kern => 0,
+            Self::User => 1,
+            Self::Mail => 2,
+            Self::Daemon => 3,
+            Self::Auth => 4,
+            Self::Syslog => 5,
+            Self::Lpr => 6,
+            Self::News => 7,
+            Self::Uucp => 8,
+            Self::Cron => 9,
+            Self::Authpriv => 10,
+            Self::Ftp => 11,
+            Self::Local0 => 16,
+            Self::Local1 => 17,
+            Self::Local2 => 18,
+            Self::Local3 => 19,
+            Self::Local4 => 20,
+            Self::Local5 => 21,
+            Self::Local6 => 22,
+            Self::Local7 => 23,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(feature = "settings")]
+use crate::settings::settings;
+
+/// Thread dump settings.
+#[cfg_attr(feature = "settings", settings(crate_path = "crate"))]
+#[cfg_attr(
+    not(feature = "settings"),
+    derive(Clone, Debug, Default, serde::Deserialize)
+)]
+pub struct ThreadDumpSettings {
+    /// Enables thread dumps
+    pub enabled: bool,
+
+    /// Offset from `SIGRTMAX` of the realtime signal used to interrupt the threads to capture
+    /// their stacks, e.g. `2` for `SIGRTMAX-2`.
+    ///
+    /// The signal must not be used by the service or the other libraries: the initialization
+    /// fails if a handler has already been installed for it.
+    ///
+    /// # Default
+    ///
+    /// Default value is 0, i.e. `SIGRTMAX` is used.
+    pub capture_signal_offset: u8,
+
+    /// A signal that writes a thread dump to the log when received by the process.
+    ///
+    /// The initialization fails if another handler is already installed for the signal or if
+    /// the signal is also used for the log file reopening. Not set by default.
+    pub log_signal: Option<ThreadDumpSignal>,
+}
+
+/// A signal that triggers a thread dump.
+#[cfg_attr(
+    feature = "settings",
+    settings(crate_path = "crate", impl_default = false)
+)]
+#[cfg_attr(not(feature = "settings"), derive(Clone, Debug, serde::Deserialize))]
+#[derive(Copy, Default)]
+pub enum ThreadDumpSignal {
+    /// `SIGQUIT`
+    #[default]
+    #[serde(rename = "SIGQUIT")]
+    Sigquit,
+    /// `SIGUSR1`
+    #[serde(rename = "SIGUSR1")]
+    Sigusr1,
+    /// `SIGUSR2`
+    #[serde(rename = "SIGUSR2")]
+    Sigusr2,
+}
+
+impl ThreadDumpSignal {
+    pub(crate) fn signum(self) -> libc::c_int {
+        match self {
+            ThreadDumpSignal::Sigquit => libc::SIGQUIT,
+            ThreadDumpSignal::Sigusr1 => libc::SIGUSR1,
+            ThreadDumpSignal::Sigusr2 => libc::SIGUSR2,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Signal handler installation shared by the telemetry components.
+
+use std::ffi::{c_int, c_void};
+use std::{io, ptr};
+
+/// A signal handler with the `SA_SIGINFO` signature.
+pub(super) type SignalHandler = extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void);
+
+/// Installs the signal handler. Fails if another handler has already been installed for the
+/// signal, so the handlers of the other libraries or of the service itself are not silently
+/// replaced.
+pub(super) fn install_signal_handler(signum: c_int, handler: SignalHandler) -> io::Result<()> {
+    unsafe {
+        let mut current: libc::sigaction = std::mem::zeroed();
+
+        if libc::sigaction(signum, ptr::null(), &mut current) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let current_handler = current.sa_sigaction;
+
+        if current_handler != libc::SIG_DFL
+            && current_handler != libc::SIG_IGN
+            && current_handler != handler as *const () as usize
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("another handler is already installed for signal {signum}"),
+            ));
+        }
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+
+        action.sa_sigaction = handler as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        if libc::sigaction(signum, &action, ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn handler(_signum: c_int, _info: *mut libc::siginfo_t, _ucontext: *mut c_void) {}
+
+    extern "C" fn other_handler(
+        _signum: c_int,
+        _info: *mut libc::siginfo_t,
+        _ucontext: *mut c_void,
+    ) {
+    }
+
+    #[test]
+    fn install_signal_handler_conflict() {
+        let signum = libc::SIGRTMIN() + 1;
+
+        install_signal_handler(signum, handler).unwrap();
+
+        // NOTE: reinstalling the same handler is allowed.
+        install_signal_handler(signum, handler).unwrap();
+
+        let err = install_signal_handler(signum, other_handler).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+}
@@ -0,0 +1,84 @@
+//! Async-signal-safe stack capturing shared by the signal-based stack samplers.
+
+use std::ffi::c_void;
+
+pub(super) const MAX_STACK_DEPTH: usize = 64;
+
+/// Preallocated storage for a single stack, so the signal handler doesn't need to allocate.
+pub(super) struct StackSlot {
+    depth: usize,
+    frames: [usize; MAX_STACK_DEPTH],
+}
+
+impl StackSlot {
+    pub(super) const fn new() -> Self {
+        Self {
+            depth: 0,
+            frames: [0; MAX_STACK_DEPTH],
+        }
+    }
+
+    /// Captures the stack of the interrupted thread. Must be called only from a signal handler
+    /// running on the interrupted thread. Async-signal-safe.
+    pub(super) fn capture(&mut self, interrupted_pc: usize) {
+        let mut depth = 0;
+
+        // NOTE: skip the frames of the signal handler itself: they precede the frame of the
+        // interrupted function. If the interrupted address is unknown, record the whole stack.
+        let mut reached_interrupted_frame = interrupted_pc == 0;
+
+        // SAFETY: the stack is traced only on the current thread and the callback doesn't allocate.
+        unsafe {
+            backtrace::trace_unsynchronized(|frame| {
+                let ip = frame.ip() as usize;
+
+                if !reached_interrupted_frame {
+                    reached_interrupted_frame = ip == interrupted_pc;
+
+                    if !reached_interrupted_frame {
+                        return true;
+                    }
+                }
+
+                self.frames[depth] = ip;
+                depth += 1;
+
+                depth < MAX_STACK_DEPTH
+            });
+        }
+
+        // NOTE: the unwinder might fail to step over the signal frame, in which case we still
+        // know the interrupted address.
+        if depth == 0 && interrupted_pc != 0 {
+            self.frames[0] = interrupted_pc;
+            depth = 1;
+        }
+
+        self.depth = depth;
+    }
+
+    /// Returns the captured stack, starting with the interrupted instruction address, followed
+    /// by the return addresses of the callers.
+    pub(super) fn frames(&self) -> &[usize] {
+        &self.frames[..self.depth]
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn interrupted_pc(ucontext: *mut c_void) -> usize {
+    let ucontext = ucontext as *const libc::ucontext_t;
+
+    unsafe { (*ucontext).uc_mcontext.gregs[libc::REG_RIP as usize] as usize }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub(super) fn interrupted_pc(ucontext: *mut c_void) -> usize {
+    let ucontext = ucontext as *const libc::ucontext_t;
+
+    unsafe { (*ucontext).uc_mcontext.pc as usize }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub(super) fn interrupted_pc(_ucontext: *mut c_void) -> usize {
+    0
+}
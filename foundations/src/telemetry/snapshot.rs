@@ -0,0 +1,323 @@
+use std::fmt::Write as _;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "tracing")]
+use super::tracing::{TagValue, TestSpan, TestTrace, TestTraceOptions};
+
+#[cfg(feature = "logging")]
+use super::log::TestLogRecord;
+
+/// Environment variable that makes the snapshot assertions write the golden files instead of
+/// comparing with them.
+const UPDATE_SNAPSHOTS_ENV_VAR: &str = "FOUNDATIONS_UPDATE_SNAPSHOTS";
+
+/// The keys of the fields and tags that always have their values masked.
+const MASKED_KEYS: &[&str] = &["trace_id", "span_id"];
+
+/// Options for the [telemetry snapshot assertions].
+///
+/// [telemetry snapshot assertions]: super::TestTelemetryContext::assert_snapshot
+#[derive(Default, Clone)]
+pub struct TestSnapshotOptions {
+    /// Options for the construction of the snapshotted traces.
+    ///
+    /// Span start and finish times are never included in the snapshot.
+    #[cfg(feature = "tracing")]
+    pub trace_options: TestTraceOptions,
+
+    /// The keys of the log record fields, span logs and span tags that have their values masked
+    /// in addition to `trace_id` and `span_id`.
+    pub masked_keys: Vec<String>,
+
+    /// Whether to mask the values that look like UUIDs or hex trace and span IDs regardless of
+    /// their keys.
+    ///
+    /// Disabled by default, as the values like 16-digit numbers look like the IDs as well.
+    pub mask_ids: bool,
+}
+
+/// Normalized textual form of the telemetry produced in a test.
+pub(crate) struct Snapshot<'o> {
+    options: &'o TestSnapshotOptions,
+    out: String,
+}
+
+impl<'o> Snapshot<'o> {
+    pub(crate) fn new(options: &'o TestSnapshotOptions) -> Self {
+        Self {
+            options,
+            out: String::new(),
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    pub(crate) fn add_log_records(&mut self, records: &[TestLogRecord]) {
+        self.out.push_str("[logs]\n");
+
+        for record in records {
+            let _ = write!(self.out, "{} {:?}", record.level.as_str(), record.message);
+
+            self.write_fields(record.fields.iter().map(|(k, v)| (k, v.as_str())));
+            self.out.push('\n');
+        }
+
+        self.out.push('\n');
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn add_traces(&mut self, traces: &[TestTrace]) {
+        self.out.push_str("[traces]\n");
+
+        for trace in traces {
+            self.write_span(&trace.0, 0);
+        }
+
+        self.out.push('\n');
+    }
+
+    #[cfg(feature = "tracing")]
+    fn write_span(&mut self, span: &TestSpan, depth: usize) {
+        let indent = "  ".repeat(depth);
+
+        let _ = write!(self.out, "{indent}{}", span.name);
+
+        self.write_fields(span.tags.iter().map(|(k, v)| {
+            let value = match v {
+                TagValue::String(s) => s.to_string(),
+                TagValue::Boolean(b) => b.to_string(),
+                TagValue::Integer(i) => i.to_string(),
+                TagValue::Float(f) => f.to_string(),
+            };
+
+            (k, value)
+        }));
+
+        self.out.push('\n');
+
+        for (key, value) in &span.logs {
+            let _ = write!(self.out, "{indent}  | log");
+
+            self.write_fields([(key, value.as_str())]);
+            self.out.push('\n');
+        }
+
+        for child in &span.children {
+            self.write_span(child, depth + 1);
+        }
+    }
+
+    fn write_fields<'k, V: AsRef<str>>(
+        &mut self,
+        fields: impl IntoIterator<Item = (&'k String, V)>,
+    ) {
+        let mut empty = true;
+
+        for (key, value) in fields {
+            let sep = if empty { " {" } else { ", " };
+            let value = self.mask(key, value.as_ref());
+
+            let _ = write!(self.out, "{sep}{key}={value:?}");
+
+            empty = false;
+        }
+
+        if !empty {
+            self.out.push('}');
+        }
+    }
+
+    fn mask<'v>(&self, key: &str, value: &'v str) -> &'v str {
+        if MASKED_KEYS.contains(&key) || self.options.masked_keys.iter().any(|k| k == key) {
+            return "[masked]";
+        }
+
+        if is_timestamp(value) {
+            return "[timestamp]";
+        }
+
+        if self.options.mask_ids && is_id(value) {
+            return "[id]";
+        }
+
+        value
+    }
+
+    /// Compares the snapshot with the golden file in the `snapshots` directory beside the caller's
+    /// source file, or overwrites the file if [`UPDATE_SNAPSHOTS_ENV_VAR`] is set.
+    #[track_caller]
+    pub(crate) fn assert_golden(self, name: &str) {
+        let path = golden_file_path(Location::caller(), name);
+        let actual = self.out;
+
+        if update_requested() {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).unwrap_or_else(|err| {
+                    panic!("failed to create directory {}: {err}", dir.display())
+                });
+            }
+
+            std::fs::write(&path, &actual).unwrap_or_else(|err| {
+                panic!("failed to write snapshot file {}: {err}", path.display())
+            });
+
+            return;
+        }
+
+        let expected = match std::fs::read_to_string(&path) {
+            Ok(expected) => expected,
+            Err(err) => panic!(
+                "failed to read snapshot file {}: {err}\n\nrun the test with \
+                 {UPDATE_SNAPSHOTS_ENV_VAR}=1 to create it with the actual telemetry:\n\n{actual}",
+                path.display()
+            ),
+        };
+
+        if expected != actual {
+            panic!(
+                "telemetry doesn't match snapshot file {}\n\nrun the test with \
+                 {UPDATE_SNAPSHOTS_ENV_VAR}=1 to update it if the changes are expected\n\n\
+                 --- expected\n+++ actual\n{}",
+                path.display(),
+                diff(&expected, &actual)
+            );
+        }
+    }
+}
+
+fn update_requested() -> bool {
+    std::env::var(UPDATE_SNAPSHOTS_ENV_VAR).is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Returns `snapshots/<source file name>__<name>.snap` in the directory of the caller's source
+/// file.
+fn golden_file_path(caller: &Location, name: &str) -> PathBuf {
+    let source = Path::new(caller.file());
+
+    // NOTE: source file paths are relative to the workspace root, while tests run in the package
+    // directory, so look for the file in the ancestors of the current directory.
+    let source = std::env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            dir.ancestors()
+                .map(|dir| dir.join(source))
+                .find(|path| path.exists())
+        })
+        .unwrap_or_else(|| source.to_path_buf());
+
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    source
+        .with_file_name("snapshots")
+        .join(format!("{stem}__{name}.snap"))
+}
+
+/// Checks whether the value is an RFC 3339 or a similar date and time.
+fn is_timestamp(value: &str) -> bool {
+    const PATTERN: &[u8] = b"dddd-dd-ddTdd:dd:dd";
+
+    let value = value.as_bytes();
+
+    value.len() >= PATTERN.len()
+        && PATTERN.iter().zip(value).all(|(p, c)| match p {
+            b'd' => c.is_ascii_digit(),
+            b'T' => matches!(c, b'T' | b't' | b' '),
+            p => p == c,
+        })
+}
+
+/// Checks whether the value is a UUID or a 64-bit or 128-bit hex ID, like trace and span IDs.
+fn is_id(value: &str) -> bool {
+    let is_hex = |s: &str| s.bytes().all(|c| c.is_ascii_hexdigit());
+
+    if matches!(value.len(), 16 | 32) {
+        return is_hex(value);
+    }
+
+    let groups: Vec<_> = value.split('-').map(str::len).collect();
+
+    groups == [8, 4, 4, 4, 12] && is_hex(&value.replace('-', ""))
+}
+
+/// A line diff of the snapshots based on the longest common subsequence.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+
+    // NOTE: `lcs[i][j]` is the length of the longest common subsequence of `expected[i..]` and
+    // `actual[j..]`.
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            let _ = writeln!(out, " {}", expected[i]);
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "-{}", expected[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+{}", actual[j]);
+            j += 1;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masked_values() {
+        assert!(is_timestamp("2024-01-02T03:04:05.678Z"));
+        assert!(is_timestamp("2024-01-02 03:04:05"));
+        assert!(!is_timestamp("2024-01-02"));
+
+        assert!(is_id("0af7651916cd43dd8448eb211c80319c"));
+        assert!(is_id("b7ad6b7169203331"));
+        assert!(is_id("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+        assert!(!is_id("not an identifier"));
+        assert!(!is_id("1234"));
+    }
+
+    #[test]
+    fn ids_masked_only_if_enabled() {
+        let key = "request_id".to_string();
+        let mut options = TestSnapshotOptions::default();
+
+        assert_eq!(
+            Snapshot::new(&options).mask(&key, "1234567890123456"),
+            "1234567890123456"
+        );
+
+        options.mask_ids = true;
+
+        assert_eq!(
+            Snapshot::new(&options).mask(&key, "1234567890123456"),
+            "[id]"
+        );
+    }
+
+    #[test]
+    fn line_diff() {
+        assert_eq!(diff("a\nb\nc\n", "a\nx\nc\nd\n"), " a\n-b\n+x\n c\n+d\n");
+    }
+}
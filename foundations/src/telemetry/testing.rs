@@ -1,9 +1,13 @@
 use super::TelemetryContext;
+use super::snapshot::{Snapshot, TestSnapshotOptions};
 use crate::utils::feature_use;
 use std::ops::Deref;
 
 feature_use!(cfg(feature = "logging"), {
-    use super::log::testing::{TestLogRecord, TestLogRecords, create_test_log};
+    use super::log::testing::{
+        LogMatcher, TestLogRecord, TestLogRecords, assert_logged, assert_logged_in_order,
+        assert_not_logged, create_test_log,
+    };
     use super::settings::LogVerbosity;
     use super::settings::LoggingSettings;
     use std::sync::Arc;
@@ -148,12 +152,107 @@ impl TestTelemetryContext {
         self.log_records.read().unwrap()
     }
 
+    /// Asserts that a log record matching the `matcher` has been produced in the test context.
+    /// Returns the first matching record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no matching record. The panic message shows the closest record with
+    /// the criteria it doesn't satisfy and all the produced records.
+    #[cfg(feature = "logging")]
+    #[track_caller]
+    pub fn assert_logged(&self, matcher: LogMatcher) -> TestLogRecord {
+        assert_logged(&self.log_records(), &matcher)
+    }
+
+    /// Asserts that no log record matching the `matcher` has been produced in the test context.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the first matching record if there is one.
+    #[cfg(feature = "logging")]
+    #[track_caller]
+    pub fn assert_not_logged(&self, matcher: LogMatcher) {
+        assert_not_logged(&self.log_records(), &matcher)
+    }
+
+    /// Asserts that the log records matching the `matchers` have been produced in the test
+    /// context in the order of the matchers. Other records can be produced in between.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no matching record for one of the matchers after the record matched
+    /// by the previous matcher. The panic message shows the closest record after it with the
+    /// criteria it doesn't satisfy and all the produced records.
+    #[cfg(feature = "logging")]
+    #[track_caller]
+    pub fn assert_logged_in_order(&self, matchers: impl IntoIterator<Item = LogMatcher>) {
+        assert_logged_in_order(&self.log_records(), matchers)
+    }
+
     /// Returns all the traces produced in the test context.
     #[cfg(feature = "tracing")]
     pub fn traces(&self, options: TestTraceOptions) -> Vec<TestTrace> {
         self.traces_sink.lock().traces(options)
     }
 
+    /// Compares the log records and traces produced in the test context with the golden file.
+    ///
+    /// The golden file is `snapshots/<test file name>__<name>.snap` in the directory of the test
+    /// source file. It contains a normalized textual form of the [log records] and of the trace
+    /// trees produced by [`traces`] with [`TestSnapshotOptions::trace_options`]. Span start and
+    /// finish times are omitted, and the values of the fields, span logs and span tags with the
+    /// `trace_id`, `span_id` and [`TestSnapshotOptions::masked_keys`] keys, as well as the values
+    /// that look like timestamps, are masked. The values that look like UUIDs or hex trace and
+    /// span IDs are also masked if [`TestSnapshotOptions::mask_ids`] is enabled.
+    ///
+    /// If the `FOUNDATIONS_UPDATE_SNAPSHOTS` environment variable is set to a value other than
+    /// `0`, the golden file is created or overwritten with the produced telemetry instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a line diff if the produced telemetry doesn't match the golden file, or if
+    /// the golden file doesn't exist.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use foundations::telemetry::log;
+    /// use foundations::telemetry::tracing;
+    /// use foundations::telemetry::{TelemetryContext, TestSnapshotOptions};
+    ///
+    /// #[tracing::span_fn("connect")]
+    /// fn connect() {
+    ///     log::info!("connected"; "host" => "db");
+    /// }
+    ///
+    /// let ctx = TelemetryContext::test();
+    ///
+    /// {
+    ///     let _scope = ctx.scope();
+    ///     let _root = tracing::span("request");
+    ///
+    ///     connect();
+    /// }
+    ///
+    /// ctx.assert_snapshot("connect", TestSnapshotOptions::default());
+    /// ```
+    ///
+    /// [log records]: Self::log_records
+    /// [`traces`]: Self::traces
+    #[track_caller]
+    pub fn assert_snapshot(&self, name: &str, options: TestSnapshotOptions) {
+        #[cfg_attr(not(any(feature = "logging", feature = "tracing")), allow(unused_mut))]
+        let mut snapshot = Snapshot::new(&options);
+
+        #[cfg(feature = "logging")]
+        snapshot.add_log_records(&self.log_records());
+
+        #[cfg(feature = "tracing")]
+        snapshot.add_traces(&self.traces(options.trace_options));
+
+        snapshot.assert_golden(name);
+    }
+
     /// Returns all the user-tracing traces produced in the test context.
     #[cfg(feature = "user-tracing")]
     pub fn user_traces(&self, options: TestTraceOptions) -> Vec<TestTrace> {
@@ -0,0 +1,343 @@
+use super::settings::ThreadDumpSettings;
+use super::signal::install_signal_handler;
+use super::signal_stack::{StackSlot, interrupted_pc};
+use crate::telemetry::log;
+use crate::{BootstrapError, BootstrapResult, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::cell::UnsafeCell;
+use std::ffi::{c_int, c_void};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read as _;
+use std::os::fd::{FromRawFd, IntoRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use std::{fs, io, thread};
+
+// TODO(once_cell_try): replace with `std::sync::OnceLock`
+static DUMPER: OnceCell<Option<ThreadDumper>> = OnceCell::new();
+
+// NOTE: threads are captured one at a time. The capture state is accessed from the signal
+// handler, so it needs to be plain atomics and preallocated storage.
+static CAPTURE_TARGET_TID: AtomicI32 = AtomicI32::new(0);
+static CAPTURED: AtomicBool = AtomicBool::new(false);
+static CAPTURED_STACK: CapturedStack = CapturedStack(UnsafeCell::new(StackSlot::new()));
+static SIGNAL_HANDLERS_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static LOG_SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+static CAPTURE_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+static DUMP_LOCK: Mutex<()> = Mutex::new(());
+
+const THREAD_RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// NOTE: prevent direct construction by the external code.
+#[derive(Copy, Clone)]
+struct Seal;
+
+/// A dumper of the native stacks of all the process threads.
+///
+/// Each thread of the process is interrupted with a realtime signal, `SIGRTMAX` by default (see
+/// [`ThreadDumpSettings::capture_signal_offset`]), which captures the thread's stack in the
+/// signal handler. The stacks are then symbolized and labeled with the
+/// thread name and TID. This allows to debug threads stuck in blocking code, which are not
+/// visible in async task dumps.
+#[derive(Copy, Clone)]
+pub struct ThreadDumper {
+    _seal: Seal,
+}
+
+impl ThreadDumper {
+    /// Creates a new thread dumper with the given settings or returns a previously initialized
+    /// dumper ignoring the settings.
+    ///
+    /// Returns `None` if [`ThreadDumpSettings::enabled`] is set to `false`.
+    ///
+    /// # Syscall sandboxing
+    ///
+    /// If syscall sandboxing is being used (see [`crate::security`] for more details), the
+    /// dumper must be initialized prior to syscall sandboxing, so the signal handlers and the
+    /// log signal thread are set up without the sandbox restrictions. Sandboxed threads also need
+    /// the `THREAD_DUMP_EXTRAS` syscall allow list from
+    /// [`crate::security::common_syscall_allow_lists`].
+    pub fn get_or_init_with(settings: &ThreadDumpSettings) -> BootstrapResult<Option<Self>> {
+        DUMPER.get_or_try_init(|| init_dumper(settings)).copied()
+    }
+
+    /// Captures the stacks of all the process threads and returns them in a human-readable
+    /// form.
+    ///
+    /// Threads are captured one at a time and threads that don't respond within 100ms, e.g.
+    /// due to the signal being blocked, are reported without a stack. Only one dump can be
+    /// collected at a time, so this method blocks if there is another dump in progress.
+    ///
+    /// # Examples
+    /// ```
+    /// use foundations::telemetry::ThreadDumper;
+    /// use foundations::telemetry::settings::ThreadDumpSettings;
+    ///
+    /// let settings = ThreadDumpSettings {
+    ///     enabled: true,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let dumper = ThreadDumper::get_or_init_with(&settings)
+    ///     .unwrap()
+    ///     .expect("thread dumps should be enabled");
+    ///
+    /// let dump = dumper.dump().unwrap();
+    ///
+    /// assert!(dump.contains("ThreadDumper::dump"));
+    /// ```
+    pub fn dump(&self) -> Result<String> {
+        let _guard = DUMP_LOCK.lock();
+        let current_tid = gettid();
+        let mut dump = String::new();
+
+        for entry in fs::read_dir("/proc/self/task")? {
+            let Some(tid) = entry?
+                .file_name()
+                .to_str()
+                .and_then(|tid| tid.parse::<i32>().ok())
+            else {
+                continue;
+            };
+
+            // NOTE: the thread might have exited while we were capturing the others.
+            let Ok(name) = fs::read_to_string(format!("/proc/self/task/{tid}/comm")) else {
+                continue;
+            };
+
+            let stack = if tid == current_tid {
+                Some(capture_current_thread())
+            } else {
+                match capture_thread(tid) {
+                    Err(err) if err.raw_os_error() == Some(libc::ESRCH) => continue,
+                    res => res?,
+                }
+            };
+
+            writeln!(dump, "Thread {:?} (tid {tid}):", name.trim_end())?;
+
+            match stack {
+                Some(stack) => write_symbolized_stack(&mut dump, &stack)?,
+                None => writeln!(dump, "    <thread didn't respond>")?,
+            }
+
+            writeln!(dump)?;
+        }
+
+        Ok(dump)
+    }
+}
+
+struct CapturedStack(UnsafeCell<StackSlot>);
+
+// SAFETY: the stack is written only by the signal handler on the capture target thread and
+// read only after all the signal handlers are done with it.
+unsafe impl Sync for CapturedStack {}
+
+fn init_dumper(settings: &ThreadDumpSettings) -> BootstrapResult<Option<ThreadDumper>> {
+    if !settings.enabled {
+        return Ok(None);
+    }
+
+    let offset = c_int::from(settings.capture_signal_offset);
+    let capture_signal = libc::SIGRTMAX() - offset;
+
+    if capture_signal < libc::SIGRTMIN() {
+        anyhow::bail!(
+            "thread dump capture signal offset {offset} is out of the realtime signal range"
+        );
+    }
+
+    install_signal_handler(capture_signal, on_capture_signal).map_err(|e| {
+        BootstrapError::new(e).context(format!("failed to install SIGRTMAX-{offset} handler"))
+    })?;
+
+    CAPTURE_SIGNAL.store(capture_signal, Ordering::SeqCst);
+
+    let dumper = ThreadDumper { _seal: Seal };
+
+    if let Some(signal) = settings.log_signal {
+        let (reader, writer) = pipe()?;
+
+        LOG_SIGNAL_PIPE.store(writer.into_raw_fd(), Ordering::SeqCst);
+
+        install_signal_handler(signal.signum(), on_log_signal).map_err(|e| {
+            BootstrapError::new(e).context(format!("failed to install {signal:?} handler"))
+        })?;
+
+        thread::Builder::new()
+            .name("foundations-thread-dump".into())
+            .spawn(move || log_dumps_thread(dumper, reader))?;
+    }
+
+    Ok(Some(dumper))
+}
+
+fn log_dumps_thread(dumper: ThreadDumper, reader: OwnedFd) {
+    let mut reader = File::from(reader);
+    let mut buf = [0; 64];
+
+    // NOTE: multiple signals received during a dump result in a single subsequent dump.
+    while let Ok(1..) = reader.read(&mut buf) {
+        match dumper.dump() {
+            Ok(dump) => {
+                log::warn!("thread dump:\n{dump}");
+            }
+            Err(err) => {
+                log::error!("failed to collect thread dump"; "error" => %err);
+            }
+        }
+    }
+}
+
+fn capture_current_thread() -> Vec<usize> {
+    let mut stack = vec![];
+
+    backtrace::trace(|frame| {
+        stack.push(frame.ip() as usize);
+
+        true
+    });
+
+    stack
+}
+
+/// Captures the stack of the thread with the given TID. Returns `None` if the thread hasn't
+/// responded in time.
+fn capture_thread(tid: i32) -> io::Result<Option<Vec<usize>>> {
+    CAPTURED.store(false, Ordering::SeqCst);
+    CAPTURE_TARGET_TID.store(tid, Ordering::SeqCst);
+
+    let res = tgkill(tid, CAPTURE_SIGNAL.load(Ordering::SeqCst));
+    let deadline = Instant::now() + THREAD_RESPONSE_TIMEOUT;
+
+    while res.is_ok() && !CAPTURED.load(Ordering::SeqCst) && Instant::now() < deadline {
+        thread::sleep(Duration::from_micros(100));
+    }
+
+    CAPTURE_TARGET_TID.store(0, Ordering::SeqCst);
+
+    // NOTE: wait for the signal handler that might still be writing the stack.
+    while SIGNAL_HANDLERS_IN_FLIGHT.load(Ordering::SeqCst) > 0 {
+        thread::yield_now();
+    }
+
+    res?;
+
+    if !CAPTURED.load(Ordering::SeqCst) {
+        return Ok(None);
+    }
+
+    // SAFETY: the stack is no longer accessible to the signal handlers.
+    let stack = unsafe { &*CAPTURED_STACK.0.get() };
+
+    Ok(Some(stack.frames().to_vec()))
+}
+
+fn write_symbolized_stack(dump: &mut String, stack: &[usize]) -> std::fmt::Result {
+    for (i, &addr) in stack.iter().enumerate() {
+        // NOTE: return addresses point to the instruction following the call, which can belong
+        // to a different line or even to a different function.
+        let lookup_addr = if i == 0 { addr } else { addr.saturating_sub(1) };
+        let mut res = Ok(());
+        let mut resolved = false;
+
+        backtrace::resolve(lookup_addr as *mut c_void, |symbol| {
+            let Some(name) = symbol.name() else {
+                return;
+            };
+
+            resolved = true;
+            res = res.and_then(|_| write!(dump, "    #{i:<3} {addr:#018x} {name:#}"));
+
+            if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                res = res.and_then(|_| write!(dump, " at {}:{line}", file.display()));
+            }
+
+            res = res.and_then(|_| writeln!(dump));
+        });
+
+        res?;
+
+        if !resolved {
+            writeln!(dump, "    #{i:<3} {addr:#018x} <unknown>")?;
+        }
+    }
+
+    Ok(())
+}
+
+extern "C" fn on_capture_signal(
+    _signum: c_int,
+    _info: *mut libc::siginfo_t,
+    ucontext: *mut c_void,
+) {
+    // NOTE: the signal handler must not change `errno` of the interrupted code.
+    let errno = unsafe { *libc::__errno_location() };
+
+    SIGNAL_HANDLERS_IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+
+    if CAPTURE_TARGET_TID.load(Ordering::SeqCst) == gettid() && !CAPTURED.load(Ordering::SeqCst) {
+        // SAFETY: only the capture target thread writes the stack and the signal is blocked
+        // while its handler is running, so there are no concurrent writers.
+        unsafe { &mut *CAPTURED_STACK.0.get() }.capture(interrupted_pc(ucontext));
+
+        CAPTURED.store(true, Ordering::SeqCst);
+    }
+
+    SIGNAL_HANDLERS_IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+
+    unsafe { *libc::__errno_location() = errno };
+}
+
+extern "C" fn on_log_signal(_signum: c_int, _info: *mut libc::siginfo_t, _ucontext: *mut c_void) {
+    // NOTE: the signal handler must not change `errno` of the interrupted code.
+    let errno = unsafe { *libc::__errno_location() };
+
+    // NOTE: the write end of the pipe is non-blocking, so if the pipe is full the dump has
+    // already been requested.
+    unsafe {
+        libc::write(
+            LOG_SIGNAL_PIPE.load(Ordering::SeqCst),
+            [0u8].as_ptr().cast(),
+            1,
+        )
+    };
+
+    unsafe { *libc::__errno_location() = errno };
+}
+
+fn gettid() -> i32 {
+    unsafe { libc::gettid() }
+}
+
+fn tgkill(tid: i32, signum: c_int) -> io::Result<()> {
+    let res = unsafe { libc::syscall(libc::SYS_tgkill, libc::getpid(), tid, signum) };
+
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Creates a pipe with a non-blocking write end.
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: the file descriptors have just been created and are not owned by anything else.
+    let (reader, writer) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    if unsafe { libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok((reader, writer))
+}
@@ -31,6 +31,13 @@
 //! | tokio_runtime_worker_local_queue_depth           | [`tokio::runtime::RuntimeMetrics::worker_local_queue_depth`]        | runtime_name?, runtime_id?, worker_idx |
 //! | tokio_runtime_worker_mean_poll_time_micros       | [`tokio::runtime::RuntimeMetrics::worker_mean_poll_time`]           | runtime_name?, runtime_id?, worker_idx |
 //!
+//! # Task dumps
+//! With the `tokio-task-dump` feature, [`dump_tasks`] captures the traces of the tasks of all
+//! the registered runtimes. The dumps are also served by the telemetry server on the
+//! `/debug/tasks` route, in plain text or in JSON with the `format=json` query parameter.
+//! Task dumps are only supported on Linux on `aarch64`, `x86`, `x86_64` and `s390x`, and require
+//! `--cfg tokio_taskdump` to be added to `RUSTFLAGS`.
+//!
 //! # Example
 //! ```no_run
 //! # use std::thread;
@@ -63,11 +70,17 @@
 mod metrics;
 mod runtime_handle;
 
+#[cfg(foundations_tokio_task_dump)]
+mod task_dump;
+
 use crate::telemetry::tokio_runtime_metrics::runtime_handle::RuntimeHandle;
 use slab::Slab;
 use std::sync::Arc;
 use tokio::runtime::Handle;
 
+#[cfg(foundations_tokio_task_dump)]
+pub use self::task_dump::{RuntimeTaskDump, TaskGroup, dump_tasks};
+
 static MONITOR: parking_lot::Mutex<Slab<RuntimeHandle>> = parking_lot::Mutex::new(Slab::new());
 
 /// Key for removing runtimes registered with the global monitor.
@@ -0,0 +1,126 @@
+use super::MONITOR;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::{Dump, Handle};
+
+/// A task dump of a runtime registered with the global monitor.
+#[derive(Debug, Serialize)]
+pub struct RuntimeTaskDump {
+    /// Name of the runtime, if any.
+    pub runtime_name: Option<Arc<str>>,
+
+    /// ID of the runtime, if any.
+    pub runtime_id: Option<usize>,
+
+    /// Tasks of the runtime grouped by their traces, with the largest groups first.
+    pub tasks: Vec<TaskGroup>,
+
+    /// An error that prevented the runtime from being dumped, if any.
+    pub error: Option<String>,
+}
+
+/// A group of tasks with identical traces.
+#[derive(Debug, Serialize)]
+pub struct TaskGroup {
+    /// Number of tasks in the group.
+    pub count: usize,
+
+    /// The trace of the tasks.
+    pub trace: String,
+}
+
+impl fmt::Display for RuntimeTaskDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "runtime")?;
+
+        if let Some(name) = &self.runtime_name {
+            write!(f, " name={name}")?;
+        }
+
+        if let Some(id) = self.runtime_id {
+            write!(f, " id={id}")?;
+        }
+
+        if let Some(error) = &self.error {
+            return writeln!(f, ": {error}");
+        }
+
+        let num_tasks: usize = self.tasks.iter().map(|group| group.count).sum();
+
+        writeln!(f, ": {num_tasks} tasks")?;
+
+        for group in &self.tasks {
+            writeln!(f, "\n{} tasks:\n{}", group.count, group.trace)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Captures task dumps of all the runtimes registered with the global monitor.
+///
+/// Capturing a task dump requires the runtime to pause its workers, so a runtime with a blocked
+/// worker can't be dumped. Such runtimes are reported with an error once `timeout` elapses.
+///
+/// Dumps are expensive to capture and shouldn't be requested often.
+pub async fn dump_tasks(timeout: Duration) -> Vec<RuntimeTaskDump> {
+    // NOTE: collect the handles first, so the monitor lock is not held across the await points.
+    let runtimes: Vec<_> = MONITOR
+        .lock()
+        .iter()
+        .map(|(_, h)| (h.runtime_name.clone(), h.runtime_id, h.handle.clone()))
+        .collect();
+
+    let mut dumps = Vec::with_capacity(runtimes.len());
+
+    for (runtime_name, runtime_id, handle) in runtimes {
+        let (tasks, error) = match dump_runtime(&handle, timeout).await {
+            Ok(dump) => (group_tasks(&dump), None),
+            Err(err) => (vec![], Some(err)),
+        };
+
+        dumps.push(RuntimeTaskDump {
+            runtime_name,
+            runtime_id,
+            tasks,
+            error,
+        });
+    }
+
+    dumps
+}
+
+async fn dump_runtime(handle: &Handle, timeout: Duration) -> Result<Dump, String> {
+    // NOTE: current thread runtimes can only be dumped from within the runtime itself.
+    let mut dump = handle.spawn(async { Handle::current().dump().await });
+
+    match tokio::time::timeout(timeout, &mut dump).await {
+        Ok(Ok(dump)) => Ok(dump),
+        Ok(Err(err)) => Err(format!("failed to dump the runtime: {err}")),
+        Err(_) => {
+            dump.abort();
+
+            Err(format!("runtime dump timed out after {timeout:?}"))
+        }
+    }
+}
+
+fn group_tasks(dump: &Dump) -> Vec<TaskGroup> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for task in dump.tasks().iter() {
+        *counts.entry(task.trace().to_string()).or_default() += 1;
+    }
+
+    let mut groups: Vec<_> = counts
+        .into_iter()
+        .map(|(trace, count)| TaskGroup { count, trace })
+        .collect();
+
+    groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.trace.cmp(&b.trace)));
+
+    groups
+}
@@ -0,0 +1,88 @@
+//! Helpers shared by the integration tests.
+
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{
+    LogsService, LogsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+
+/// A mock OpenTelemetry collector that passes the bodies of the exported log records to the test.
+pub(crate) struct MockLogsCollector {
+    addr: SocketAddr,
+    body_rx: mpsc::UnboundedReceiver<String>,
+}
+
+impl MockLogsCollector {
+    /// Spawns the collector on a random port.
+    pub(crate) async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (body_tx, body_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(LogsServiceServer::new(LogsServiceImpl { body_tx }))
+                .serve_with_incoming(TcpIncoming::from(listener)),
+        );
+
+        Self { addr, body_rx }
+    }
+
+    /// Endpoint URL to put in the OTLP output settings.
+    pub(crate) fn endpoint_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Waits for a log record with the given body to be exported.
+    pub(crate) async fn expect_record(&mut self, body: &str) {
+        let recv_fut = async {
+            while let Some(received) = self.body_rx.recv().await {
+                if received == body {
+                    return;
+                }
+            }
+
+            panic!("collector stopped before receiving the `{body}` log record");
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), recv_fut)
+            .await
+            .unwrap_or_else(|_| panic!("the `{body}` log record should be exported"));
+    }
+}
+
+struct LogsServiceImpl {
+    body_tx: mpsc::UnboundedSender<String>,
+}
+
+#[tonic::async_trait]
+impl LogsService for LogsServiceImpl {
+    async fn export(
+        &self,
+        request: Request<ExportLogsServiceRequest>,
+    ) -> Result<Response<ExportLogsServiceResponse>, Status> {
+        let records = request
+            .into_inner()
+            .resource_logs
+            .into_iter()
+            .flat_map(|resource_logs| resource_logs.scope_logs)
+            .flat_map(|scope_logs| scope_logs.log_records);
+
+        for record in records {
+            if let Some(Value::StringValue(body)) = record.body.and_then(|body| body.value) {
+                let _ = self.body_tx.send(body);
+            }
+        }
+
+        Ok(Response::new(Default::default()))
+    }
+}
@@ -0,0 +1,93 @@
+#![cfg(unix)]
+
+use foundations::addr::{InheritedListener, ListenAddr};
+use foundations::settings::from_yaml_str;
+use foundations::telemetry::settings::TelemetryServerSettings;
+use foundations::telemetry::settings::TelemetrySettings;
+use foundations::telemetry::{TelemetryConfig, init};
+use std::io::Write;
+use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+use std::os::fd::{AsRawFd, IntoRawFd};
+
+#[tokio::test]
+async fn telemetry_server_on_inherited_fd() {
+    let std_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let server_addr = std_listener.local_addr().unwrap();
+    let inherited = InheritedListener::Fd(std_listener.into_raw_fd());
+
+    let settings = TelemetrySettings {
+        server: TelemetryServerSettings {
+            enabled: true,
+            inherited_listener: Some(inherited.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let driver = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![],
+    })
+    .unwrap();
+
+    assert!(matches!(
+        driver.server_addr(),
+        Some(ListenAddr::Tcp(addr)) if *addr == server_addr
+    ));
+
+    tokio::spawn(driver);
+
+    assert_eq!(
+        reqwest::get(format!("http://{server_addr}/health"))
+            .await
+            .unwrap()
+            .status(),
+        200
+    );
+
+    // The socket has already been taken by the telemetry server.
+    assert!(inherited.take_fd().is_err());
+}
+
+#[test]
+fn non_listener_fd_is_not_taken() {
+    let mut file = tempfile::tempfile().unwrap();
+    let udp_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+    assert!(InheritedListener::Fd(file.as_raw_fd()).take_fd().is_err());
+
+    assert!(
+        InheritedListener::Fd(udp_socket.as_raw_fd())
+            .take_fd()
+            .is_err()
+    );
+
+    // The file descriptors are still open.
+    file.write_all(b"still open").unwrap();
+    udp_socket.local_addr().unwrap();
+}
+
+#[test]
+fn missing_systemd_socket() {
+    assert!(
+        InheritedListener::Systemd("telemetry".into())
+            .take_fd()
+            .is_err()
+    );
+}
+
+#[test]
+fn parse_inherited_listener() {
+    let settings: TelemetryServerSettings =
+        from_yaml_str("inherited_listener:\n  systemd: telemetry").unwrap();
+
+    assert_eq!(
+        settings.inherited_listener,
+        Some(InheritedListener::Systemd("telemetry".into()))
+    );
+
+    let settings: TelemetryServerSettings = from_yaml_str("inherited_listener:\n  fd: 3").unwrap();
+
+    assert_eq!(settings.inherited_listener, Some(InheritedListener::Fd(3)));
+}
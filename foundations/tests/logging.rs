@@ -1,8 +1,14 @@
 use foundations::telemetry::TelemetryContext;
 use foundations::telemetry::TestTelemetryContext;
 use foundations::telemetry::log::internal::LoggerWithKvNestingTracking;
-use foundations::telemetry::log::{add_fields, freeze, is_frozen, set_verbosity, unfreeze, warn};
-use foundations::telemetry::settings::{LogVerbosity, LoggingSettings, RateLimitingSettings};
+use foundations::telemetry::log::{
+    LogMatcher, add_fields, debug, error, freeze, info, is_frozen, set_verbosity, unfreeze, warn,
+};
+use foundations::telemetry::settings::{
+    LogCollapseSettings, LogLevelRateLimits, LogSourceRateLimitingSettings,
+    LogTraceCorrelationSettings, LogVerbosity, LoggingSettings, RateLimitingSettings,
+};
+use foundations::telemetry::tracing;
 use foundations_macros::with_test_telemetry;
 
 #[with_test_telemetry(test)]
@@ -28,6 +34,79 @@ fn test_rate_limiter(mut ctx: TestTelemetryContext) {
     assert!(ctx.log_records().len() < 32);
 }
 
+#[with_test_telemetry(test)]
+fn test_source_rate_limiter(mut ctx: TestTelemetryContext) {
+    ctx.set_logging_settings(LoggingSettings {
+        source_rate_limit: LogSourceRateLimitingSettings {
+            enabled: true,
+            max_events_per_second: 2,
+            level_max_events_per_second: LogLevelRateLimits {
+                error: Some(0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    let hot_loop = || {
+        for i in 0..100 {
+            warn!("hot loop {}", i);
+        }
+    };
+
+    hot_loop();
+
+    for i in 0..10 {
+        error!("unlimited {}", i);
+    }
+
+    warn!("other callsite");
+
+    assert_eq!(ctx.log_records().len(), 2 + 10 + 1);
+}
+
+#[with_test_telemetry(test)]
+fn test_collapse_repeated(mut ctx: TestTelemetryContext) {
+    ctx.set_logging_settings(LoggingSettings {
+        collapse_repeated: LogCollapseSettings {
+            enabled: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    let flapping = |upstream| {
+        for _ in 0..5 {
+            warn!("connection refused"; "upstream" => upstream);
+        }
+    };
+
+    flapping("db");
+    flapping("cache");
+    info!("recovered");
+
+    let records = ctx.log_records();
+    let messages: Vec<_> = records.iter().map(|r| r.message.as_str()).collect();
+
+    assert_eq!(
+        messages,
+        [
+            "connection refused",
+            "last message repeated 4 times",
+            "connection refused",
+            "last message repeated 4 times",
+            "recovered"
+        ]
+    );
+
+    assert_eq!(
+        records[1].fields,
+        vec![("repeated_count".into(), "4".into())]
+    );
+    assert_eq!(records[2].fields, vec![("upstream".into(), "cache".into())]);
+}
+
 // Every time we call set_verbosity(), or the add_fields! macro, it adds one to the depth of the
 // nested structure of Arcs inside the logger object. If the structure gets too deeply nested, it
 // causes a stack overflow on drop.
@@ -141,6 +220,161 @@ fn test_frozen_logger_add_fields_panics(_ctx: TestTelemetryContext) {
     }
 }
 
+#[with_test_telemetry(test)]
+fn test_trace_correlation(mut ctx: TestTelemetryContext) {
+    ctx.set_logging_settings(LoggingSettings {
+        trace_correlation: LogTraceCorrelationSettings {
+            enabled: true,
+            include_unsampled: false,
+        },
+        ..Default::default()
+    });
+
+    warn!("outside of span");
+
+    let _span = tracing::span("span");
+
+    warn!("inside span"; "foo" => "bar");
+
+    // NOTE: `00-{trace_id}-{span_id}-{flags}`
+    let traceparent = tracing::w3c_traceparent().unwrap();
+    let trace_id = &traceparent[3..35];
+    let span_id = &traceparent[36..52];
+
+    let records = ctx.log_records();
+
+    assert_eq!(records[0].fields, vec![]);
+    assert_eq!(
+        records[1].fields,
+        vec![
+            ("foo".into(), "bar".into()),
+            ("trace_id".into(), trace_id.into()),
+            ("span_id".into(), span_id.into()),
+        ]
+    );
+}
+
+#[cfg(feature = "log-sampling")]
+#[with_test_telemetry(test)]
+fn test_sampling(mut ctx: TestTelemetryContext) {
+    use foundations::telemetry::settings::{LogLevelSampleRates, LogSamplingSettings};
+
+    ctx.set_logging_settings(LoggingSettings {
+        sampling: LogSamplingSettings {
+            enabled: true,
+            level_sample_rates: LogLevelSampleRates {
+                info: Some(0.5),
+                debug: Some(0.0),
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    });
+
+    debug!("sampled out");
+    warn!("not sampled");
+
+    let _span = tracing::span("span");
+
+    for i in 0..10 {
+        info!("inside span {}", i);
+    }
+
+    // NOTE: the records of the trace are either all kept or all dropped, depending on the low
+    // 64 bits of the trace ID in `00-{trace_id}-{span_id}-{flags}`.
+    let traceparent = tracing::w3c_traceparent().unwrap();
+    let trace_id_low = u64::from_str_radix(&traceparent[19..35], 16).unwrap();
+    let trace_kept = (trace_id_low as f64) < 0.5 * u64::MAX as f64;
+
+    let records = ctx.log_records();
+
+    assert_eq!(records[0].message, "not sampled");
+    assert_eq!(records[0].fields, vec![]);
+    assert_eq!(records.len(), if trace_kept { 11 } else { 1 });
+
+    for record in &records[1..] {
+        assert_eq!(record.fields, vec![("sample_rate".into(), "0.5".into())]);
+    }
+}
+
+#[with_test_telemetry(test)]
+fn test_verbosity_directives(mut ctx: TestTelemetryContext) {
+    ctx.set_logging_settings(LoggingSettings {
+        verbosity: LogVerbosity::Trace,
+        verbosity_directives: "logging=warn,logging::other=trace".into(),
+        ..Default::default()
+    });
+
+    debug!("filtered out");
+    info!("filtered out");
+    warn!("passed through");
+
+    let messages: Vec<_> = ctx
+        .log_records()
+        .iter()
+        .map(|record| record.message.clone())
+        .collect();
+
+    assert_eq!(messages, ["passed through"]);
+}
+
+#[with_test_telemetry(test)]
+fn test_log_assertions(ctx: TestTelemetryContext) {
+    info!("connecting"; "host" => "db");
+    warn!("connection to db failed"; "attempt" => 1);
+    info!("connected"; "host" => "db");
+
+    let record = ctx.assert_logged(
+        LogMatcher::new()
+            .level(slog::Level::Warning)
+            .message_glob("connection to * failed")
+            .field("attempt", 1),
+    );
+
+    assert_eq!(record.message, "connection to db failed");
+
+    ctx.assert_not_logged(LogMatcher::new().level(slog::Level::Error));
+    ctx.assert_not_logged(
+        LogMatcher::new()
+            .message("connected")
+            .field("host", "cache"),
+    );
+
+    ctx.assert_logged_in_order([
+        LogMatcher::new().message_regex("^connect"),
+        LogMatcher::new().has_field("attempt"),
+        LogMatcher::new().message("connected"),
+    ]);
+}
+
+#[with_test_telemetry(test)]
+#[should_panic(
+    expected = "closest log record #1: WARNING \"connection to db failed\" {attempt=\"1\"}\n  \
+                           field `attempt`: expected \"2\", got \"1\""
+)]
+fn test_log_assertion_failure(ctx: TestTelemetryContext) {
+    info!("connecting"; "host" => "db");
+    warn!("connection to db failed"; "attempt" => 1);
+
+    ctx.assert_logged(
+        LogMatcher::new()
+            .level(slog::Level::Warning)
+            .field("attempt", 2),
+    );
+}
+
+#[with_test_telemetry(test)]
+#[should_panic(expected = "no log record from #2 matches the matcher #1 in the order")]
+fn test_log_order_assertion_failure(ctx: TestTelemetryContext) {
+    info!("connected");
+    info!("connecting");
+
+    ctx.assert_logged_in_order([
+        LogMatcher::new().message("connecting"),
+        LogMatcher::new().message("connected"),
+    ]);
+}
+
 #[cfg(feature = "tracing-rs-compat")]
 mod tracing_rs_compat {
     use std::io;
@@ -219,3 +453,35 @@ mod tracing_rs_compat {
         assert!(tracing_record.contains("WARN slog: compat-layer-works"));
     }
 }
+
+#[cfg(all(target_os = "linux", feature = "thread-dump"))]
+#[test]
+fn test_reopen_and_thread_dump_signal_conflict() {
+    use foundations::telemetry::settings::{
+        LogReopenSignal, TelemetrySettings, ThreadDumpSettings, ThreadDumpSignal,
+    };
+    use foundations::telemetry::{TelemetryConfig, init};
+
+    let settings = TelemetrySettings {
+        logging: LoggingSettings {
+            reopen_signal: Some(LogReopenSignal::Sigusr1),
+            ..Default::default()
+        },
+        thread_dump: ThreadDumpSettings {
+            enabled: true,
+            log_signal: Some(ThreadDumpSignal::Sigusr1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let err = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![],
+    })
+    .err()
+    .expect("init should fail");
+
+    assert!(err.to_string().contains("should be different signals"));
+}
@@ -0,0 +1,64 @@
+//! Kept in its own test binary because `telemetry::init` may only run once per process.
+
+mod common;
+
+use common::MockLogsCollector;
+use foundations::telemetry::log::warn;
+use foundations::telemetry::settings::{
+    LogOutput, LoggingSettings, OpenTelemetryGrpcOutputSettings, TelemetryServerSettings,
+    TelemetrySettings, TracingSettings,
+};
+use foundations::telemetry::{TelemetryConfig, init};
+use std::time::Duration;
+
+#[test]
+fn otlp_log_export_without_telemetry_server() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut collector = runtime.block_on(MockLogsCollector::start());
+
+    let settings = TelemetrySettings {
+        logging: LoggingSettings {
+            output: LogOutput::OpenTelemetryGrpc(OpenTelemetryGrpcOutputSettings {
+                endpoint_url: collector.endpoint_url(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        tracing: TracingSettings {
+            enabled: false,
+            ..Default::default()
+        },
+        server: TelemetryServerSettings {
+            enabled: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    // NOTE: initialized outside of the runtime, as nothing should be spawned before the driver
+    // is polled.
+    let driver = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![],
+    })
+    .unwrap();
+
+    runtime.block_on(async move {
+        // NOTE: without the server, the driver completes as soon as the gRPC channel connects.
+        tokio::time::timeout(Duration::from_secs(10), driver)
+            .await
+            .expect("telemetry driver should complete once connected")
+            .unwrap();
+
+        warn!("logged after the driver completed");
+
+        collector
+            .expect_record("logged after the driver completed")
+            .await;
+    });
+}
@@ -0,0 +1,74 @@
+//! Kept in its own test binary because `telemetry::init` may only run once per process.
+
+mod common;
+
+use common::MockLogsCollector;
+use foundations::telemetry::log::warn;
+use foundations::telemetry::settings::{
+    LogOutput, LoggingSettings, OpenTelemetryGrpcOutputSettings, TelemetryServerSettings,
+    TelemetrySettings, TracingSettings,
+};
+use foundations::telemetry::{TelemetryConfig, init};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+#[tokio::test]
+async fn otlp_log_export_with_telemetry_server() {
+    let mut collector = MockLogsCollector::start().await;
+
+    let settings = TelemetrySettings {
+        logging: LoggingSettings {
+            output: LogOutput::OpenTelemetryGrpc(OpenTelemetryGrpcOutputSettings {
+                endpoint_url: collector.endpoint_url(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        tracing: TracingSettings {
+            enabled: false,
+            ..Default::default()
+        },
+        server: TelemetryServerSettings {
+            enabled: true,
+            addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)).into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut driver = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![],
+    })
+    .unwrap();
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+    driver.with_graceful_shutdown(async move {
+        let _ = shutdown_rx.await;
+    });
+
+    let driver = tokio::spawn(driver);
+
+    warn!("logged while the driver runs");
+
+    collector
+        .expect_record("logged while the driver runs")
+        .await;
+
+    shutdown_tx.send(()).unwrap();
+
+    tokio::time::timeout(Duration::from_secs(10), driver)
+        .await
+        .expect("telemetry driver should complete on graceful shutdown")
+        .unwrap()
+        .unwrap();
+
+    warn!("logged after the graceful shutdown");
+
+    collector
+        .expect_record("logged after the graceful shutdown")
+        .await;
+}
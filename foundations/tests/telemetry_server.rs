@@ -13,7 +13,7 @@ use std::future::IntoFuture;
 use std::net::{Ipv4Addr, SocketAddr};
 
 #[cfg(target_os = "linux")]
-use foundations::telemetry::settings::MemoryProfilerSettings;
+use foundations::telemetry::settings::{CpuProfilerSettings, MemoryProfilerSettings};
 
 #[cfg(target_os = "linux")]
 use foundations::telemetry::MemoryProfiler;
@@ -53,12 +53,18 @@ async fn telemetry_server() {
         server: TelemetryServerSettings {
             enabled: true,
             addr: server_addr.into(),
+            ..Default::default()
         },
         #[cfg(target_os = "linux")]
         memory_profiler: MemoryProfilerSettings {
             enabled: true,
             ..Default::default()
         },
+        #[cfg(target_os = "linux")]
+        cpu_profiler: CpuProfilerSettings {
+            enabled: true,
+            ..Default::default()
+        },
         tracing: TracingSettings {
             liveness_tracking: LivenessTrackingSettings {
                 enabled: true,
@@ -116,6 +122,17 @@ async fn telemetry_server() {
         "Hello"
     );
 
+    // The flight recorder is disabled, so its route is not served.
+    assert_eq!(
+        reqwest::Client::new()
+            .post(format!("http://{server_addr}/debug/flight_recorder"))
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        404
+    );
+
     let metrics_res = reqwest::get(format!("http://{server_addr}/metrics"))
         .await
         .unwrap()
@@ -148,6 +165,26 @@ async fn telemetry_server() {
             .contains("Allocated")
     );
 
+    #[cfg(target_os = "linux")]
+    {
+        let profile_res = reqwest::get(format!("http://{server_addr}/pprof/profile?seconds=1"))
+            .await
+            .unwrap();
+
+        assert_eq!(profile_res.status(), 200);
+
+        // gzip magic bytes
+        assert_eq!(profile_res.bytes().await.unwrap()[..2], [0x1f, 0x8b]);
+
+        assert_eq!(
+            reqwest::get(format!("http://{server_addr}/pprof/profile?seconds=foo"))
+                .await
+                .unwrap()
+                .status(),
+            400
+        );
+    }
+
     // Capture a real PC and the symbol the backtrace crate resolves it to, so
     // we can verify both GET and POST resolve to the same name.
     let (known_pc, expected_symbol) = capture_self_pc();
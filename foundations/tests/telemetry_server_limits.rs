@@ -0,0 +1,123 @@
+use foundations::addr::ListenAddr;
+use foundations::telemetry::reexports::http_body_util::{BodyExt, Full};
+use foundations::telemetry::reexports::hyper::{Method, Response};
+use foundations::telemetry::settings::{TelemetryServerSettings, TelemetrySettings};
+use foundations::telemetry::{TelemetryConfig, TelemetryRouteBody, TelemetryServerRoute, init};
+use futures_util::FutureExt;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn get(server_addr: SocketAddr, path: &str) -> reqwest::Result<reqwest::Response> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .build()?
+        .get(format!("http://{server_addr}{path}"))
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn telemetry_server_limits() {
+    let settings = TelemetrySettings {
+        server: TelemetryServerSettings {
+            enabled: true,
+            addr: ListenAddr::Tcp((Ipv4Addr::LOCALHOST, 0).into()),
+            max_connections: 1,
+            idle_timeout_seconds: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let driver = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![TelemetryServerRoute {
+            path: "/slow".into(),
+            methods: vec![Method::GET],
+            handler: Box::new(|_, _| {
+                async {
+                    // NOTE: the handler doesn't do any I/O for longer than two idle timeouts.
+                    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+                    Ok(Response::new(TelemetryRouteBody::new(
+                        Full::from("done").map_err(Into::into),
+                    )))
+                }
+                .boxed()
+            }),
+        }],
+    })
+    .unwrap();
+
+    let Some(ListenAddr::Tcp(server_addr)) = driver.server_addr().cloned() else {
+        panic!("telemetry server should listen on a TCP socket");
+    };
+
+    tokio::spawn(driver);
+
+    // Occupy the only connection slot with an idle connection.
+    let mut idle_conn = TcpStream::connect(server_addr).await.unwrap();
+
+    // Give the server some time to accept the connection.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(get(server_addr, "/health").await.is_err());
+
+    // The idle connection gets closed by the server.
+    let mut buf = [0; 16];
+    let bytes_read = tokio::time::timeout(Duration::from_secs(5), idle_conn.read(&mut buf))
+        .await
+        .expect("idle connection should be closed by the server")
+        .unwrap();
+
+    assert_eq!(bytes_read, 0);
+
+    assert_eq!(get(server_addr, "/health").await.unwrap().status(), 200);
+    assert_eq!(get(server_addr, "/not-found").await.unwrap().status(), 404);
+
+    let metrics = get(server_addr, "/metrics")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(metrics.contains(r#"telemetry_server_requests_total{route="/health",status="200"} 1"#));
+    assert!(
+        metrics.contains(r#"telemetry_server_requests_total{route="unmatched",status="404"} 1"#)
+    );
+    assert!(metrics.contains("telemetry_server_connections_active 1"));
+
+    // A request that takes longer than the idle timeout is not cut off.
+    let mut slow_conn = TcpStream::connect(server_addr).await.unwrap();
+
+    slow_conn
+        .write_all(b"GET /slow HTTP/1.1\r\nhost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut buf = [0; 16];
+    let bytes_read = tokio::time::timeout(Duration::from_secs(5), slow_conn.read(&mut buf))
+        .await
+        .expect("slow request should complete")
+        .unwrap();
+
+    assert!(buf[..bytes_read].starts_with(b"HTTP/1.1 200"));
+
+    // The connection gets closed by the server once it's idle again.
+    let mut buf = [0; 1024];
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        while slow_conn.read(&mut buf).await.unwrap() > 0 {}
+    })
+    .await;
+
+    assert!(
+        closed.is_ok(),
+        "idle connection should be closed by the server"
+    );
+
+    assert_eq!(get(server_addr, "/health").await.unwrap().status(), 200);
+}
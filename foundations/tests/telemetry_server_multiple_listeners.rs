@@ -0,0 +1,85 @@
+#![cfg(unix)]
+
+use foundations::addr::ListenAddr;
+use foundations::telemetry::settings::{
+    TelemetryServerListenerSettings, TelemetryServerSettings, TelemetrySettings,
+};
+use foundations::telemetry::{TelemetryConfig, init};
+use std::net::Ipv4Addr;
+use std::path::Path;
+use tempfile::tempdir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+async fn unix_socket_get(socket_path: &Path, path: &str) -> String {
+    let mut stream = UnixStream::connect(socket_path).await.unwrap();
+
+    stream
+        .write_all(format!("GET {path} HTTP/1.1\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+    stream.flush().await.unwrap();
+
+    let mut buf = vec![0; 4096];
+    let bytes_read = stream.read(&mut buf).await.unwrap();
+
+    String::from_utf8_lossy(&buf[..bytes_read]).into_owned()
+}
+
+#[tokio::test]
+async fn telemetry_server_multiple_listeners() {
+    let temp_dir = tempdir().unwrap();
+    let socket_path = temp_dir.path().join("telemetry.sock");
+
+    let settings = TelemetrySettings {
+        server: TelemetryServerSettings {
+            enabled: true,
+            addr: ListenAddr::Tcp((Ipv4Addr::LOCALHOST, 0).into()),
+            additional_listeners: vec![TelemetryServerListenerSettings {
+                addr: ListenAddr::Unix(socket_path.clone()),
+                routes: Some(vec!["/health".into()]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let driver = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![],
+    })
+    .unwrap();
+
+    let server_addrs = driver.server_addrs().to_vec();
+
+    assert_eq!(server_addrs.len(), 2);
+    assert!(matches!(&server_addrs[1], ListenAddr::Unix(path) if *path == socket_path));
+
+    let Some(ListenAddr::Tcp(tcp_addr)) = driver.server_addr().cloned() else {
+        panic!("main listener should be a TCP listener");
+    };
+
+    tokio::spawn(driver);
+
+    assert_eq!(
+        reqwest::get(format!("http://{tcp_addr}/metrics"))
+            .await
+            .unwrap()
+            .status(),
+        200
+    );
+
+    assert!(
+        unix_socket_get(&socket_path, "/health")
+            .await
+            .contains("200 OK")
+    );
+
+    assert!(
+        unix_socket_get(&socket_path, "/metrics")
+            .await
+            .contains("404 Not Found")
+    );
+}
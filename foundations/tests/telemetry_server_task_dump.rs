@@ -0,0 +1,83 @@
+#![cfg(foundations_tokio_task_dump)]
+
+use foundations::addr::ListenAddr;
+use foundations::telemetry::settings::{TelemetryServerSettings, TelemetrySettings};
+use foundations::telemetry::tokio_runtime_metrics::register_runtime;
+use foundations::telemetry::{TelemetryConfig, init};
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+
+#[inline(never)]
+async fn wait_for_notification(notify: Arc<Notify>) {
+    notify.notified().await;
+}
+
+#[tokio::test]
+async fn telemetry_server_task_dump() {
+    let settings = TelemetrySettings {
+        server: TelemetryServerSettings {
+            enabled: true,
+            addr: ListenAddr::Tcp((Ipv4Addr::LOCALHOST, 0).into()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let driver = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![],
+    })
+    .unwrap();
+
+    let Some(ListenAddr::Tcp(server_addr)) = driver.server_addr().cloned() else {
+        panic!("telemetry server should listen on a TCP socket");
+    };
+
+    tokio::spawn(driver);
+
+    register_runtime(Some("main".into()), None, &Handle::current());
+
+    let notify = Arc::new(Notify::new());
+
+    for _ in 0..3 {
+        tokio::spawn(wait_for_notification(Arc::clone(&notify)));
+    }
+
+    let dump = reqwest::get(format!("http://{server_addr}/debug/tasks"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(dump.starts_with("runtime name=main: "), "{dump}");
+    assert!(dump.contains("wait_for_notification"), "{dump}");
+
+    let dump = reqwest::get(format!("http://{server_addr}/debug/tasks?format=json"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let dump: serde_json::Value = serde_json::from_str(&dump).unwrap();
+
+    let group = dump[0]["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|group| {
+            group["trace"]
+                .as_str()
+                .unwrap()
+                .contains("wait_for_notification")
+        })
+        .unwrap();
+
+    assert_eq!(group["count"], 3);
+
+    notify.notify_waiters();
+}
@@ -0,0 +1,75 @@
+#![cfg(all(target_os = "linux", feature = "thread-dump"))]
+
+use foundations::addr::ListenAddr;
+use foundations::telemetry::settings::{
+    TelemetryServerSettings, TelemetrySettings, ThreadDumpSettings,
+};
+use foundations::telemetry::{TelemetryConfig, init};
+use std::net::Ipv4Addr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[inline(never)]
+fn wait_for_notification(receiver: mpsc::Receiver<()>) {
+    let _ = receiver.recv();
+}
+
+#[tokio::test]
+async fn telemetry_server_thread_dump() {
+    let settings = TelemetrySettings {
+        server: TelemetryServerSettings {
+            enabled: true,
+            addr: ListenAddr::Tcp((Ipv4Addr::LOCALHOST, 0).into()),
+            ..Default::default()
+        },
+        thread_dump: ThreadDumpSettings {
+            enabled: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let driver = init(TelemetryConfig {
+        service_info: &foundations::service_info!(),
+        settings: &settings,
+        custom_server_routes: vec![],
+    })
+    .unwrap();
+
+    let Some(ListenAddr::Tcp(server_addr)) = driver.server_addr().cloned() else {
+        panic!("telemetry server should listen on a TCP socket");
+    };
+
+    tokio::spawn(driver);
+
+    let (sender, receiver) = mpsc::channel();
+
+    let blocked_thread = thread::Builder::new()
+        .name("blocked-thread".into())
+        .spawn(move || wait_for_notification(receiver))
+        .unwrap();
+
+    // Give the thread some time to block.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let dump = reqwest::get(format!("http://{server_addr}/debug/threads"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    sender.send(()).unwrap();
+    blocked_thread.join().unwrap();
+
+    let thread_dump = dump
+        .split("\n\n")
+        .find(|thread| thread.starts_with("Thread \"blocked-thread\" (tid "))
+        .unwrap_or_else(|| panic!("dump should contain the blocked thread: {dump}"));
+
+    assert!(
+        thread_dump.contains("wait_for_notification"),
+        "{thread_dump}"
+    );
+}
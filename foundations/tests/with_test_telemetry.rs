@@ -1,5 +1,7 @@
-use foundations::telemetry::tracing::{self, test_trace};
-use foundations::telemetry::{TestTelemetryContext, with_test_telemetry};
+use foundations::telemetry::log::{info, warn};
+use foundations::telemetry::settings::{LogTraceCorrelationSettings, LoggingSettings};
+use foundations::telemetry::tracing::{self, TestTraceOptions, test_trace};
+use foundations::telemetry::{TestSnapshotOptions, TestTelemetryContext, with_test_telemetry};
 
 #[with_test_telemetry(tokio::test)]
 async fn wrap_tokio_test(ctx: TestTelemetryContext) {
@@ -39,3 +41,42 @@ fn wrap_rust_test(ctx: TestTelemetryContext) {
         }]
     );
 }
+
+#[with_test_telemetry(test)]
+fn snapshot(mut ctx: TestTelemetryContext) {
+    ctx.set_logging_settings(LoggingSettings {
+        trace_correlation: LogTraceCorrelationSettings {
+            enabled: true,
+            include_unsampled: false,
+        },
+        ..Default::default()
+    });
+
+    info!("started"; "at" => "2024-01-02T03:04:05.678Z");
+
+    {
+        let _root = tracing::span("request");
+
+        tracing::add_span_tags!("request_id" => "67e55044-10b1-426f-9247-bb680e5fe0c8");
+
+        {
+            let _child = tracing::span("connect");
+
+            tracing::add_span_log_fields!("attempt" => "1");
+            warn!("connection failed"; "host" => "db", "secret" => "hunter2");
+        }
+    }
+
+    ctx.assert_snapshot(
+        "snapshot",
+        TestSnapshotOptions {
+            trace_options: TestTraceOptions {
+                include_logs: true,
+                include_tags: true,
+                ..Default::default()
+            },
+            masked_keys: vec!["secret".into()],
+            mask_ids: true,
+        },
+    );
+}